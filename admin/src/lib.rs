@@ -0,0 +1,321 @@
+//! Owner operations of the staking contract and their reports, used by the `staking-admin` binary.
+
+use anyhow::anyhow;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use near_sdk::Balance;
+use near_workspaces::result::ExecutionFinalResult;
+use staking_client::{apr_bps, reward_end_time_in_sec, ContractMetadata, PoolId, PoolView, StakingClient};
+use std::cmp::max;
+use std::convert::TryFrom;
+use std::fmt;
+
+const DURATION_1DAY_IN_SEC: u128 = 60 * 60 * 24;
+const NANOSEC_IN_SEC: u64 = 1_000_000_000;
+
+/// Reward stream of a pool, enough to project its rate and runway
+#[derive(Clone, Debug, PartialEq)]
+pub struct RewardSchedule {
+    pub reward_per_sec: Balance,
+    // at call time, the amount of undistributed reward
+    pub undistributed_reward: Balance,
+    // at call time, the amount of staked token
+    pub locked_token_amount: Balance,
+    pub total_staked: Balance,
+    pub reward_genesis_time_in_sec: u32,
+    /// reward streams from this second
+    pub start_time_in_sec: u32,
+    /// performance fee in basis points, taken from the stakers reward
+    pub fee_bps: u32,
+}
+
+impl RewardSchedule {
+    pub fn from_metadata(metadata: &ContractMetadata, cur_time_in_sec: u32) -> Self {
+        Self {
+            reward_per_sec: metadata.reward_per_sec.0,
+            undistributed_reward: metadata.cur_undistributed_reward.0,
+            locked_token_amount: metadata.cur_locked_token_amount.0,
+            total_staked: metadata.total_staked.0,
+            reward_genesis_time_in_sec: metadata.reward_genesis_time_in_sec,
+            start_time_in_sec: max(cur_time_in_sec, metadata.prev_distribution_time_in_sec),
+            fee_bps: if metadata.treasury_id.is_some() {
+                metadata.performance_fee_bps
+            } else {
+                0
+            },
+        }
+    }
+
+    pub fn from_pool(pool: &PoolView, cur_time_in_sec: u32) -> Self {
+        Self {
+            reward_per_sec: pool.reward_per_sec.0,
+            undistributed_reward: pool.cur_undistributed_reward.0,
+            locked_token_amount: pool.cur_locked_token_amount.0,
+            total_staked: pool.total_staked.0,
+            reward_genesis_time_in_sec: pool.reward_genesis_time_in_sec,
+            start_time_in_sec: max(cur_time_in_sec, pool.prev_distribution_time_in_sec),
+            fee_bps: 0,
+        }
+    }
+
+    /// The second when undistributed reward runs out, None if nothing is streaming, the same as `get_reward_end_time_in_sec`
+    pub fn end_time_in_sec(&self) -> Option<u32> {
+        reward_end_time_in_sec(
+            self.reward_per_sec,
+            self.total_staked,
+            self.undistributed_reward,
+            max(self.start_time_in_sec, self.reward_genesis_time_in_sec),
+        )
+    }
+
+    pub fn daily_reward(&self) -> Balance {
+        self.reward_per_sec.saturating_mul(DURATION_1DAY_IN_SEC)
+    }
+
+    /// APR in basis points after the performance fee, 0 if nothing is streaming, the same as `get_apr_bps`
+    pub fn apr_bps(&self) -> Balance {
+        apr_bps(
+            self.reward_per_sec,
+            self.total_staked,
+            self.locked_token_amount,
+            self.undistributed_reward,
+            self.fee_bps as u128,
+        )
+    }
+}
+
+/// Reward stream before and after an owner operation
+#[derive(Clone, Debug, PartialEq)]
+pub struct Preview {
+    pub before: RewardSchedule,
+    pub after: RewardSchedule,
+}
+
+impl fmt::Display for Preview {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (before, after) = (&self.before, &self.after);
+        writeln!(f, "{:>62} -> after", "current")?;
+        writeln!(
+            f,
+            "{:<22}{:>40} -> {}",
+            "reward per sec:", before.reward_per_sec, after.reward_per_sec
+        )?;
+        writeln!(
+            f,
+            "{:<22}{:>40} -> {}",
+            "daily reward:",
+            before.daily_reward(),
+            after.daily_reward()
+        )?;
+        writeln!(
+            f,
+            "{:<22}{:>40} -> {}",
+            "undistributed reward:", before.undistributed_reward, after.undistributed_reward
+        )?;
+        writeln!(
+            f,
+            "{:<22}{:>40} -> {}",
+            "reward genesis time:",
+            format_time(before.reward_genesis_time_in_sec),
+            format_time(after.reward_genesis_time_in_sec)
+        )?;
+        writeln!(
+            f,
+            "{:<22}{:>40} -> {}",
+            "reward runs out at:",
+            format_end_time(before.end_time_in_sec()),
+            format_end_time(after.end_time_in_sec())
+        )?;
+        write!(
+            f,
+            "{:<22}{:>40} -> {}",
+            "APR:",
+            format_bps(before.apr_bps()),
+            format_bps(after.apr_bps())
+        )
+    }
+}
+
+/// Report of the contract state built from `contract_metadata`
+#[derive(Debug)]
+pub struct Status {
+    pub contract_id: String,
+    pub metadata: ContractMetadata,
+    pub schedule: RewardSchedule,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let metadata = &self.metadata;
+        writeln!(f, "{:<26}{}", "contract:", self.contract_id)?;
+        writeln!(f, "{:<26}{}", "version:", metadata.version)?;
+        writeln!(f, "{:<26}{}", "owner:", metadata.owner_id)?;
+        writeln!(f, "{:<26}{}", "token:", metadata.token_id)?;
+        writeln!(f, "{:<26}{}", "accounts:", metadata.account_number.0)?;
+        writeln!(f, "{:<26}{}", "total staked shares:", metadata.total_staked.0)?;
+        writeln!(f, "{:<26}{}", "locked token amount:", metadata.cur_locked_token_amount.0)?;
+        writeln!(f, "{:<26}{}", "undistributed reward:", metadata.cur_undistributed_reward.0)?;
+        writeln!(f, "{:<26}{}", "deferred reward:", metadata.deferred_reward.0)?;
+        writeln!(f, "{:<26}{}", "reward per sec:", metadata.reward_per_sec.0)?;
+        writeln!(f, "{:<26}{}", "daily reward:", self.schedule.daily_reward())?;
+        writeln!(
+            f,
+            "{:<26}{}",
+            "reward genesis time:",
+            format_time(metadata.reward_genesis_time_in_sec)
+        )?;
+        writeln!(
+            f,
+            "{:<26}{}",
+            "reward runs out at:",
+            format_end_time(self.schedule.end_time_in_sec())
+        )?;
+        write!(f, "{:<26}{}", "APR:", format_bps(self.schedule.apr_bps()))
+    }
+}
+
+/// Owner operations on behalf of the client account
+pub struct Admin {
+    client: StakingClient,
+}
+
+impl Admin {
+    pub fn new(client: StakingClient) -> Self {
+        Self { client }
+    }
+
+    pub fn client(&self) -> &StakingClient {
+        &self.client
+    }
+
+    /// Block time of the network, in seconds
+    pub async fn cur_time_in_sec(&self) -> anyhow::Result<u32> {
+        let (timestamp, _epoch_height) = self.client.current_env_data().await?;
+        Ok((timestamp / NANOSEC_IN_SEC) as u32)
+    }
+
+    pub async fn reward_schedule(&self, pool_id: Option<PoolId>) -> anyhow::Result<RewardSchedule> {
+        let cur_time_in_sec = self.cur_time_in_sec().await?;
+        match pool_id {
+            Some(pool_id) => {
+                let pool = self
+                    .client
+                    .get_pool(pool_id)
+                    .await?
+                    .ok_or_else(|| anyhow!("pool {} doesn't exist", pool_id))?;
+                Ok(RewardSchedule::from_pool(&pool, cur_time_in_sec))
+            }
+            None => Ok(RewardSchedule::from_metadata(
+                &self.client.contract_metadata().await?,
+                cur_time_in_sec,
+            )),
+        }
+    }
+
+    pub async fn status(&self) -> anyhow::Result<Status> {
+        let metadata = self.client.contract_metadata().await?;
+        let schedule = RewardSchedule::from_metadata(&metadata, self.cur_time_in_sec().await?);
+        Ok(Status {
+            contract_id: self.client.contract_id().to_string(),
+            metadata,
+            schedule,
+        })
+    }
+
+    pub async fn preview_reward_per_sec(&self, reward_per_sec: Balance, pool_id: Option<PoolId>) -> anyhow::Result<Preview> {
+        let before = self.reward_schedule(pool_id).await?;
+        let after = RewardSchedule {
+            reward_per_sec,
+            ..before.clone()
+        };
+        Ok(Preview { before, after })
+    }
+
+    pub async fn preview_reset_reward_genesis_time(
+        &self,
+        reward_genesis_time_in_sec: u32,
+        pool_id: Option<PoolId>,
+    ) -> anyhow::Result<Preview> {
+        let before = self.reward_schedule(pool_id).await?;
+        let after = RewardSchedule {
+            reward_genesis_time_in_sec,
+            start_time_in_sec: reward_genesis_time_in_sec,
+            ..before.clone()
+        };
+        Ok(Preview { before, after })
+    }
+
+    pub async fn preview_add_rewards(&self, amount: Balance) -> anyhow::Result<Preview> {
+        let before = self.reward_schedule(None).await?;
+        let after = RewardSchedule {
+            undistributed_reward: before.undistributed_reward + amount,
+            ..before.clone()
+        };
+        Ok(Preview { before, after })
+    }
+
+    pub async fn modify_reward_per_sec(
+        &self,
+        reward_per_sec: Balance,
+        distribute_before_change: bool,
+        pool_id: Option<PoolId>,
+    ) -> anyhow::Result<()> {
+        ensure_success(
+            self.client
+                .modify_reward_per_sec(reward_per_sec, distribute_before_change, pool_id)
+                .await?,
+        )
+    }
+
+    pub async fn reset_reward_genesis_time(&self, reward_genesis_time_in_sec: u32, pool_id: Option<PoolId>) -> anyhow::Result<()> {
+        ensure_success(
+            self.client
+                .reset_reward_genesis_time_in_sec(reward_genesis_time_in_sec, pool_id)
+                .await?,
+        )
+    }
+
+    pub async fn set_owner(&self, owner_id: &near_workspaces::AccountId) -> anyhow::Result<()> {
+        ensure_success(self.client.set_owner(owner_id).await?)
+    }
+
+    /// `ft_transfer_call` of the staked token as reward, from the client account
+    pub async fn add_rewards(&self, amount: Balance) -> anyhow::Result<()> {
+        ensure_success(self.client.add_rewards(amount).await?)
+    }
+}
+
+pub fn ensure_success(outcome: ExecutionFinalResult) -> anyhow::Result<()> {
+    outcome
+        .into_result()
+        .map(|_| ())
+        .map_err(|failure| anyhow!("transaction failed: {:?}", failure))
+}
+
+/// Parse `2022-01-22T01:00:00Z`, `2022-01-22 01:00:00` in UTC or a unix timestamp in seconds
+pub fn parse_time(time: &str) -> anyhow::Result<u32> {
+    let timestamp = if let Ok(timestamp) = time.parse::<u32>() {
+        return Ok(timestamp);
+    } else if let Ok(date_time) = DateTime::parse_from_rfc3339(time) {
+        date_time.timestamp()
+    } else {
+        NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S")
+            .map_err(|_| anyhow!("can't parse time {}, use 2022-01-22 01:00:00 or RFC 3339", time))?
+            .and_utc()
+            .timestamp()
+    };
+    u32::try_from(timestamp).map_err(|_| anyhow!("time {} is out of range", time))
+}
+
+pub fn format_time(timestamp_in_sec: u32) -> String {
+    DateTime::<Utc>::from_timestamp(timestamp_in_sec as i64, 0)
+        .map(|date_time| date_time.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+        .unwrap_or_else(|| timestamp_in_sec.to_string())
+}
+
+fn format_end_time(end_time_in_sec: Option<u32>) -> String {
+    end_time_in_sec.map(format_time).unwrap_or_else(|| "-".to_string())
+}
+
+fn format_bps(bps: Balance) -> String {
+    format!("{}.{:02}%", bps / 100, bps % 100)
+}
@@ -0,0 +1,183 @@
+use anyhow::{anyhow, bail};
+use clap::{Parser, Subcommand, ValueEnum};
+use near_sdk::Balance;
+use near_workspaces::types::SecretKey;
+use near_workspaces::{Account, AccountId, Network, Worker};
+use staking_admin::{parse_time, Admin};
+use staking_client::{PoolId, StakingClient};
+use std::path::PathBuf;
+
+/// Owner operations of the staking contract
+///
+/// Amounts are in the smallest units of the staked token, times are
+/// `2022-01-22 01:00:00` in UTC, RFC 3339 or unix seconds
+#[derive(Parser)]
+#[command(name = "staking-admin", version)]
+struct Cli {
+    #[arg(long, value_enum, default_value = "testnet")]
+    network: NetworkName,
+    /// RPC endpoint, e.g. a local sandbox
+    #[arg(long)]
+    rpc_url: Option<String>,
+    /// Account signing the transactions, the owner of the contract
+    #[arg(long, env = "STAKING_ACCOUNT_ID")]
+    account_id: AccountId,
+    /// Key file of the account, ~/.near-credentials/<network>/<account_id>.json by default
+    #[arg(long, conflicts_with = "secret_key")]
+    credentials: Option<PathBuf>,
+    #[arg(long, env = "STAKING_SECRET_KEY", hide_env_values = true)]
+    secret_key: Option<SecretKey>,
+    #[arg(long, env = "STAKING_CONTRACT_ID")]
+    contract_id: AccountId,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum NetworkName {
+    Testnet,
+    Mainnet,
+}
+
+impl NetworkName {
+    fn as_str(&self) -> &'static str {
+        match self {
+            NetworkName::Testnet => "testnet",
+            NetworkName::Mainnet => "mainnet",
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Initialize the contract, signed by the contract account
+    Init {
+        #[arg(long)]
+        owner_id: AccountId,
+        #[arg(long)]
+        token_id: AccountId,
+        #[arg(long)]
+        factory_id: Option<AccountId>,
+    },
+    /// Print the state of the contract
+    Status,
+    /// Change the reward rate
+    SetRewardPerSec {
+        reward_per_sec: Balance,
+        /// Distribute reward at the old rate before the change
+        #[arg(long)]
+        distribute_before_change: bool,
+        #[arg(long)]
+        pool_id: Option<PoolId>,
+        /// Print the rate and runway after the change without sending it
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Move the time reward starts streaming from
+    ResetGenesis {
+        #[arg(value_parser = parse_time)]
+        reward_genesis_time: u32,
+        #[arg(long)]
+        pool_id: Option<PoolId>,
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Transfer the ownership of the contract
+    SetOwner { owner_id: AccountId },
+    /// Top up undistributed reward of the main pool from the signing account
+    AddRewards {
+        amount: Balance,
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    match (cli.network, cli.rpc_url.clone()) {
+        (NetworkName::Testnet, Some(rpc_url)) => run(cli, near_workspaces::testnet().rpc_addr(&rpc_url).await?).await,
+        (NetworkName::Testnet, None) => run(cli, near_workspaces::testnet().await?).await,
+        (NetworkName::Mainnet, Some(rpc_url)) => run(cli, near_workspaces::mainnet().rpc_addr(&rpc_url).await?).await,
+        (NetworkName::Mainnet, None) => run(cli, near_workspaces::mainnet().await?).await,
+    }
+}
+
+async fn run(cli: Cli, worker: Worker<impl Network + 'static>) -> anyhow::Result<()> {
+    let account = match cli.secret_key {
+        Some(secret_key) => Account::from_secret_key(cli.account_id.clone(), secret_key, &worker),
+        None => {
+            let credentials = match cli.credentials {
+                Some(credentials) => credentials,
+                None => default_credentials(cli.network, &cli.account_id)?,
+            };
+            Account::from_file(&credentials, &worker).map_err(|err| anyhow!("can't read credentials {}: {}", credentials.display(), err))?
+        }
+    };
+
+    if let Command::Init {
+        owner_id,
+        token_id,
+        factory_id,
+    } = &cli.command
+    {
+        if account.id() != &cli.contract_id {
+            bail!("init must be signed by the contract account {}", cli.contract_id);
+        }
+        let client = StakingClient::new(account, cli.contract_id, token_id.clone());
+        staking_admin::ensure_success(client.init(owner_id, factory_id.as_ref()).await?)?;
+        println!("Initialized {}", client.contract_id());
+        return Ok(());
+    }
+
+    let admin = Admin::new(StakingClient::connect(account, cli.contract_id).await?);
+    match cli.command {
+        Command::Init { .. } => unreachable!(),
+        Command::Status => println!("{}", admin.status().await?),
+        Command::SetRewardPerSec {
+            reward_per_sec,
+            distribute_before_change,
+            pool_id,
+            dry_run,
+        } => {
+            println!("{}", admin.preview_reward_per_sec(reward_per_sec, pool_id).await?);
+            if !dry_run {
+                admin
+                    .modify_reward_per_sec(reward_per_sec, distribute_before_change, pool_id)
+                    .await?;
+                println!("Reward per sec is set to {}", reward_per_sec);
+            }
+        }
+        Command::ResetGenesis {
+            reward_genesis_time,
+            pool_id,
+            dry_run,
+        } => {
+            println!("{}", admin.preview_reset_reward_genesis_time(reward_genesis_time, pool_id).await?);
+            if !dry_run {
+                admin.reset_reward_genesis_time(reward_genesis_time, pool_id).await?;
+                println!("Reward genesis time is set to {}", staking_admin::format_time(reward_genesis_time));
+            }
+        }
+        Command::SetOwner { owner_id } => {
+            admin.set_owner(&owner_id).await?;
+            println!("Owner is set to {}", owner_id);
+        }
+        Command::AddRewards { amount, dry_run } => {
+            println!("{}", admin.preview_add_rewards(amount).await?);
+            if !dry_run {
+                admin.add_rewards(amount).await?;
+                println!("Added {} to rewards", amount);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn default_credentials(network: NetworkName, account_id: &AccountId) -> anyhow::Result<PathBuf> {
+    let home = std::env::var_os("HOME").ok_or_else(|| anyhow!("HOME is not set, pass --credentials"))?;
+    Ok(PathBuf::from(home)
+        .join(".near-credentials")
+        .join(network.as_str())
+        .join(format!("{}.json", account_id)))
+}
@@ -0,0 +1,126 @@
+use near_contract_standards::fungible_token::metadata::{FungibleTokenMetadata, FT_METADATA_SPEC};
+use near_gas::NearGas;
+use near_sdk::json_types::U128;
+use near_sdk::serde_json;
+use near_sdk::Balance;
+use near_workspaces::network::Sandbox;
+use near_workspaces::{types::NearToken, Account, Contract, Worker};
+use staking_admin::*;
+use staking_client::StakingClient;
+
+const CONTRACT_WASM_FILEPATH: &str = "../out/release.wasm";
+const FT_WASM_FILEPATH: &str = "../out/fungible_token.wasm";
+const REWARD_PER_SEC: Balance = 100_000;
+
+pub const TOKEN_DECIMALS: u8 = 24;
+pub const TOKEN_TOTAL_SUPPLY: Balance = 1_000_000_000 * 10u128.pow(TOKEN_DECIMALS as _);
+
+async fn init(worker: &Worker<Sandbox>) -> anyhow::Result<(Contract, Contract, Account)> {
+    let owner = worker.dev_create_account().await?;
+
+    let ft_wasm = std::fs::read(FT_WASM_FILEPATH)?;
+    let ft_contract = worker.dev_deploy(&ft_wasm).await?;
+    let _ = ft_contract
+        .call("new")
+        .args_json(serde_json::json!({
+            "owner_id": owner.id().to_string(),
+            "total_supply": U128::from(TOKEN_TOTAL_SUPPLY),
+            "metadata": FungibleTokenMetadata {
+                    spec: FT_METADATA_SPEC.to_string(),
+                    name: "Token".to_string(),
+                    symbol: "TOKEN".to_string(),
+                    icon: None,
+                    reference: None,
+                    reference_hash: None,
+                    decimals: TOKEN_DECIMALS,
+                }
+        }))
+        .gas(NearGas::from_tgas(100))
+        .transact()
+        .await?;
+
+    let contract_wasm = std::fs::read(CONTRACT_WASM_FILEPATH)?;
+    let contract = worker.dev_deploy(&contract_wasm).await?;
+
+    // the contract signs its own init, like `staking-admin init`
+    let client = StakingClient::new(contract.as_account().clone(), contract.id().clone(), ft_contract.id().clone());
+    ensure_success(client.init(owner.id(), None).await?)?;
+
+    let _ = owner
+        .call(ft_contract.id(), "storage_deposit")
+        .args_json(serde_json::json!({
+            "account_id": contract.id().to_string(),
+        }))
+        .gas(NearGas::from_tgas(100))
+        .deposit(NearToken::from_yoctonear(1250000000000000000000))
+        .transact()
+        .await?;
+
+    Ok((contract, ft_contract, owner))
+}
+
+#[tokio::test]
+async fn verify_admin() -> anyhow::Result<()> {
+    let worker: Worker<Sandbox> = near_workspaces::sandbox().await?;
+    let (contract, ft_contract, owner) = init(&worker).await?;
+    let admin = Admin::new(StakingClient::connect(owner.clone(), contract.id().clone()).await?);
+    assert_eq!(admin.client().token_id(), ft_contract.id());
+
+    let amount_100_tokens = NearToken::from_near(100).as_yoctonear();
+    let preview = admin.preview_add_rewards(amount_100_tokens).await?;
+    assert_eq!(
+        preview.after.undistributed_reward,
+        preview.before.undistributed_reward + amount_100_tokens
+    );
+    admin.add_rewards(amount_100_tokens).await?;
+    let _ = admin.client().stake(amount_100_tokens).await?;
+
+    // a dry run only previews the change
+    let preview = admin.preview_reward_per_sec(REWARD_PER_SEC, None).await?;
+    assert_eq!(preview.before.reward_per_sec, 0);
+    assert_eq!(preview.before.end_time_in_sec(), None);
+    assert_eq!(preview.after.reward_per_sec, REWARD_PER_SEC);
+    assert_eq!(
+        preview.after.end_time_in_sec(),
+        Some(preview.after.start_time_in_sec + (amount_100_tokens / REWARD_PER_SEC) as u32)
+    );
+    assert!(preview.after.apr_bps() > 0);
+    assert_eq!(admin.client().get_reward_per_sec(None).await?, 0);
+
+    admin.modify_reward_per_sec(REWARD_PER_SEC, false, None).await?;
+    assert_eq!(admin.client().get_reward_per_sec(None).await?, REWARD_PER_SEC);
+
+    let genesis = admin.cur_time_in_sec().await? + 1;
+    let preview = admin
+        .preview_reset_reward_genesis_time(parse_time(&format_time(genesis).replace(" UTC", ""))?, None)
+        .await?;
+    assert_eq!(preview.after.reward_genesis_time_in_sec, genesis);
+    admin.reset_reward_genesis_time(genesis, None).await?;
+
+    worker.fast_forward(100).await?;
+
+    let status = admin.status().await?;
+    assert_eq!(status.metadata.reward_genesis_time_in_sec, genesis);
+    assert!(
+        status.metadata.cur_locked_token_amount.0 > amount_100_tokens,
+        "ERR REWARD WAS NOT DISTRIBUTED"
+    );
+    assert_eq!(
+        status.schedule.end_time_in_sec(),
+        admin.client().get_reward_end_time_in_sec(None).await?
+    );
+    assert!(status.schedule.apr_bps() > 0);
+    assert!(status.to_string().contains(owner.id().as_str()));
+
+    // only the owner changes the contract
+    let user = worker.dev_create_account().await?;
+    let user_admin = Admin::new(admin.client().with_account(user.clone()));
+    assert!(user_admin.modify_reward_per_sec(0, true, None).await.is_err());
+
+    admin.set_owner(user.id()).await?;
+    assert_eq!(admin.status().await?.metadata.owner_id.as_str(), user.id().as_str());
+    assert!(admin.modify_reward_per_sec(0, true, None).await.is_err());
+    user_admin.modify_reward_per_sec(0, true, None).await?;
+
+    Ok(())
+}
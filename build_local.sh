@@ -1,6 +1,9 @@
 #!/bin/bash
 set -e
 
-RUSTFLAGS='-C link-arg=-s' cargo build --target wasm32-unknown-unknown --release
-mkdir -p ../out
-cp target/wasm32-unknown-unknown/release/staking.wasm ./out/local.wasm
+RUSTFLAGS='-C link-arg=-s' cargo build --target wasm32-unknown-unknown --release --workspace
+mkdir -p ./out
+cp target/wasm32-unknown-unknown/release/staking.wasm ./out/release.wasm
+cp target/wasm32-unknown-unknown/release/staking_factory.wasm ./out/factory_release.wasm
+cp target/wasm32-unknown-unknown/release/mock_dex.wasm ./out/mock_dex.wasm
+cp target/wasm32-unknown-unknown/release/mock_lending.wasm ./out/mock_lending.wasm
@@ -0,0 +1,14 @@
+#!/bin/bash
+set -e
+
+# The first upgradable release, the source of ./out/release_v1.wasm for verify_migrate_from_v1
+RELEASE_V1_COMMIT=ff51932da59dc920b1cf92789a5b02712ba7d43f
+
+DIR="$( cd "$( dirname "${BASH_SOURCE[0]}" )" >/dev/null 2>&1 && pwd )"
+WORKTREE="$(mktemp -d)"
+trap 'git -C "$DIR" worktree remove --force "$WORKTREE"' EXIT
+
+git -C "$DIR" worktree add --detach "$WORKTREE" $RELEASE_V1_COMMIT
+(cd "$WORKTREE" && RUSTFLAGS='-C link-arg=-s' cargo build --target wasm32-unknown-unknown --release -p staking)
+mkdir -p "$DIR/out"
+cp "$WORKTREE/target/wasm32-unknown-unknown/release/staking.wasm" "$DIR/out/release_v1.wasm"
@@ -0,0 +1,542 @@
+//! Typed client of the staking contract over near-workspaces.
+//! Requests, views and events use the types of the contract crate, so they can't drift from it.
+
+use near_sdk::json_types::U128;
+use near_sdk::serde::{de::DeserializeOwned, Serialize};
+use near_sdk::serde_json::{self, json};
+use near_sdk::Balance;
+use near_workspaces::result::ExecutionFinalResult;
+use near_workspaces::types::NearToken;
+use near_workspaces::{Account, AccountId};
+
+pub use staking::events::*;
+pub use staking::{
+    apr_bps, reward_end_time_in_sec, AccountPoints, BoostView, ContractMetadata, GrantView, PoolId, PoolView, PositionView,
+    QueuedWithdrawView, SeasonId, SeasonView, Slash, StrategyView, TokenReceiverMsg, UnstakeCooldown, VestingView, U256,
+};
+
+const NO_DEPOSIT: NearToken = NearToken::from_yoctonear(0);
+const ONE_YOCTO: NearToken = NearToken::from_yoctonear(1);
+const EVENT_JSON_PREFIX: &str = "EVENT_JSON:";
+
+/// Calls the staking contract `contract_id` on behalf of `account`
+#[derive(Clone)]
+pub struct StakingClient {
+    account: Account,
+    contract_id: AccountId,
+    token_id: AccountId,
+}
+
+impl StakingClient {
+    pub fn new(account: Account, contract_id: AccountId, token_id: AccountId) -> Self {
+        Self {
+            account,
+            contract_id,
+            token_id,
+        }
+    }
+
+    /// Client of an initialized contract, the staked token is read from `contract_metadata`
+    pub async fn connect(account: Account, contract_id: AccountId) -> anyhow::Result<Self> {
+        let metadata: ContractMetadata = account.view(&contract_id, "contract_metadata").args_json(json!({})).await?.json()?;
+        let token_id = metadata.token_id.as_str().parse()?;
+        Ok(Self::new(account, contract_id, token_id))
+    }
+
+    /// The same contract, called on behalf of another account
+    pub fn with_account(&self, account: Account) -> Self {
+        Self { account, ..self.clone() }
+    }
+
+    pub fn account(&self) -> &Account {
+        &self.account
+    }
+
+    pub fn contract_id(&self) -> &AccountId {
+        &self.contract_id
+    }
+
+    pub fn token_id(&self) -> &AccountId {
+        &self.token_id
+    }
+
+    /// `ft_transfer_call` of `token_id` to the contract with the given msg
+    pub async fn transfer_call(
+        &self,
+        token_id: &AccountId,
+        amount: Balance,
+        msg: &TokenReceiverMsg,
+    ) -> anyhow::Result<ExecutionFinalResult> {
+        Ok(self
+            .account
+            .call(token_id, "ft_transfer_call")
+            .args_json(json!({
+                "receiver_id": self.contract_id,
+                "amount": U128(amount),
+                "msg": serde_json::to_string(msg)?,
+            }))
+            .deposit(ONE_YOCTO)
+            .max_gas()
+            .transact()
+            .await?)
+    }
+
+    pub async fn stake(&self, amount: Balance) -> anyhow::Result<ExecutionFinalResult> {
+        self.transfer_call(
+            &self.token_id,
+            amount,
+            &TokenReceiverMsg::Stake {
+                referrer_id: None,
+                pool_id: None,
+            },
+        )
+        .await
+    }
+
+    pub async fn add_rewards(&self, amount: Balance) -> anyhow::Result<ExecutionFinalResult> {
+        self.transfer_call(&self.token_id, amount, &TokenReceiverMsg::AddRewards { pool_id: None })
+            .await
+    }
+
+    pub async fn add_boost_rewards(&self, amount: Balance) -> anyhow::Result<ExecutionFinalResult> {
+        self.transfer_call(&self.token_id, amount, &TokenReceiverMsg::AddBoostRewards).await
+    }
+
+    /// Send `amount` of an approved reward token, it's swapped to the staked token for at least `min_amount_out`
+    pub async fn swap_rewards(
+        &self,
+        token_id: &AccountId,
+        amount: Balance,
+        min_amount_out: Balance,
+    ) -> anyhow::Result<ExecutionFinalResult> {
+        self.transfer_call(
+            token_id,
+            amount,
+            &TokenReceiverMsg::SwapRewards {
+                min_amount_out: U128(min_amount_out),
+            },
+        )
+        .await
+    }
+
+    /// `nft_transfer_call` of the NFT of `collection_id` to the contract, it boosts the reward weight of the account
+    pub async fn deposit_boost_nft(&self, collection_id: &AccountId, token_id: &str) -> anyhow::Result<ExecutionFinalResult> {
+        Ok(self
+            .account
+            .call(collection_id, "nft_transfer_call")
+            .args_json(json!({
+                "receiver_id": self.contract_id,
+                "token_id": token_id,
+                "msg": "",
+            }))
+            .deposit(ONE_YOCTO)
+            .max_gas()
+            .transact()
+            .await?)
+    }
+
+    /// Take back the NFT of `collection_id` and its boost
+    pub async fn nft_withdraw(&self, collection_id: &AccountId) -> anyhow::Result<ExecutionFinalResult> {
+        self.call("nft_withdraw", json!({ "collection_id": collection_id })).await
+    }
+
+    /// Send the boost reward of the account and of its positions in `token_ids`
+    pub async fn claim_boost_reward(&self, token_ids: Option<&[&str]>) -> anyhow::Result<ExecutionFinalResult> {
+        self.call("claim_boost_reward", json!({ "token_ids": token_ids })).await
+    }
+
+    /// Pay queued withdraws, or take tokens for them back from the strategy
+    pub async fn process_withdraw_queue(&self) -> anyhow::Result<ExecutionFinalResult> {
+        self.call_with_deposit("process_withdraw_queue", json!({}), NO_DEPOSIT).await
+    }
+
+    /// Start the unstake cooldown of the account
+    pub async fn start_unstake_cooldown(&self) -> anyhow::Result<ExecutionFinalResult> {
+        self.call_with_deposit("start_unstake_cooldown", json!({}), NO_DEPOSIT).await
+    }
+
+    /// Take `amount` of locked tokens to `receiver_id`, called by the slasher
+    pub async fn slash(&self, amount: Balance, receiver_id: &AccountId, reason: Option<&str>) -> anyhow::Result<ExecutionFinalResult> {
+        self.call(
+            "slash",
+            json!({ "amount": U128(amount), "receiver_id": receiver_id, "reason": reason }),
+        )
+        .await
+    }
+
+    /// Move the strategy yield to undistributed reward
+    pub async fn harvest_strategy(&self) -> anyhow::Result<ExecutionFinalResult> {
+        self.call_with_deposit("harvest_strategy", json!({}), NO_DEPOSIT).await
+    }
+
+    /// Stake `amount` for the beneficiary, its shares unlock linearly in `duration_in_sec` from now, nothing before the cliff
+    pub async fn grant_stake(
+        &self,
+        beneficiary_id: &AccountId,
+        amount: Balance,
+        cliff_duration_in_sec: u32,
+        duration_in_sec: u32,
+        revocable: bool,
+    ) -> anyhow::Result<ExecutionFinalResult> {
+        self.transfer_call(
+            &self.token_id,
+            amount,
+            &TokenReceiverMsg::GrantStake {
+                beneficiary_id: beneficiary_id.as_str().parse()?,
+                start_time_in_sec: None,
+                cliff_duration_in_sec,
+                duration_in_sec,
+                revocable,
+            },
+        )
+        .await
+    }
+
+    /// Stake `amount` locked for `lock_duration_in_sec`, the position NFT is minted to the client account
+    pub async fn lock_stake(&self, amount: Balance, lock_duration_in_sec: u32) -> anyhow::Result<ExecutionFinalResult> {
+        self.transfer_call(&self.token_id, amount, &TokenReceiverMsg::LockStake { lock_duration_in_sec })
+            .await
+    }
+
+    pub async fn merge_positions(&self, token_id: &str, other_token_id: &str) -> anyhow::Result<ExecutionFinalResult> {
+        self.call("merge_positions", json!({ "token_id": token_id, "other_token_id": other_token_id }))
+            .await
+    }
+
+    pub async fn split_position(&self, token_id: &str, shares: Balance) -> anyhow::Result<ExecutionFinalResult> {
+        self.call("split_position", json!({ "token_id": token_id, "shares": U128(shares) }))
+            .await
+    }
+
+    /// Unstake the position once its lock is expired
+    pub async fn unstake_position(&self, token_id: &str) -> anyhow::Result<ExecutionFinalResult> {
+        self.call("unstake_position", json!({ "token_id": token_id })).await
+    }
+
+    /// Unstake `amount` of shares, or all of them
+    pub async fn unstake(&self, amount: Option<Balance>, pool_id: Option<PoolId>) -> anyhow::Result<ExecutionFinalResult> {
+        self.call(
+            "unstake",
+            json!({
+                "amount": amount.map(U128),
+                "pool_id": pool_id,
+            }),
+        )
+        .await
+    }
+
+    /// Send vested reward of unstaked shares
+    pub async fn claim_vested(&self) -> anyhow::Result<ExecutionFinalResult> {
+        self.call("claim_vested", json!({})).await
+    }
+
+    /// Move unvested shares of the grant to the treasury
+    pub async fn revoke_grant(&self, beneficiary_id: &AccountId) -> anyhow::Result<ExecutionFinalResult> {
+        self.call_with_deposit("revoke_grant", json!({ "beneficiary_id": beneficiary_id }), NO_DEPOSIT)
+            .await
+    }
+
+    pub async fn set_boost_collection(&self, collection_id: &AccountId, boost_bps: u32) -> anyhow::Result<ExecutionFinalResult> {
+        self.call_with_deposit(
+            "set_boost_collection",
+            json!({ "collection_id": collection_id, "boost_bps": boost_bps }),
+            NO_DEPOSIT,
+        )
+        .await
+    }
+
+    pub async fn set_swap_dex(&self, dex_id: Option<&AccountId>) -> anyhow::Result<ExecutionFinalResult> {
+        self.call_with_deposit("set_swap_dex", json!({ "dex_id": dex_id }), NO_DEPOSIT)
+            .await
+    }
+
+    pub async fn set_reward_token(&self, token_id: &AccountId, dex_pool_id: u64) -> anyhow::Result<ExecutionFinalResult> {
+        self.call_with_deposit(
+            "set_reward_token",
+            json!({ "token_id": token_id, "dex_pool_id": dex_pool_id }),
+            NO_DEPOSIT,
+        )
+        .await
+    }
+
+    pub async fn modify_boost_reward_per_sec(&self, reward_per_sec: Balance) -> anyhow::Result<ExecutionFinalResult> {
+        self.call_with_deposit(
+            "modify_boost_reward_per_sec",
+            json!({ "reward_per_sec": U128(reward_per_sec) }),
+            NO_DEPOSIT,
+        )
+        .await
+    }
+
+    pub async fn set_min_stake_amount(&self, min_stake_amount: Balance) -> anyhow::Result<ExecutionFinalResult> {
+        self.call_with_deposit(
+            "set_min_stake_amount",
+            json!({ "min_stake_amount": U128(min_stake_amount) }),
+            NO_DEPOSIT,
+        )
+        .await
+    }
+
+    pub async fn set_strategy(&self, strategy_id: Option<&AccountId>) -> anyhow::Result<ExecutionFinalResult> {
+        self.call_with_deposit("set_strategy", json!({ "strategy_id": strategy_id }), NO_DEPOSIT)
+            .await
+    }
+
+    pub async fn set_liquidity_buffer(&self, liquidity_buffer_bps: u32) -> anyhow::Result<ExecutionFinalResult> {
+        self.call_with_deposit(
+            "set_liquidity_buffer",
+            json!({ "liquidity_buffer_bps": liquidity_buffer_bps }),
+            NO_DEPOSIT,
+        )
+        .await
+    }
+
+    pub async fn deploy_to_strategy(&self, amount: Balance) -> anyhow::Result<ExecutionFinalResult> {
+        self.call("deploy_to_strategy", json!({ "amount": U128(amount) })).await
+    }
+
+    /// Record a loss of `amount` deployed tokens, it lowers locked tokens
+    pub async fn write_down_strategy(&self, amount: Balance) -> anyhow::Result<ExecutionFinalResult> {
+        self.call("write_down_strategy", json!({ "amount": U128(amount) })).await
+    }
+
+    pub async fn set_slasher(&self, slasher_id: Option<&AccountId>) -> anyhow::Result<ExecutionFinalResult> {
+        self.call_with_deposit("set_slasher", json!({ "slasher_id": slasher_id }), NO_DEPOSIT)
+            .await
+    }
+
+    pub async fn set_max_slash(&self, max_slash_bps: u32) -> anyhow::Result<ExecutionFinalResult> {
+        self.call_with_deposit("set_max_slash", json!({ "max_slash_bps": max_slash_bps }), NO_DEPOSIT)
+            .await
+    }
+
+    pub async fn set_unstake_cooldown(&self, cooldown_in_sec: u32, unstake_window_in_sec: u32) -> anyhow::Result<ExecutionFinalResult> {
+        self.call_with_deposit(
+            "set_unstake_cooldown",
+            json!({ "cooldown_in_sec": cooldown_in_sec, "unstake_window_in_sec": unstake_window_in_sec }),
+            NO_DEPOSIT,
+        )
+        .await
+    }
+
+    /// Initialize the contract, called on behalf of the contract account
+    pub async fn init(&self, owner_id: &AccountId, factory_id: Option<&AccountId>) -> anyhow::Result<ExecutionFinalResult> {
+        self.call_with_deposit(
+            "new",
+            json!({
+                "owner_id": owner_id,
+                "token_id": self.token_id,
+                "factory_id": factory_id,
+            }),
+            NO_DEPOSIT,
+        )
+        .await
+    }
+
+    pub async fn set_owner(&self, owner_id: &AccountId) -> anyhow::Result<ExecutionFinalResult> {
+        self.call_with_deposit("set_owner", json!({ "owner_id": owner_id }), NO_DEPOSIT)
+            .await
+    }
+
+    pub async fn modify_reward_per_sec(
+        &self,
+        reward_per_sec: Balance,
+        distribute_before_change: bool,
+        pool_id: Option<PoolId>,
+    ) -> anyhow::Result<ExecutionFinalResult> {
+        self.call_with_deposit(
+            "modify_reward_per_sec",
+            json!({
+                "reward_per_sec": U128(reward_per_sec),
+                "distribute_before_change": distribute_before_change,
+                "pool_id": pool_id,
+            }),
+            NO_DEPOSIT,
+        )
+        .await
+    }
+
+    pub async fn reset_reward_genesis_time_in_sec(
+        &self,
+        reward_genesis_time_in_sec: u32,
+        pool_id: Option<PoolId>,
+    ) -> anyhow::Result<ExecutionFinalResult> {
+        self.call_with_deposit(
+            "reset_reward_genesis_time_in_sec",
+            json!({
+                "reward_genesis_time_in_sec": reward_genesis_time_in_sec,
+                "pool_id": pool_id,
+            }),
+            NO_DEPOSIT,
+        )
+        .await
+    }
+
+    /// Call a method with 1 yoctoNEAR attached
+    pub async fn call<A: Serialize>(&self, method_name: &str, args: A) -> anyhow::Result<ExecutionFinalResult> {
+        self.call_with_deposit(method_name, args, ONE_YOCTO).await
+    }
+
+    pub async fn call_with_deposit<A: Serialize>(
+        &self,
+        method_name: &str,
+        args: A,
+        deposit: NearToken,
+    ) -> anyhow::Result<ExecutionFinalResult> {
+        Ok(self
+            .account
+            .call(&self.contract_id, method_name)
+            .args_json(args)
+            .deposit(deposit)
+            .max_gas()
+            .transact()
+            .await?)
+    }
+
+    pub async fn view<A: Serialize, T: DeserializeOwned>(&self, method_name: &str, args: A) -> anyhow::Result<T> {
+        Ok(self.account.view(&self.contract_id, method_name).args_json(args).await?.json()?)
+    }
+
+    pub async fn contract_metadata(&self) -> anyhow::Result<ContractMetadata> {
+        self.view("contract_metadata", json!({})).await
+    }
+
+    pub async fn get_shares(&self, account_id: &AccountId, pool_id: Option<PoolId>) -> anyhow::Result<Balance> {
+        self.view_balance("get_shares", json!({ "account_id": account_id, "pool_id": pool_id }))
+            .await
+    }
+
+    pub async fn get_total_staked(&self, pool_id: Option<PoolId>) -> anyhow::Result<Balance> {
+        self.view_balance("get_total_staked", json!({ "pool_id": pool_id })).await
+    }
+
+    pub async fn get_virtual_price(&self, pool_id: Option<PoolId>) -> anyhow::Result<Balance> {
+        self.view_balance("get_virtual_price", json!({ "pool_id": pool_id })).await
+    }
+
+    pub async fn get_virtual_price_at(&self, timestamp_in_sec: u32, pool_id: Option<PoolId>) -> anyhow::Result<Balance> {
+        self.view_balance(
+            "get_virtual_price_at",
+            json!({ "timestamp_in_sec": timestamp_in_sec, "pool_id": pool_id }),
+        )
+        .await
+    }
+
+    pub async fn get_apr_bps(&self, pool_id: Option<PoolId>) -> anyhow::Result<Balance> {
+        self.view_balance("get_apr_bps", json!({ "pool_id": pool_id })).await
+    }
+
+    pub async fn get_reward_end_time_in_sec(&self, pool_id: Option<PoolId>) -> anyhow::Result<Option<u32>> {
+        self.view("get_reward_end_time_in_sec", json!({ "pool_id": pool_id })).await
+    }
+
+    pub async fn get_reward_per_sec(&self, pool_id: Option<PoolId>) -> anyhow::Result<Balance> {
+        self.view_balance("get_reward_per_sec", json!({ "pool_id": pool_id })).await
+    }
+
+    pub async fn get_undistributed_reward(&self, pool_id: Option<PoolId>) -> anyhow::Result<Balance> {
+        self.view_balance("get_undistributed_reward", json!({ "pool_id": pool_id })).await
+    }
+
+    pub async fn get_deferred_reward(&self, pool_id: Option<PoolId>) -> anyhow::Result<Balance> {
+        self.view_balance("get_deferred_reward", json!({ "pool_id": pool_id })).await
+    }
+
+    pub async fn get_unstake_available_time(&self, account_id: &AccountId, pool_id: Option<PoolId>) -> anyhow::Result<u32> {
+        self.view(
+            "get_unstake_available_time",
+            json!({ "account_id": account_id, "pool_id": pool_id }),
+        )
+        .await
+    }
+
+    /// Block timestamp in nanoseconds and epoch height
+    pub async fn current_env_data(&self) -> anyhow::Result<(u64, u64)> {
+        self.view("current_env_data", json!({})).await
+    }
+
+    pub async fn get_pool(&self, pool_id: PoolId) -> anyhow::Result<Option<PoolView>> {
+        self.view("get_pool", json!({ "pool_id": pool_id })).await
+    }
+
+    pub async fn get_pools(&self, from_index: Option<u64>, limit: Option<u64>) -> anyhow::Result<Vec<PoolView>> {
+        self.view("get_pools", json!({ "from_index": from_index, "limit": limit })).await
+    }
+
+    /// Points of the account in the current season, shares × seconds
+    pub async fn get_points(&self, account_id: &AccountId) -> anyhow::Result<U256> {
+        self.view("get_points", json!({ "account_id": account_id })).await
+    }
+
+    pub async fn get_season_points(&self, account_id: &AccountId, season_id: SeasonId) -> anyhow::Result<U256> {
+        self.view("get_season_points", json!({ "account_id": account_id, "season_id": season_id }))
+            .await
+    }
+
+    pub async fn get_points_ranking(&self, from_index: Option<u64>, limit: Option<u64>) -> anyhow::Result<Vec<AccountPoints>> {
+        self.view("get_points_ranking", json!({ "from_index": from_index, "limit": limit }))
+            .await
+    }
+
+    /// The current season without `season_id`
+    pub async fn get_season(&self, season_id: Option<SeasonId>) -> anyhow::Result<SeasonView> {
+        self.view("get_season", json!({ "season_id": season_id })).await
+    }
+
+    pub async fn get_position(&self, token_id: &str) -> anyhow::Result<Option<PositionView>> {
+        self.view("get_position", json!({ "token_id": token_id })).await
+    }
+
+    pub async fn get_grant(&self, account_id: &AccountId) -> anyhow::Result<Option<GrantView>> {
+        self.view("get_grant", json!({ "account_id": account_id })).await
+    }
+
+    pub async fn get_vesting(&self, account_id: &AccountId) -> anyhow::Result<VestingView> {
+        self.view("get_vesting", json!({ "account_id": account_id })).await
+    }
+
+    pub async fn get_cost_basis(&self, account_id: &AccountId) -> anyhow::Result<Balance> {
+        self.view_balance("get_cost_basis", json!({ "account_id": account_id })).await
+    }
+
+    pub async fn get_boost(&self, account_id: &AccountId) -> anyhow::Result<BoostView> {
+        self.view("get_boost", json!({ "account_id": account_id })).await
+    }
+
+    pub async fn get_strategy(&self) -> anyhow::Result<StrategyView> {
+        self.view("get_strategy", json!({})).await
+    }
+
+    pub async fn get_withdraw_queue(&self, from_index: Option<u64>, limit: Option<u64>) -> anyhow::Result<Vec<QueuedWithdrawView>> {
+        self.view("get_withdraw_queue", json!({ "from_index": from_index, "limit": limit }))
+            .await
+    }
+
+    pub async fn get_unstake_cooldown(&self, account_id: &AccountId) -> anyhow::Result<UnstakeCooldown> {
+        self.view("get_unstake_cooldown", json!({ "account_id": account_id })).await
+    }
+
+    pub async fn get_slashes(&self, from_index: Option<u64>, limit: Option<u64>) -> anyhow::Result<Vec<Slash>> {
+        self.view("get_slashes", json!({ "from_index": from_index, "limit": limit })).await
+    }
+
+    pub async fn get_total_slashed(&self) -> anyhow::Result<Balance> {
+        self.view_balance("get_total_slashed", json!({})).await
+    }
+
+    async fn view_balance<A: Serialize>(&self, method_name: &str, args: A) -> anyhow::Result<Balance> {
+        self.view::<A, U128>(method_name, args).await.map(|balance| balance.0)
+    }
+}
+
+/// Events of the staking contract logged during the transaction, other logs are skipped
+pub fn events(outcome: &ExecutionFinalResult) -> Vec<Event> {
+    outcome
+        .logs()
+        .into_iter()
+        .filter_map(|log| log.strip_prefix(EVENT_JSON_PREFIX))
+        .filter_map(|event| serde_json::from_str::<EventLog>(event).ok())
+        .filter(|event| event.standard == EVENT_STANDARD)
+        .map(|event| event.event)
+        .collect()
+}
@@ -0,0 +1,134 @@
+use near_contract_standards::fungible_token::metadata::{FungibleTokenMetadata, FT_METADATA_SPEC};
+use near_gas::NearGas;
+use near_sdk::json_types::U128;
+use near_sdk::serde_json;
+use near_sdk::Balance;
+use near_workspaces::network::Sandbox;
+use near_workspaces::{types::NearToken, Account, Contract, Worker};
+use staking_client::*;
+
+const CONTRACT_WASM_FILEPATH: &str = "../out/release.wasm";
+const FT_WASM_FILEPATH: &str = "../out/fungible_token.wasm";
+const REWARD_PER_SEC: Balance = 100_000;
+
+const NANOSEC_IN_SEC: u64 = 1_000_000_000;
+
+pub const TOKEN_DECIMALS: u8 = 24;
+pub const TOKEN_TOTAL_SUPPLY: Balance = 1_000_000_000 * 10u128.pow(TOKEN_DECIMALS as _);
+
+async fn init(worker: &Worker<Sandbox>) -> anyhow::Result<(Contract, Contract, Account)> {
+    let owner = worker.dev_create_account().await?;
+
+    let ft_wasm = std::fs::read(FT_WASM_FILEPATH)?;
+    let ft_contract = worker.dev_deploy(&ft_wasm).await?;
+    let _ = ft_contract
+        .call("new")
+        .args_json(serde_json::json!({
+            "owner_id": owner.id().to_string(),
+            "total_supply": U128::from(TOKEN_TOTAL_SUPPLY),
+            "metadata": FungibleTokenMetadata {
+                    spec: FT_METADATA_SPEC.to_string(),
+                    name: "Token".to_string(),
+                    symbol: "TOKEN".to_string(),
+                    icon: None,
+                    reference: None,
+                    reference_hash: None,
+                    decimals: TOKEN_DECIMALS,
+                }
+        }))
+        .gas(NearGas::from_tgas(100))
+        .transact()
+        .await?;
+
+    let contract_wasm = std::fs::read(CONTRACT_WASM_FILEPATH)?;
+    let contract = worker.dev_deploy(&contract_wasm).await?;
+    let _ = contract
+        .call("new")
+        .args_json(serde_json::json!({
+            "owner_id": owner.id().to_string(),
+            "token_id": ft_contract.id().to_string()
+        }))
+        .gas(NearGas::from_tgas(100))
+        .transact()
+        .await?;
+
+    let _ = owner
+        .call(ft_contract.id(), "storage_deposit")
+        .args_json(serde_json::json!({
+            "account_id": contract.id().to_string(),
+        }))
+        .gas(NearGas::from_tgas(100))
+        .deposit(NearToken::from_yoctonear(1250000000000000000000))
+        .transact()
+        .await?;
+
+    Ok((contract, ft_contract, owner))
+}
+
+#[tokio::test]
+async fn verify_client() -> anyhow::Result<()> {
+    let worker: Worker<Sandbox> = near_workspaces::sandbox().await?;
+    let (contract, ft_contract, owner) = init(&worker).await?;
+    let client = StakingClient::new(owner.clone(), contract.id().clone(), ft_contract.id().clone());
+
+    let amount_100_tokens = NearToken::from_near(100).as_yoctonear();
+    let add_rewards = client.add_rewards(amount_100_tokens).await?;
+    assert_eq!(
+        events(&add_rewards),
+        vec![Event::AddRewards(vec![AccountAmountToken {
+            account_id: owner.id().as_str().parse()?,
+            amount: amount_100_tokens,
+            token_id: ft_contract.id().as_str().parse()?,
+        }])]
+    );
+
+    let stake = client.stake(amount_100_tokens).await?;
+    assert!(matches!(events(&stake).as_slice(), [Event::AddStake(_)]));
+    assert_eq!(client.get_shares(owner.id(), None).await?, amount_100_tokens);
+    assert_eq!(client.get_total_staked(None).await?, amount_100_tokens);
+
+    let (timestamp, _epoch_height): (u64, u64) = client.view("current_env_data", serde_json::json!({})).await?;
+    let _ = owner
+        .call(contract.id(), "modify_reward_per_sec")
+        .args_json(serde_json::json!({
+            "reward_per_sec": U128::from(REWARD_PER_SEC),
+            "distribute_before_change": false,
+        }))
+        .transact()
+        .await?;
+    let _ = owner
+        .call(contract.id(), "reset_reward_genesis_time_in_sec")
+        .args_json(serde_json::json!({
+            "reward_genesis_time_in_sec": timestamp / NANOSEC_IN_SEC + 1,
+        }))
+        .transact()
+        .await?;
+    assert_eq!(client.get_reward_per_sec(None).await?, REWARD_PER_SEC);
+
+    worker.fast_forward(100).await?;
+
+    let metadata = client.contract_metadata().await?;
+    assert_eq!(metadata.token_id.as_str(), ft_contract.id().as_str());
+    assert!(
+        metadata.cur_locked_token_amount.0 > amount_100_tokens,
+        "ERR REWARD WAS NOT DISTRIBUTED"
+    );
+    assert!(client.get_virtual_price(None).await? > 100_000_000);
+    assert!(client.get_apr_bps(None).await? > 0);
+    assert!(client.get_reward_end_time_in_sec(None).await?.is_some());
+
+    // keep one token staked
+    let amount_50_tokens = NearToken::from_near(50).as_yoctonear();
+    let unstake = client.unstake(Some(amount_50_tokens), None).await?;
+    assert!(unstake.is_success());
+    match events(&unstake).as_slice() {
+        [Event::WithdrawSucceeded(data)] => {
+            assert_eq!(data[0].account_id.as_str(), owner.id().as_str());
+            assert!(data[0].amount > amount_50_tokens, "ERR UNSTAKE WITHOUT REWARD");
+        }
+        events => panic!("ERR UNEXPECTED EVENTS {:?}", events),
+    }
+    assert_eq!(client.get_shares(owner.id(), None).await?, amount_100_tokens - amount_50_tokens);
+
+    Ok(())
+}
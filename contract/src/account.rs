@@ -1,32 +1,171 @@
+use crate::accounting::WeightStake;
 use crate::*;
+use near_sdk::collections::{LookupMap, UnorderedMap};
 
-const ERR_TOTAL_STAKED_OVERFLOW: &str = "Total staked overflow";
+/// Account data which doesn't fit into `shares`
+#[derive(BorshSerialize, BorshDeserialize, Clone, Default)]
+pub struct Account {
+    /// the last time the account staked tokens
+    pub last_stake_time_in_sec: Option<u32>,
+    /// shares minted within the holding period of the last stake, they can't be unstaked until it passes
+    pub holding_shares: Balance,
+    /// account which gets voting power of the shares
+    pub delegatee_id: Option<AccountId>,
+    /// account which earns a part of the reward, set on the first stake
+    pub referrer_id: Option<AccountId>,
+    /// share price at the last referral sync
+    pub referral_price: u128,
+    /// shares × seconds in `points_season_id` up to `points_time_in_sec`
+    pub points: U256,
+    pub points_season_id: SeasonId,
+    pub points_time_in_sec: u32,
+    /// staked token of the shares, the part of unstaked tokens above it is reward
+    pub cost_basis: Balance,
+    /// sum of boosts of the deposited NFTs, in basis points
+    pub nft_boost_bps: u32,
+    /// weight of the account in the boost reward stream
+    pub boost_stake: WeightStake,
+    /// when the account started its unstake cooldown
+    pub cooldown_start_time_in_sec: Option<u32>,
+    /// shares of the locked positions owned by the account, they are held by the contract but earn points here
+    pub position_shares: Balance,
+}
+
+/// Account of the layout before the upgrade to versioned accounts, see `OldContract`
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct AccountV1 {
+    pub last_stake_time_in_sec: Option<u32>,
+    pub entry_time_in_sec: Option<u32>,
+    pub delegatee_id: Option<AccountId>,
+    pub referrer_id: Option<AccountId>,
+    pub referral_price: u128,
+}
+
+/// Account in any layout, the older ones are converted on read
+#[derive(BorshSerialize, BorshDeserialize)]
+pub enum VAccount {
+    V1(AccountV1),
+    Current(Account),
+}
 
 #[near_bindgen]
 impl Contract {
     pub fn internal_deposit(&mut self, account_id: &AccountId, amount: Balance) {
-        let user_balance: Balance = self.shares.get(account_id).unwrap_or_default();
-        if let Some(new_balance) = amount.checked_add(user_balance) {
-            self.shares.insert(account_id, &new_balance);
-            self.total_staked = self
-                .total_staked
-                .checked_add(amount)
-                .unwrap_or_else(|| env::panic_str(ERR_TOTAL_STAKED_OVERFLOW));
-        } else {
-            env::panic_str("Balance overflow");
-        }
+        self.internal_sync_referral(account_id);
+        self.internal_accrue_points(account_id);
+        let new_balance = self.shares.deposit_shares(account_id, amount);
+        self.internal_checkpoint_shares(account_id);
+        self.internal_checkpoint_total_staked();
+        self.internal_update_boost_weight(account_id, new_balance);
+        self.internal_update_delegated_power(account_id, amount, true);
     }
 
     pub fn internal_withdraw(&mut self, account_id: &AccountId, amount: Balance) {
-        let user_balance: Balance = self.shares.get(account_id).unwrap_or_default();
-        if let Some(new_balance) = user_balance.checked_sub(amount) {
-            self.shares.insert(account_id, &new_balance);
-            self.total_staked = self
-                .total_staked
-                .checked_sub(amount)
-                .unwrap_or_else(|| env::panic_str(ERR_TOTAL_STAKED_OVERFLOW));
-        } else {
-            env::panic_str("The account doesn't have enough balance");
+        self.internal_sync_referral(account_id);
+        self.internal_accrue_points(account_id);
+        let new_balance = self.shares.withdraw_shares(account_id, amount);
+        self.internal_checkpoint_shares(account_id);
+        self.internal_checkpoint_total_staked();
+        self.internal_update_boost_weight(account_id, new_balance);
+        self.internal_update_delegated_power(account_id, amount, false);
+    }
+}
+
+impl Contract {
+    pub fn internal_get_account(&self, account_id: &AccountId) -> Account {
+        let v_account = self
+            .accounts
+            .get(account_id)
+            .or_else(|| self.legacy_accounts.get(account_id).map(VAccount::V1));
+        match v_account {
+            Some(VAccount::Current(account)) => account,
+            Some(VAccount::V1(account)) => self.internal_convert_account(account_id, account),
+            // every share change writes the account, so shares without it were minted before the upgrade
+            None if self.shares.get(account_id).unwrap_or_default() > 0 => self.internal_convert_account(
+                account_id,
+                AccountV1 {
+                    last_stake_time_in_sec: None,
+                    entry_time_in_sec: None,
+                    delegatee_id: None,
+                    referrer_id: None,
+                    referral_price: 0,
+                },
+            ),
+            None => Account::default(),
+        }
+    }
+
+    pub fn internal_set_account(&mut self, account_id: &AccountId, account: &Account) {
+        // the account moves out of the old layout on its first write
+        self.legacy_accounts.remove(account_id);
+        self.accounts.insert(account_id, &VAccount::Current(account.clone()));
+    }
+
+    /// Account of the old layout with the data it lacks. The shares are held and their value becomes
+    /// the cost basis, points accrue from the upgrade. The boost weight of the shares counts from the upgrade,
+    /// `migrate` adds all of them to the total weight.
+    fn internal_convert_account(&self, account_id: &AccountId, account: AccountV1) -> Account {
+        let shares = self.shares.get(account_id).unwrap_or_default();
+        Account {
+            last_stake_time_in_sec: account.last_stake_time_in_sec,
+            holding_shares: shares,
+            delegatee_id: account.delegatee_id,
+            referrer_id: account.referrer_id,
+            referral_price: account.referral_price,
+            cost_basis: self.ledger.tokens_to_unlock(shares),
+            boost_stake: WeightStake {
+                weight: shares,
+                ..Default::default()
+            },
+            ..Default::default()
         }
     }
 }
+
+/// Share balances of the accounts in a pool, shared by the main pool and the added pools.
+/// The share supply is updated by the ledger of the pool, `Ledger::stake`, `Ledger::unstake` or `Ledger::mint`.
+pub(crate) trait ShareBalances {
+    fn get_shares(&self, account_id: &AccountId) -> Balance;
+
+    fn set_shares(&mut self, account_id: &AccountId, balance: Balance);
+
+    /// Add `amount` to the shares of the account, returns its new balance
+    fn deposit_shares(&mut self, account_id: &AccountId, amount: Balance) -> Balance {
+        let new_balance = self
+            .get_shares(account_id)
+            .checked_add(amount)
+            .unwrap_or_else(|| env::panic_str("Balance overflow"));
+        self.set_shares(account_id, new_balance);
+        new_balance
+    }
+
+    /// Remove `amount` from the shares of the account, returns its new balance
+    fn withdraw_shares(&mut self, account_id: &AccountId, amount: Balance) -> Balance {
+        let new_balance = self
+            .get_shares(account_id)
+            .checked_sub(amount)
+            .unwrap_or_else(|| env::panic_str("The account doesn't have enough balance"));
+        self.set_shares(account_id, new_balance);
+        new_balance
+    }
+}
+
+impl ShareBalances for UnorderedMap<AccountId, Balance> {
+    fn get_shares(&self, account_id: &AccountId) -> Balance {
+        self.get(account_id).unwrap_or_default()
+    }
+
+    fn set_shares(&mut self, account_id: &AccountId, balance: Balance) {
+        self.insert(account_id, &balance);
+    }
+}
+
+impl ShareBalances for LookupMap<AccountId, Balance> {
+    fn get_shares(&self, account_id: &AccountId) -> Balance {
+        self.get(account_id).unwrap_or_default()
+    }
+
+    fn set_shares(&mut self, account_id: &AccountId, balance: Balance) {
+        self.insert(account_id, &balance);
+    }
+}
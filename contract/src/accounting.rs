@@ -0,0 +1,221 @@
+//! Share math of a pool, free of storage and `env`, so it can be simulated off-chain.
+//! Rounding always favours the pool: minted shares and unlocked tokens are rounded down,
+//! so rounding never drops the virtual price, only a slash does, and a stake followed by an unstake can't return more than it took.
+
+use crate::utils::U256;
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::Balance;
+use std::cmp::{max, min};
+
+const ERR_TOTAL_STAKED_OVERFLOW: &str = "Total staked overflow";
+
+/// Shares which must stay staked after an unstake, so the virtual price can't be pushed around by dust
+pub const MIN_TOTAL_STAKED: Balance = 10u128.pow(18);
+
+/// Virtual price of a share while nobody is staked
+pub const INITIAL_VIRTUAL_PRICE: Balance = 100_000_000;
+
+/// Reward stream and share supply of a pool
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Default)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct Ledger {
+    // at prev_distribution_time, reward token that haven't distribute yet
+    pub undistributed_reward: Balance,
+    /// at prev_distribution_time, backend staked token amount
+    pub locked_token_amount: Balance,
+    /// the previous distribution time in seconds
+    pub prev_distribution_time_in_sec: u32,
+    /// when would the reward starts to distribute
+    pub reward_genesis_time_in_sec: u32,
+    pub reward_per_sec: Balance,
+    /// reward which was not distributed since nobody was staked at that time
+    pub deferred_reward: Balance,
+    // total amount of staked token
+    pub total_staked: Balance,
+}
+
+impl Ledger {
+    pub fn new(reward_per_sec: Balance, reward_genesis_time_in_sec: u32) -> Self {
+        Self {
+            prev_distribution_time_in_sec: reward_genesis_time_in_sec,
+            reward_genesis_time_in_sec,
+            reward_per_sec,
+            ..Default::default()
+        }
+    }
+
+    /// reward released by the clock since prev_distribution_time, whether or not someone is staked
+    fn try_release_reward(&self, cur_timestamp_in_sec: u32) -> Balance {
+        if cur_timestamp_in_sec > self.reward_genesis_time_in_sec && cur_timestamp_in_sec > self.prev_distribution_time_in_sec {
+            let ideal_amount = self.reward_per_sec * (cur_timestamp_in_sec - self.prev_distribution_time_in_sec) as u128;
+            min(ideal_amount, self.undistributed_reward)
+        } else {
+            0
+        }
+    }
+
+    pub fn try_distribute_reward(&self, cur_timestamp_in_sec: u32) -> Balance {
+        if self.total_staked == 0 {
+            0
+        } else {
+            self.try_release_reward(cur_timestamp_in_sec)
+        }
+    }
+
+    /// reward released while nobody is staked, it stays in undistributed_reward
+    pub fn try_defer_reward(&self, cur_timestamp_in_sec: u32) -> Balance {
+        if self.total_staked == 0 {
+            self.try_release_reward(cur_timestamp_in_sec)
+        } else {
+            0
+        }
+    }
+
+    /// Move the reward released up to `cur_timestamp_in_sec` into locked_token_amount, returns its amount
+    pub fn distribute_reward(&mut self, cur_timestamp_in_sec: u32) -> Balance {
+        let new_reward = self.try_distribute_reward(cur_timestamp_in_sec);
+        self.undistributed_reward -= new_reward;
+        self.locked_token_amount += new_reward;
+        self.deferred_reward += self.try_defer_reward(cur_timestamp_in_sec);
+        self.prev_distribution_time_in_sec = max(cur_timestamp_in_sec, self.reward_genesis_time_in_sec);
+        new_reward
+    }
+
+    /// Shares minted for staking `amount`, one share per token for the first stake
+    pub fn shares_to_mint(&self, amount: Balance) -> Balance {
+        if self.total_staked == 0 {
+            return amount;
+        }
+        assert!(self.locked_token_amount > 0, "ERR_INTERNAL");
+        (U256::from(amount) * U256::from(self.total_staked) / U256::from(self.locked_token_amount)).as_u128()
+    }
+
+    /// Staked token unlocked by burning `shares`
+    pub fn tokens_to_unlock(&self, shares: Balance) -> Balance {
+        assert!(self.total_staked > 0, "ERR_EMPTY_TOTAL_SUPPLY");
+        (U256::from(shares) * U256::from(self.locked_token_amount) / U256::from(self.total_staked)).as_u128()
+    }
+
+    /// Lock staked `amount` and mint shares for it, returns the minted shares
+    pub fn stake(&mut self, amount: Balance) -> Balance {
+        let minted = self.shares_to_mint(amount);
+        self.locked_token_amount += amount;
+        self.mint(minted);
+        minted
+    }
+
+    /// Burn `shares` and unlock their tokens, `exit_fee_bps` of them stays locked.
+    /// Returns the unlocked tokens without the fee and the fee.
+    pub fn unstake(&mut self, shares: Balance, exit_fee_bps: u32) -> (Balance, Balance) {
+        let unlocked = self.tokens_to_unlock(shares);
+        let exit_fee = bps_of(unlocked, exit_fee_bps);
+        self.burn(shares);
+        assert!(self.total_staked >= MIN_TOTAL_STAKED, "ERR_KEEP_AT_LEAST_ONE_STAKED_TOKEN");
+        self.locked_token_amount -= unlocked - exit_fee;
+        (unlocked - exit_fee, exit_fee)
+    }
+
+    /// Add `shares` to the share supply
+    pub fn mint(&mut self, shares: Balance) {
+        self.total_staked = self.total_staked.checked_add(shares).expect(ERR_TOTAL_STAKED_OVERFLOW);
+    }
+
+    /// Remove `shares` from the share supply
+    pub fn burn(&mut self, shares: Balance) {
+        self.total_staked = self.total_staked.checked_sub(shares).expect(ERR_TOTAL_STAKED_OVERFLOW);
+    }
+
+    /// Virtual price once the reward up to `timestamp_in_sec` is distributed
+    pub fn virtual_price_at(&self, timestamp_in_sec: u32) -> Balance {
+        virtual_price(
+            self.locked_token_amount + self.try_distribute_reward(timestamp_in_sec),
+            self.total_staked,
+        )
+    }
+}
+
+/// Staked token per share, scaled by `INITIAL_VIRTUAL_PRICE`
+pub fn virtual_price(locked_token_amount: Balance, total_staked: Balance) -> Balance {
+    if total_staked == 0 {
+        return INITIAL_VIRTUAL_PRICE;
+    }
+    (U256::from(locked_token_amount) * U256::from(INITIAL_VIRTUAL_PRICE) / U256::from(total_staked)).as_u128()
+}
+
+/// `bps` basis points of `amount`, rounded down
+pub fn bps_of(amount: Balance, bps: u32) -> Balance {
+    (U256::from(amount) * U256::from(bps) / U256::from(crate::utils::FULL_BASIS_POINTS)).as_u128()
+}
+
+/// Precision of the reward per weight
+const REWARD_PER_WEIGHT_PRECISION: u128 = 1_000_000_000_000_000_000_000_000;
+
+/// Reward stream split by weight instead of shares, each holder keeps a `WeightStake`.
+/// Unlike `Ledger` the reward is not compounded, holders claim it.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Default)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct WeightLedger {
+    pub reward_per_sec: Balance,
+    // at prev_distribution_time, reward token that haven't distribute yet
+    pub undistributed_reward: Balance,
+    pub prev_distribution_time_in_sec: u32,
+    /// reward per weight distributed since the start, scaled by REWARD_PER_WEIGHT_PRECISION
+    pub reward_per_weight: U256,
+    pub total_weight: Balance,
+    /// distributed reward which is not claimed yet
+    pub owed_reward: Balance,
+}
+
+/// Weight of a holder and its reward settled up to `reward_per_weight_paid`
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Default)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct WeightStake {
+    pub weight: Balance,
+    pub reward_per_weight_paid: U256,
+    pub unclaimed: Balance,
+}
+
+impl WeightLedger {
+    /// Release the reward up to `cur_timestamp_in_sec`, nothing is released while the total weight is 0
+    pub fn distribute_reward(&mut self, cur_timestamp_in_sec: u32) {
+        if cur_timestamp_in_sec <= self.prev_distribution_time_in_sec {
+            return;
+        }
+        if self.total_weight > 0 {
+            let ideal_amount = self.reward_per_sec * (cur_timestamp_in_sec - self.prev_distribution_time_in_sec) as u128;
+            let reward = min(ideal_amount, self.undistributed_reward);
+            self.undistributed_reward -= reward;
+            self.owed_reward += reward;
+            self.reward_per_weight += U256::from(reward) * U256::from(REWARD_PER_WEIGHT_PRECISION) / U256::from(self.total_weight);
+        }
+        self.prev_distribution_time_in_sec = cur_timestamp_in_sec;
+    }
+
+    /// Move the reward of `stake` up to now into its unclaimed, rounded down
+    pub fn settle(&self, stake: &mut WeightStake) {
+        stake.unclaimed += (U256::from(stake.weight) * (self.reward_per_weight - stake.reward_per_weight_paid)
+            / U256::from(REWARD_PER_WEIGHT_PRECISION))
+        .as_u128();
+        stake.reward_per_weight_paid = self.reward_per_weight;
+    }
+
+    pub fn set_weight(&mut self, stake: &mut WeightStake, weight: Balance) {
+        self.settle(stake);
+        self.total_weight = self.total_weight - stake.weight + weight;
+        stake.weight = weight;
+    }
+
+    /// Take the unclaimed reward of `stake`
+    pub fn claim(&mut self, stake: &mut WeightStake) -> Balance {
+        self.settle(stake);
+        let amount = stake.unclaimed;
+        stake.unclaimed = 0;
+        self.owed_reward -= amount;
+        amount
+    }
+}
+
+/// Weight of `shares` raised by `boost_bps`, 0 keeps it equal to the shares
+pub fn boosted_weight(shares: Balance, boost_bps: u32) -> Balance {
+    shares + bps_of(shares, boost_bps)
+}
@@ -11,34 +11,82 @@ impl Contract {
         self.owner_id.clone()
     }
 
-    pub fn modify_reward_per_sec(&mut self, reward_per_sec: WrappedBalance, distribute_before_change: bool) {
+    pub fn set_reward_manager(&mut self, reward_manager_id: Option<AccountId>) {
         self.assert_owner();
+        self.reward_manager_id = reward_manager_id;
+    }
+
+    pub fn get_reward_manager(&self) -> Option<AccountId> {
+        self.reward_manager_id.clone()
+    }
+
+    pub fn modify_reward_per_sec(&mut self, reward_per_sec: WrappedBalance, distribute_before_change: bool, pool_id: Option<PoolId>) {
+        self.assert_owner();
+        if let Some(pool_id) = pool_id {
+            return self.internal_modify_pool_reward_per_sec(pool_id, reward_per_sec.into(), distribute_before_change);
+        }
         if distribute_before_change {
             self.distribute_reward();
         }
-        self.reward_per_sec = reward_per_sec.into();
+        self.ledger.reward_per_sec = reward_per_sec.into();
     }
 
-    pub fn get_reward_per_sec(&self) -> WrappedBalance {
-        self.reward_per_sec.into()
+    pub fn get_reward_per_sec(&self, pool_id: Option<PoolId>) -> WrappedBalance {
+        match pool_id {
+            Some(pool_id) => self.internal_get_pool(pool_id).ledger.reward_per_sec.into(),
+            None => self.ledger.reward_per_sec.into(),
+        }
     }
 
-    pub fn reset_reward_genesis_time_in_sec(&mut self, reward_genesis_time_in_sec: u32) {
+    pub fn set_min_holding_period(&mut self, min_holding_period_in_sec: u32) {
+        self.assert_owner();
+        self.min_holding_period_in_sec = min_holding_period_in_sec;
+    }
+
+    pub fn get_min_holding_period(&self) -> u32 {
+        self.min_holding_period_in_sec
+    }
+
+    /// Set the smallest stake to the main pool, it bounds the checkpoints an account can add by dust stakes.
+    /// Requirements:
+    /// * Can only be called by the owner.
+    /// * `min_stake_amount` must not be 0.
+    pub fn set_min_stake_amount(&mut self, min_stake_amount: WrappedBalance) {
+        self.assert_owner();
+        assert!(min_stake_amount.0 > 0, "ERR_ZERO_AMOUNT");
+        self.min_stake_amount = min_stake_amount.into();
+    }
+
+    pub fn get_min_stake_amount(&self) -> WrappedBalance {
+        self.min_stake_amount.into()
+    }
+
+    pub fn reset_reward_genesis_time_in_sec(&mut self, reward_genesis_time_in_sec: u32, pool_id: Option<PoolId>) {
         self.assert_owner();
         let cur_time = nano_to_sec(env::block_timestamp());
         if reward_genesis_time_in_sec < cur_time {
             env::panic_str("ERR_RESET_TIME_IS_PAST_TIME");
-        } else if self.reward_genesis_time_in_sec < cur_time {
+        } else if let Some(pool_id) = pool_id {
+            return self.internal_reset_pool_reward_genesis_time_in_sec(pool_id, reward_genesis_time_in_sec);
+        } else if self.ledger.reward_genesis_time_in_sec < cur_time {
             env::panic_str("ERR_REWARD_GENESIS_TIME_PASSED");
         }
-        self.reward_genesis_time_in_sec = reward_genesis_time_in_sec;
-        self.prev_distribution_time_in_sec = reward_genesis_time_in_sec;
+        self.ledger.reward_genesis_time_in_sec = reward_genesis_time_in_sec;
+        self.ledger.prev_distribution_time_in_sec = reward_genesis_time_in_sec;
     }
 
     pub(crate) fn assert_owner(&self) {
         assert_eq!(env::predecessor_account_id(), self.owner_id, "ERR_NOT_AN_OWNER");
     }
 
+    pub(crate) fn assert_owner_or_reward_manager(&self) {
+        let predecessor_id = env::predecessor_account_id();
+        assert!(
+            predecessor_id == self.owner_id || Some(&predecessor_id) == self.reward_manager_id.as_ref(),
+            "ERR_NOT_AN_OWNER_OR_REWARD_MANAGER"
+        );
+    }
+
     pub fn current_env_data() -> (u64, u64) {
         let now = env::block_timestamp();
         let eh = env::epoch_height();
@@ -0,0 +1,287 @@
+use crate::accounting::{boosted_weight, bps_of};
+use crate::ft::{ext_self, GAS_FOR_AFTER_FT_TRANSFER, GAS_FOR_FT_TRANSFER};
+use crate::*;
+use near_contract_standards::fungible_token::core::ext_ft_core;
+use near_contract_standards::non_fungible_token::core::NonFungibleTokenReceiver;
+#[cfg(not(target_arch = "wasm32"))]
+use near_sdk::serde::Deserialize;
+use near_sdk::{assert_one_yocto, PromiseOrValue, PromiseResult, ONE_YOCTO};
+
+/// Hard cap of the boost of a collection, 1x on top of the shares
+pub const MAX_COLLECTION_BOOST_BPS: u32 = 10_000;
+
+const GAS_FOR_NFT_TRANSFER: Gas = Gas(Gas::ONE_TERA.0 * 20);
+const GAS_FOR_AFTER_NFT_TRANSFER: Gas = Gas(Gas::ONE_TERA.0 * 20);
+
+/// NFT deposited by an account, one per collection
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct BoostNft {
+    pub token_id: TokenId,
+    /// boost of the collection at deposit time
+    pub boost_bps: u32,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, Deserialize))]
+pub struct BoostView {
+    // sum of boosts of the deposited NFTs
+    pub nft_boost_bps: u32,
+    pub weight: WrappedBalance,
+    // at call time, boost reward which can be claimed, without the one of positions
+    pub unclaimed: WrappedBalance,
+}
+
+#[ext_contract(ext_nft)]
+#[allow(dead_code)]
+pub trait ExtNft {
+    fn nft_transfer(&mut self, receiver_id: AccountId, token_id: TokenId, approval_id: Option<u64>, memo: Option<String>);
+}
+
+#[near_bindgen]
+impl NonFungibleTokenReceiver for Contract {
+    /// Deposit an NFT of an approved collection, it raises the reward weight of `previous_owner_id`.
+    /// Requirements:
+    /// * The collection must be approved by the owner.
+    /// * The account must not have an NFT of the collection deposited or being withdrawn.
+    #[allow(unused_variables)]
+    fn nft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        previous_owner_id: AccountId,
+        token_id: TokenId,
+        msg: String,
+    ) -> PromiseOrValue<bool> {
+        let collection_id = env::predecessor_account_id();
+        let boost_bps = self.boost_collections.get(&collection_id).expect("ERR_COLLECTION_NOT_APPROVED");
+        let key = (previous_owner_id.clone(), collection_id.clone());
+        assert!(self.boost_nfts.get(&key).is_none(), "ERR_COLLECTION_ALREADY_DEPOSITED");
+        // a failed withdraw puts the pending NFT back
+        assert!(!self.pending_nft_withdraws.contains(&key), "ERR_NFT_WITHDRAW_PENDING");
+
+        self.boost_nfts.insert(
+            &key,
+            &BoostNft {
+                token_id: token_id.clone(),
+                boost_bps,
+            },
+        );
+        self.internal_add_nft_boost(&previous_owner_id, boost_bps, true);
+        events::emit::deposit_boost_nft(&previous_owner_id, &collection_id, &token_id, boost_bps);
+        PromiseOrValue::Value(false)
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Approve an NFT collection for boosts or change its boost, NFTs already deposited keep their boost.
+    /// Requirements:
+    /// * Can only be called by the owner.
+    /// * `boost_bps` must be positive and not exceed 10_000.
+    pub fn set_boost_collection(&mut self, collection_id: AccountId, boost_bps: u32) {
+        self.assert_owner();
+        assert!(boost_bps > 0 && boost_bps <= MAX_COLLECTION_BOOST_BPS, "ERR_BOOST_TOO_HIGH");
+        self.boost_collections.insert(&collection_id, &boost_bps);
+    }
+
+    /// Stop accepting NFTs of the collection, deposited ones keep their boost until withdrawn.
+    /// Requirements:
+    /// * Can only be called by the owner.
+    pub fn remove_boost_collection(&mut self, collection_id: AccountId) {
+        self.assert_owner();
+        self.boost_collections.remove(&collection_id);
+    }
+
+    pub fn get_boost_collections(&self) -> Vec<(AccountId, u32)> {
+        self.boost_collections.to_vec()
+    }
+
+    /// Change the boost reward stream.
+    /// Requirements:
+    /// * Can only be called by the owner.
+    pub fn modify_boost_reward_per_sec(&mut self, reward_per_sec: WrappedBalance) {
+        self.assert_owner();
+        self.boost_ledger.distribute_reward(nano_to_sec(env::block_timestamp()));
+        self.boost_ledger.reward_per_sec = reward_per_sec.into();
+    }
+
+    pub fn get_boost_reward_per_sec(&self) -> WrappedBalance {
+        self.boost_ledger.reward_per_sec.into()
+    }
+
+    /// Boost reward which is not distributed yet
+    pub fn get_boost_undistributed_reward(&self) -> WrappedBalance {
+        self.boost_ledger.undistributed_reward.into()
+    }
+
+    pub fn get_boost(&self, account_id: AccountId) -> BoostView {
+        let account = self.internal_get_account(&account_id);
+        let mut ledger = self.boost_ledger;
+        ledger.distribute_reward(nano_to_sec(env::block_timestamp()));
+        let mut stake = account.boost_stake;
+        ledger.settle(&mut stake);
+        BoostView {
+            nft_boost_bps: account.nft_boost_bps,
+            weight: stake.weight.into(),
+            unclaimed: stake.unclaimed.into(),
+        }
+    }
+
+    /// Token id of the NFT of the collection deposited by the account
+    pub fn get_boost_nft(&self, account_id: AccountId, collection_id: AccountId) -> Option<TokenId> {
+        self.boost_nfts.get(&(account_id, collection_id)).map(|nft| nft.token_id)
+    }
+
+    /// Send the deposited NFT of the collection back to the predecessor account and remove its boost.
+    /// Requirements:
+    /// * The predecessor account should have an NFT of the collection deposited.
+    /// * Requires attached deposit of exactly 1 yoctoNEAR.
+    #[payable]
+    pub fn nft_withdraw(&mut self, collection_id: AccountId) -> Promise {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let key = (account_id.clone(), collection_id.clone());
+        let nft = self.boost_nfts.remove(&key).expect("ERR_NO_BOOST_NFT");
+        self.pending_nft_withdraws.insert(&key);
+        self.internal_add_nft_boost(&account_id, nft.boost_bps, false);
+
+        ext_nft::ext(collection_id.clone())
+            .with_attached_deposit(ONE_YOCTO)
+            .with_static_gas(GAS_FOR_NFT_TRANSFER)
+            .nft_transfer(account_id.clone(), nft.token_id.clone(), None, None)
+            .then(
+                ext_self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_AFTER_NFT_TRANSFER)
+                    .callback_post_nft_withdraw(account_id, collection_id, nft.token_id, nft.boost_bps),
+            )
+    }
+
+    #[private]
+    pub fn callback_post_nft_withdraw(&mut self, account_id: AccountId, collection_id: AccountId, token_id: TokenId, boost_bps: u32) {
+        assert_eq!(env::promise_results_count(), 1, "Err: expected 1 promise result from nft_withdraw");
+        let key = (account_id.clone(), collection_id.clone());
+        self.pending_nft_withdraws.remove(&key);
+
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(_) => {
+                events::emit::withdraw_boost_nft(&account_id, &collection_id, &token_id, boost_bps);
+            }
+            PromiseResult::Failed => {
+                // This reverts the changes from nft_withdraw function, the NFT is still held by the contract.
+                // No NFT of the collection could be deposited meanwhile.
+                self.boost_nfts.insert(&key, &BoostNft { token_id, boost_bps });
+                self.internal_add_nft_boost(&account_id, boost_bps, true);
+                log!("Account {} NFT withdraw failed and reverted.", account_id);
+            }
+        };
+    }
+
+    /// Send the boost reward of the predecessor account and of its positions in `token_ids`.
+    /// Requirements:
+    /// * The predecessor account should own the positions.
+    /// * Requires attached deposit of exactly 1 yoctoNEAR.
+    #[payable]
+    pub fn claim_boost_reward(&mut self, token_ids: Option<Vec<TokenId>>) -> Promise {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        self.boost_ledger.distribute_reward(nano_to_sec(env::block_timestamp()));
+
+        let mut account = self.internal_get_account(&account_id);
+        let mut amount = self.boost_ledger.claim(&mut account.boost_stake);
+        self.internal_set_account(&account_id, &account);
+        for token_id in token_ids.unwrap_or_default() {
+            let mut position = self.internal_get_own_position(&account_id, &token_id);
+            amount += self.boost_ledger.claim(&mut position.boost_stake);
+            self.positions.insert(&token_id, &position);
+        }
+        assert!(amount > 0, "ERR_NOTHING_TO_CLAIM");
+        self.pending_transfer_amount += amount;
+
+        ext_ft_core::ext(self.token_id.clone())
+            .with_attached_deposit(ONE_YOCTO)
+            .with_static_gas(GAS_FOR_FT_TRANSFER)
+            .ft_transfer(account_id.clone(), amount.into(), None)
+            .then(
+                ext_self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_AFTER_FT_TRANSFER)
+                    .callback_post_claim_boost_reward(account_id, amount.into()),
+            )
+    }
+
+    #[private]
+    pub fn callback_post_claim_boost_reward(&mut self, account_id: AccountId, amount: WrappedBalance) {
+        assert_eq!(env::promise_results_count(), 1, "Err: expected 1 promise result from claim");
+        self.pending_transfer_amount -= amount.0;
+
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(_) => {
+                events::emit::claim_boost_reward_succeeded(&account_id, amount.0, &self.token_id);
+            }
+            PromiseResult::Failed => {
+                // This reverts the changes from claim_boost_reward function.
+                self.internal_add_boost_unclaimed(&account_id, amount.0);
+                log!("Account {} claim failed and reverted.", account_id);
+
+                events::emit::claim_boost_reward_failed(&account_id, amount.0, &self.token_id);
+            }
+        };
+    }
+}
+
+impl Contract {
+    pub(crate) fn internal_add_boost_reward(&mut self, account_id: &AccountId, amount: Balance) {
+        self.boost_ledger.undistributed_reward += amount;
+        log!("{} add {} assets as boost reward", account_id, amount);
+    }
+
+    /// Set the boost weight of the account to its `shares` raised by its NFT boost,
+    /// shares of positions held by the contract account are weighted by the positions
+    pub(crate) fn internal_update_boost_weight(&mut self, account_id: &AccountId, shares: Balance) {
+        if account_id == &env::current_account_id() {
+            return;
+        }
+        self.boost_ledger.distribute_reward(nano_to_sec(env::block_timestamp()));
+        let mut account = self.internal_get_account(account_id);
+        self.boost_ledger
+            .set_weight(&mut account.boost_stake, boosted_weight(shares, account.nft_boost_bps));
+        self.internal_set_account(account_id, &account);
+    }
+
+    /// Set the boost weight of the position to its shares raised by its lock boost
+    pub(crate) fn internal_update_position_weight(&mut self, position: &mut Position) {
+        self.boost_ledger.distribute_reward(nano_to_sec(env::block_timestamp()));
+        self.boost_ledger
+            .set_weight(&mut position.boost_stake, bps_of(position.shares, position.boost_bps));
+    }
+
+    /// Move the boost reward of a burned position to its owner, it can be claimed without the token
+    pub(crate) fn internal_close_position_stake(&mut self, owner_id: &AccountId, position: &mut Position) {
+        self.boost_ledger.distribute_reward(nano_to_sec(env::block_timestamp()));
+        self.boost_ledger.set_weight(&mut position.boost_stake, 0);
+        let unclaimed = std::mem::take(&mut position.boost_stake.unclaimed);
+        let mut account = self.internal_get_account(owner_id);
+        account.boost_stake.unclaimed += unclaimed;
+        self.internal_set_account(owner_id, &account);
+    }
+
+    fn internal_add_nft_boost(&mut self, account_id: &AccountId, boost_bps: u32, add: bool) {
+        let mut account = self.internal_get_account(account_id);
+        if add {
+            account.nft_boost_bps += boost_bps;
+        } else {
+            account.nft_boost_bps -= boost_bps;
+        }
+        self.internal_set_account(account_id, &account);
+        let shares = self.shares.get(account_id).unwrap_or_default();
+        self.internal_update_boost_weight(account_id, shares);
+    }
+
+    fn internal_add_boost_unclaimed(&mut self, account_id: &AccountId, amount: Balance) {
+        let mut account = self.internal_get_account(account_id);
+        account.boost_stake.unclaimed += amount;
+        self.boost_ledger.owed_reward += amount;
+        self.internal_set_account(account_id, &account);
+    }
+}
@@ -0,0 +1,88 @@
+use crate::*;
+use near_sdk::collections::Vector;
+use near_sdk::env::sha256_array;
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy)]
+pub struct Checkpoint {
+    pub timestamp_in_sec: u32,
+    pub value: Balance,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Voting shares of the account at the end of `timestamp_in_sec`, which must be in the past.
+    /// They include shares of its locked positions, the contract which holds them has none.
+    pub fn get_shares_at(&self, account_id: AccountId, timestamp_in_sec: u32) -> WrappedBalance {
+        assert_past_timestamp(timestamp_in_sec);
+        self.internal_shares_at(&account_id, timestamp_in_sec).into()
+    }
+
+    /// Total staked at the end of `timestamp_in_sec`, which must be in the past
+    pub fn get_total_staked_at(&self, timestamp_in_sec: u32) -> WrappedBalance {
+        assert_past_timestamp(timestamp_in_sec);
+        lookup_checkpoint(&self.total_staked_checkpoints, timestamp_in_sec).into()
+    }
+}
+
+impl Contract {
+    pub(crate) fn internal_shares_at(&self, account_id: &AccountId, timestamp_in_sec: u32) -> Balance {
+        self.shares_checkpoints
+            .get(account_id)
+            .map(|checkpoints| lookup_checkpoint(&checkpoints, timestamp_in_sec))
+            .unwrap_or_default()
+    }
+
+    /// Record the voting shares of the account after a change of its shares or position shares
+    pub(crate) fn internal_checkpoint_shares(&mut self, account_id: &AccountId) {
+        let shares = self.internal_points_shares(account_id, &self.internal_get_account(account_id));
+        let mut checkpoints = self.shares_checkpoints.get(account_id).unwrap_or_else(|| {
+            Vector::new(StorageKey::SharesCheckpointsPerAccount {
+                account_hash: sha256_array(account_id.as_bytes()),
+            })
+        });
+        push_checkpoint(&mut checkpoints, shares);
+        self.shares_checkpoints.insert(account_id, &checkpoints);
+    }
+
+    pub(crate) fn internal_checkpoint_total_staked(&mut self) {
+        let total_staked = self.ledger.total_staked;
+        push_checkpoint(&mut self.total_staked_checkpoints, total_staked);
+    }
+}
+
+fn assert_past_timestamp(timestamp_in_sec: u32) {
+    assert!(timestamp_in_sec < nano_to_sec(env::block_timestamp()), "ERR_TIMESTAMP_NOT_PAST");
+}
+
+/// Record `value` at the current time, several changes within a second keep only the last value
+fn push_checkpoint(checkpoints: &mut Vector<Checkpoint>, value: Balance) {
+    let checkpoint = Checkpoint {
+        timestamp_in_sec: nano_to_sec(env::block_timestamp()),
+        value,
+    };
+    let len = checkpoints.len();
+    if len > 0 && checkpoints.get(len - 1).unwrap().timestamp_in_sec == checkpoint.timestamp_in_sec {
+        checkpoints.replace(len - 1, &checkpoint);
+    } else {
+        checkpoints.push(&checkpoint);
+    }
+}
+
+/// Binary search of the last checkpoint at or before `timestamp_in_sec`
+fn lookup_checkpoint(checkpoints: &Vector<Checkpoint>, timestamp_in_sec: u32) -> Balance {
+    let mut low = 0;
+    let mut high = checkpoints.len();
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if checkpoints.get(mid).unwrap().timestamp_in_sec > timestamp_in_sec {
+            high = mid;
+        } else {
+            low = mid + 1;
+        }
+    }
+    if high == 0 {
+        0
+    } else {
+        checkpoints.get(high - 1).unwrap().value
+    }
+}
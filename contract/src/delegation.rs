@@ -0,0 +1,98 @@
+use crate::*;
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct VotingPower {
+    // shares of the account which are not delegated
+    pub own_power: WrappedBalance,
+    // shares delegated to the account by others
+    pub delegated_power: WrappedBalance,
+    pub total_power: WrappedBalance,
+    pub delegatee_id: Option<AccountId>,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Delegate voting power of all current and future shares of the predecessor account to `delegatee_id`.
+    pub fn delegate(&mut self, delegatee_id: AccountId) {
+        let account_id = env::predecessor_account_id();
+        assert_ne!(account_id, delegatee_id, "ERR_SELF_DELEGATION");
+
+        let mut account = self.internal_get_account(&account_id);
+        assert_ne!(account.delegatee_id.as_ref(), Some(&delegatee_id), "ERR_ALREADY_DELEGATED");
+        let shares = self.shares.get(&account_id).unwrap_or_default();
+        if let Some(prev_delegatee_id) = account.delegatee_id.take() {
+            self.internal_sub_delegated_power(&prev_delegatee_id, shares);
+            events::emit::undelegate(&account_id, &prev_delegatee_id, shares);
+        }
+        self.internal_add_delegated_power(&delegatee_id, shares);
+        account.delegatee_id = Some(delegatee_id.clone());
+        self.internal_set_account(&account_id, &account);
+
+        events::emit::delegate(&account_id, &delegatee_id, shares);
+    }
+
+    /// Take back voting power of the predecessor account.
+    pub fn undelegate(&mut self) {
+        let account_id = env::predecessor_account_id();
+
+        let mut account = self.internal_get_account(&account_id);
+        let delegatee_id = account.delegatee_id.take().expect("ERR_NOT_DELEGATED");
+        let shares = self.shares.get(&account_id).unwrap_or_default();
+        self.internal_sub_delegated_power(&delegatee_id, shares);
+        self.internal_set_account(&account_id, &account);
+
+        events::emit::undelegate(&account_id, &delegatee_id, shares);
+    }
+
+    pub fn get_voting_power(&self, account_id: AccountId) -> VotingPower {
+        let delegatee_id = self.internal_get_account(&account_id).delegatee_id;
+        let own_power = if delegatee_id.is_none() {
+            self.shares.get(&account_id).unwrap_or_default()
+        } else {
+            0
+        };
+        let delegated_power = self.delegated_power.get(&account_id).unwrap_or_default();
+        VotingPower {
+            own_power: own_power.into(),
+            delegated_power: delegated_power.into(),
+            total_power: (own_power + delegated_power).into(),
+            delegatee_id,
+        }
+    }
+
+    pub fn get_delegatee(&self, account_id: AccountId) -> Option<AccountId> {
+        self.internal_get_account(&account_id).delegatee_id
+    }
+}
+
+impl Contract {
+    /// Follow shares of `account_id` changed by `amount` in its delegatee power
+    pub(crate) fn internal_update_delegated_power(&mut self, account_id: &AccountId, amount: Balance, is_deposit: bool) {
+        if let Some(delegatee_id) = self.internal_get_account(account_id).delegatee_id {
+            if is_deposit {
+                self.internal_add_delegated_power(&delegatee_id, amount);
+            } else {
+                self.internal_sub_delegated_power(&delegatee_id, amount);
+            }
+        }
+    }
+
+    fn internal_add_delegated_power(&mut self, delegatee_id: &AccountId, amount: Balance) {
+        let power = self.delegated_power.get(delegatee_id).unwrap_or_default();
+        self.delegated_power.insert(delegatee_id, &(power + amount));
+    }
+
+    fn internal_sub_delegated_power(&mut self, delegatee_id: &AccountId, amount: Balance) {
+        let power = self.delegated_power.get(delegatee_id).unwrap_or_default();
+        let new_power = power
+            .checked_sub(amount)
+            .unwrap_or_else(|| env::panic_str("ERR_DELEGATED_POWER_UNDERFLOW"));
+        if new_power == 0 {
+            self.delegated_power.remove(delegatee_id);
+        } else {
+            self.delegated_power.insert(delegatee_id, &new_power);
+        }
+    }
+}
@@ -1,76 +1,516 @@
 use crate::*;
+#[cfg(not(target_arch = "wasm32"))]
+use near_sdk::serde::Deserialize;
+
+pub const EVENT_STANDARD: &str = "single-sided-staking";
+pub const EVENT_STANDARD_VERSION: &str = "1.0.0";
+
+/// `EVENT_JSON:` log of the contract
+#[derive(Serialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, Deserialize, PartialEq))]
+#[serde(crate = "near_sdk::serde")]
+pub struct EventLog {
+    pub standard: String,
+    pub version: String,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, Deserialize, PartialEq))]
+#[serde(crate = "near_sdk::serde", tag = "event", content = "data", rename_all = "snake_case")]
+pub enum Event {
+    AddStake(Vec<AccountAmountToken>),
+    AddRewards(Vec<AccountAmountToken>),
+    WithdrawFailed(Vec<AccountAmountToken>),
+    WithdrawSucceeded(Vec<AccountAmountToken>),
+    WithdrawReward(Vec<AccountAmountToken>),
+    WithdrawRewardSucceeded(Vec<AccountAmountToken>),
+    WithdrawRewardFailed(Vec<AccountAmountToken>),
+    PerformanceFee(Vec<AccountAmountSharesToken>),
+    ExitFee(Vec<AccountAmountToken>),
+    Delegate(Vec<AccountDelegateeShares>),
+    Undelegate(Vec<AccountDelegateeShares>),
+    CreatePoll(Vec<PollCreated>),
+    Vote(Vec<PollVote>),
+    LinkReferrer(Vec<AccountReferrer>),
+    AddReferralPool(Vec<AccountAmountToken>),
+    ReferralEarnings(Vec<AccountAmountToken>),
+    ClaimReferralEarningsSucceeded(Vec<AccountAmountToken>),
+    ClaimReferralEarningsFailed(Vec<AccountAmountToken>),
+    RecoverTokensSucceeded(Vec<AccountAmountToken>),
+    RecoverTokensFailed(Vec<AccountAmountToken>),
+    AddPool(Vec<PoolToken>),
+    PoolAddStake(Vec<PoolAmountToken>),
+    PoolAddRewards(Vec<PoolAmountToken>),
+    PoolWithdrawSucceeded(Vec<PoolAmountToken>),
+    PoolWithdrawFailed(Vec<PoolAmountToken>),
+    VestReward(Vec<AccountAmountToken>),
+    ClaimVestedSucceeded(Vec<AccountAmountToken>),
+    ClaimVestedFailed(Vec<AccountAmountToken>),
+    GrantStake(Vec<AccountAmountSharesToken>),
+    RevokeGrant(Vec<GrantRevoked>),
+    DepositBoostNft(Vec<AccountBoostNft>),
+    WithdrawBoostNft(Vec<AccountBoostNft>),
+    AddBoostRewards(Vec<AccountAmountToken>),
+    ClaimBoostRewardSucceeded(Vec<AccountAmountToken>),
+    ClaimBoostRewardFailed(Vec<AccountAmountToken>),
+    SwapRewardsSucceeded(Vec<RewardsSwapped>),
+    SwapRewardsFailed(Vec<AccountAmountToken>),
+    StrategyDeploy(Vec<AccountAmountToken>),
+    StrategyWithdraw(Vec<AccountAmountToken>),
+    StrategyHarvest(Vec<AccountAmountToken>),
+    StrategyWriteDown(Vec<AccountAmountToken>),
+    QueueWithdraw(Vec<AccountAmountToken>),
+    Slash(Vec<SlashMade>),
+    SlashSucceeded(Vec<AccountAmountToken>),
+    SlashFailed(Vec<AccountAmountToken>),
+    StartUnstakeCooldown(Vec<AccountCooldown>),
+}
+
+#[derive(Serialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, Deserialize, PartialEq))]
+#[serde(crate = "near_sdk::serde")]
+pub struct AccountAmountToken {
+    pub account_id: AccountId,
+    #[serde(with = "u128_dec_format")]
+    pub amount: Balance,
+    pub token_id: AccountId,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, Deserialize, PartialEq))]
+#[serde(crate = "near_sdk::serde")]
+pub struct AccountAmountSharesToken {
+    pub account_id: AccountId,
+    #[serde(with = "u128_dec_format")]
+    pub amount: Balance,
+    #[serde(with = "u128_dec_format")]
+    pub shares: Balance,
+    pub token_id: AccountId,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, Deserialize, PartialEq))]
+#[serde(crate = "near_sdk::serde")]
+pub struct AccountDelegateeShares {
+    pub account_id: AccountId,
+    pub delegatee_id: AccountId,
+    #[serde(with = "u128_dec_format")]
+    pub shares: Balance,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, Deserialize, PartialEq))]
+#[serde(crate = "near_sdk::serde")]
+pub struct PollCreated {
+    pub poll_id: PollId,
+    pub proposer_id: AccountId,
+    pub title: String,
+    pub options: Vec<String>,
+    pub snapshot_time_in_sec: u32,
+    pub deadline_in_sec: u32,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, Deserialize, PartialEq))]
+#[serde(crate = "near_sdk::serde")]
+pub struct PollVote {
+    pub poll_id: PollId,
+    pub account_id: AccountId,
+    pub option: u32,
+    #[serde(with = "u128_dec_format")]
+    pub weight: Balance,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, Deserialize, PartialEq))]
+#[serde(crate = "near_sdk::serde")]
+pub struct AccountReferrer {
+    pub account_id: AccountId,
+    pub referrer_id: AccountId,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, Deserialize, PartialEq))]
+#[serde(crate = "near_sdk::serde")]
+pub struct PoolToken {
+    pub pool_id: PoolId,
+    pub token_id: AccountId,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, Deserialize, PartialEq))]
+#[serde(crate = "near_sdk::serde")]
+pub struct PoolAmountToken {
+    pub pool_id: PoolId,
+    pub account_id: AccountId,
+    #[serde(with = "u128_dec_format")]
+    pub amount: Balance,
+    pub token_id: AccountId,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, Deserialize, PartialEq))]
+#[serde(crate = "near_sdk::serde")]
+pub struct GrantRevoked {
+    pub account_id: AccountId,
+    pub treasury_id: AccountId,
+    #[serde(with = "u128_dec_format")]
+    pub shares: Balance,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, Deserialize, PartialEq))]
+#[serde(crate = "near_sdk::serde")]
+pub struct AccountBoostNft {
+    pub account_id: AccountId,
+    pub collection_id: AccountId,
+    pub token_id: TokenId,
+    pub boost_bps: u32,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, Deserialize, PartialEq))]
+#[serde(crate = "near_sdk::serde")]
+pub struct RewardsSwapped {
+    pub account_id: AccountId,
+    pub token_in: AccountId,
+    #[serde(with = "u128_dec_format")]
+    pub amount_in: Balance,
+    #[serde(with = "u128_dec_format")]
+    pub amount: Balance,
+    pub token_id: AccountId,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, Deserialize, PartialEq))]
+#[serde(crate = "near_sdk::serde")]
+pub struct SlashMade {
+    pub slash_id: u64,
+    pub slasher_id: AccountId,
+    pub receiver_id: AccountId,
+    #[serde(with = "u128_dec_format")]
+    pub amount: Balance,
+    // locked tokens right before the slash
+    #[serde(with = "u128_dec_format")]
+    pub locked_token_amount: Balance,
+    pub token_id: AccountId,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, Deserialize, PartialEq))]
+#[serde(crate = "near_sdk::serde")]
+pub struct AccountCooldown {
+    pub account_id: AccountId,
+    pub unstake_start_time_in_sec: u32,
+}
 
 pub mod emit {
     use super::*;
-    use near_sdk::serde_json::json;
+    use near_sdk::serde_json;
 
-    #[derive(Serialize)]
-    #[serde(crate = "near_sdk::serde")]
-    struct AccountAmountToken<'a> {
-        pub account_id: &'a AccountId,
-        #[serde(with = "u128_dec_format")]
-        pub amount: Balance,
-        pub token_id: &'a AccountId,
+    fn log_event(event: Event) {
+        let event = EventLog {
+            standard: EVENT_STANDARD.to_string(),
+            version: EVENT_STANDARD_VERSION.to_string(),
+            event,
+        };
+
+        log!("EVENT_JSON:{}", serde_json::to_string(&event).unwrap());
+    }
+
+    fn account_amount_token(account_id: &AccountId, amount: Balance, token_id: &AccountId) -> Vec<AccountAmountToken> {
+        vec![AccountAmountToken {
+            account_id: account_id.clone(),
+            amount,
+            token_id: token_id.clone(),
+        }]
     }
 
-    fn log_event<T: Serialize>(event: &str, data: T) {
-        let event = json!({
-            "standard": "single-sided-staking",
-            "version": "1.0.0",
-            "event": event,
-            "data": [data]
-        });
+    fn pool_amount_token(pool_id: PoolId, account_id: &AccountId, amount: Balance, token_id: &AccountId) -> Vec<PoolAmountToken> {
+        vec![PoolAmountToken {
+            pool_id,
+            account_id: account_id.clone(),
+            amount,
+            token_id: token_id.clone(),
+        }]
+    }
 
-        log!("EVENT_JSON:{}", event.to_string());
+    fn account_boost_nft(account_id: &AccountId, collection_id: &AccountId, token_id: &TokenId, boost_bps: u32) -> Vec<AccountBoostNft> {
+        vec![AccountBoostNft {
+            account_id: account_id.clone(),
+            collection_id: collection_id.clone(),
+            token_id: token_id.clone(),
+            boost_bps,
+        }]
     }
 
     pub fn add_stake(account_id: &AccountId, amount: Balance, token_id: &AccountId) {
-        log_event(
-            "add_stake",
-            AccountAmountToken {
-                account_id,
-                amount,
-                token_id,
-            },
-        );
+        log_event(Event::AddStake(account_amount_token(account_id, amount, token_id)));
     }
 
     pub fn add_rewards(account_id: &AccountId, amount: Balance, token_id: &AccountId) {
-        log_event(
-            "add_rewards",
-            AccountAmountToken {
-                account_id,
-                amount,
-                token_id,
-            },
-        );
+        log_event(Event::AddRewards(account_amount_token(account_id, amount, token_id)));
     }
 
     pub fn withdraw_failed(account_id: &AccountId, amount: Balance, token_id: &AccountId) {
-        log_event(
-            "withdraw_failed",
-            AccountAmountToken {
-                account_id,
-                amount,
-                token_id,
-            },
-        );
+        log_event(Event::WithdrawFailed(account_amount_token(account_id, amount, token_id)));
     }
 
     pub fn withdraw_succeeded(account_id: &AccountId, amount: Balance, token_id: &AccountId) {
-        log_event(
-            "withdraw_succeeded",
-            AccountAmountToken {
-                account_id,
-                amount,
-                token_id,
-            },
-        );
+        log_event(Event::WithdrawSucceeded(account_amount_token(account_id, amount, token_id)));
+    }
+
+    pub fn withdraw_reward(account_id: &AccountId, amount: Balance, token_id: &AccountId) {
+        log_event(Event::WithdrawReward(account_amount_token(account_id, amount, token_id)));
+    }
+
+    pub fn withdraw_reward_succeeded(account_id: &AccountId, amount: Balance, token_id: &AccountId) {
+        log_event(Event::WithdrawRewardSucceeded(account_amount_token(account_id, amount, token_id)));
+    }
+
+    pub fn withdraw_reward_failed(account_id: &AccountId, amount: Balance, token_id: &AccountId) {
+        log_event(Event::WithdrawRewardFailed(account_amount_token(account_id, amount, token_id)));
+    }
+
+    pub fn performance_fee(account_id: &AccountId, amount: Balance, shares: Balance, token_id: &AccountId) {
+        log_event(Event::PerformanceFee(vec![AccountAmountSharesToken {
+            account_id: account_id.clone(),
+            amount,
+            shares,
+            token_id: token_id.clone(),
+        }]));
+    }
+
+    pub fn exit_fee(account_id: &AccountId, amount: Balance, token_id: &AccountId) {
+        log_event(Event::ExitFee(account_amount_token(account_id, amount, token_id)));
+    }
+
+    pub fn delegate(account_id: &AccountId, delegatee_id: &AccountId, shares: Balance) {
+        log_event(Event::Delegate(vec![AccountDelegateeShares {
+            account_id: account_id.clone(),
+            delegatee_id: delegatee_id.clone(),
+            shares,
+        }]));
+    }
+
+    pub fn undelegate(account_id: &AccountId, delegatee_id: &AccountId, shares: Balance) {
+        log_event(Event::Undelegate(vec![AccountDelegateeShares {
+            account_id: account_id.clone(),
+            delegatee_id: delegatee_id.clone(),
+            shares,
+        }]));
+    }
+
+    pub fn create_poll(poll_id: PollId, poll: &Poll) {
+        log_event(Event::CreatePoll(vec![PollCreated {
+            poll_id,
+            proposer_id: poll.proposer_id.clone(),
+            title: poll.title.clone(),
+            options: poll.options.clone(),
+            snapshot_time_in_sec: poll.snapshot_time_in_sec,
+            deadline_in_sec: poll.deadline_in_sec,
+        }]));
+    }
+
+    pub fn vote(poll_id: PollId, account_id: &AccountId, option: u32, weight: Balance) {
+        log_event(Event::Vote(vec![PollVote {
+            poll_id,
+            account_id: account_id.clone(),
+            option,
+            weight,
+        }]));
+    }
+
+    pub fn link_referrer(account_id: &AccountId, referrer_id: &AccountId) {
+        log_event(Event::LinkReferrer(vec![AccountReferrer {
+            account_id: account_id.clone(),
+            referrer_id: referrer_id.clone(),
+        }]));
+    }
+
+    pub fn add_referral_pool(account_id: &AccountId, amount: Balance, token_id: &AccountId) {
+        log_event(Event::AddReferralPool(account_amount_token(account_id, amount, token_id)));
+    }
+
+    pub fn referral_earnings(account_id: &AccountId, amount: Balance, token_id: &AccountId) {
+        log_event(Event::ReferralEarnings(account_amount_token(account_id, amount, token_id)));
+    }
+
+    pub fn claim_referral_earnings_succeeded(account_id: &AccountId, amount: Balance, token_id: &AccountId) {
+        log_event(Event::ClaimReferralEarningsSucceeded(account_amount_token(
+            account_id, amount, token_id,
+        )));
+    }
+
+    pub fn claim_referral_earnings_failed(account_id: &AccountId, amount: Balance, token_id: &AccountId) {
+        log_event(Event::ClaimReferralEarningsFailed(account_amount_token(
+            account_id, amount, token_id,
+        )));
+    }
+
+    pub fn vest_reward(account_id: &AccountId, amount: Balance, token_id: &AccountId) {
+        log_event(Event::VestReward(account_amount_token(account_id, amount, token_id)));
+    }
+
+    pub fn claim_vested_succeeded(account_id: &AccountId, amount: Balance, token_id: &AccountId) {
+        log_event(Event::ClaimVestedSucceeded(account_amount_token(account_id, amount, token_id)));
+    }
+
+    pub fn claim_vested_failed(account_id: &AccountId, amount: Balance, token_id: &AccountId) {
+        log_event(Event::ClaimVestedFailed(account_amount_token(account_id, amount, token_id)));
+    }
+
+    pub fn grant_stake(account_id: &AccountId, amount: Balance, shares: Balance, token_id: &AccountId) {
+        log_event(Event::GrantStake(vec![AccountAmountSharesToken {
+            account_id: account_id.clone(),
+            amount,
+            shares,
+            token_id: token_id.clone(),
+        }]));
+    }
+
+    pub fn revoke_grant(account_id: &AccountId, treasury_id: &AccountId, shares: Balance) {
+        log_event(Event::RevokeGrant(vec![GrantRevoked {
+            account_id: account_id.clone(),
+            treasury_id: treasury_id.clone(),
+            shares,
+        }]));
+    }
+
+    pub fn deposit_boost_nft(account_id: &AccountId, collection_id: &AccountId, token_id: &TokenId, boost_bps: u32) {
+        log_event(Event::DepositBoostNft(account_boost_nft(
+            account_id,
+            collection_id,
+            token_id,
+            boost_bps,
+        )));
+    }
+
+    pub fn withdraw_boost_nft(account_id: &AccountId, collection_id: &AccountId, token_id: &TokenId, boost_bps: u32) {
+        log_event(Event::WithdrawBoostNft(account_boost_nft(
+            account_id,
+            collection_id,
+            token_id,
+            boost_bps,
+        )));
+    }
+
+    pub fn add_boost_rewards(account_id: &AccountId, amount: Balance, token_id: &AccountId) {
+        log_event(Event::AddBoostRewards(account_amount_token(account_id, amount, token_id)));
+    }
+
+    pub fn claim_boost_reward_succeeded(account_id: &AccountId, amount: Balance, token_id: &AccountId) {
+        log_event(Event::ClaimBoostRewardSucceeded(account_amount_token(account_id, amount, token_id)));
+    }
+
+    pub fn claim_boost_reward_failed(account_id: &AccountId, amount: Balance, token_id: &AccountId) {
+        log_event(Event::ClaimBoostRewardFailed(account_amount_token(account_id, amount, token_id)));
+    }
+
+    pub fn swap_rewards_succeeded(account_id: &AccountId, token_in: &AccountId, amount_in: Balance, amount: Balance, token_id: &AccountId) {
+        log_event(Event::SwapRewardsSucceeded(vec![RewardsSwapped {
+            account_id: account_id.clone(),
+            token_in: token_in.clone(),
+            amount_in,
+            amount,
+            token_id: token_id.clone(),
+        }]));
+    }
+
+    /// `token_id` is the reward token
+    pub fn swap_rewards_failed(account_id: &AccountId, amount: Balance, token_id: &AccountId) {
+        log_event(Event::SwapRewardsFailed(account_amount_token(account_id, amount, token_id)));
+    }
+
+    pub fn strategy_deploy(strategy_id: &AccountId, amount: Balance, token_id: &AccountId) {
+        log_event(Event::StrategyDeploy(account_amount_token(strategy_id, amount, token_id)));
+    }
+
+    pub fn strategy_withdraw(strategy_id: &AccountId, amount: Balance, token_id: &AccountId) {
+        log_event(Event::StrategyWithdraw(account_amount_token(strategy_id, amount, token_id)));
+    }
+
+    pub fn strategy_harvest(strategy_id: &AccountId, amount: Balance, token_id: &AccountId) {
+        log_event(Event::StrategyHarvest(account_amount_token(strategy_id, amount, token_id)));
+    }
+
+    pub fn strategy_write_down(strategy_id: &AccountId, amount: Balance, token_id: &AccountId) {
+        log_event(Event::StrategyWriteDown(account_amount_token(strategy_id, amount, token_id)));
+    }
+
+    pub fn queue_withdraw(account_id: &AccountId, amount: Balance, token_id: &AccountId) {
+        log_event(Event::QueueWithdraw(account_amount_token(account_id, amount, token_id)));
+    }
+
+    pub fn slash(slash_id: u64, slash: &Slash, token_id: &AccountId) {
+        log_event(Event::Slash(vec![SlashMade {
+            slash_id,
+            slasher_id: slash.slasher_id.clone(),
+            receiver_id: slash.receiver_id.clone(),
+            amount: slash.amount.0,
+            locked_token_amount: slash.locked_token_amount.0,
+            token_id: token_id.clone(),
+            reason: slash.reason.clone(),
+        }]));
+    }
+
+    pub fn slash_succeeded(receiver_id: &AccountId, amount: Balance, token_id: &AccountId) {
+        log_event(Event::SlashSucceeded(account_amount_token(receiver_id, amount, token_id)));
+    }
+
+    pub fn slash_failed(receiver_id: &AccountId, amount: Balance, token_id: &AccountId) {
+        log_event(Event::SlashFailed(account_amount_token(receiver_id, amount, token_id)));
+    }
+
+    pub fn start_unstake_cooldown(account_id: &AccountId, unstake_start_time_in_sec: u32) {
+        log_event(Event::StartUnstakeCooldown(vec![AccountCooldown {
+            account_id: account_id.clone(),
+            unstake_start_time_in_sec,
+        }]));
+    }
+
+    pub fn recover_tokens_succeeded(account_id: &AccountId, amount: Balance, token_id: &AccountId) {
+        log_event(Event::RecoverTokensSucceeded(account_amount_token(account_id, amount, token_id)));
+    }
+
+    pub fn recover_tokens_failed(account_id: &AccountId, amount: Balance, token_id: &AccountId) {
+        log_event(Event::RecoverTokensFailed(account_amount_token(account_id, amount, token_id)));
+    }
+
+    pub fn add_pool(pool_id: PoolId, token_id: &AccountId) {
+        log_event(Event::AddPool(vec![PoolToken {
+            pool_id,
+            token_id: token_id.clone(),
+        }]));
+    }
+
+    pub fn pool_add_stake(pool_id: PoolId, account_id: &AccountId, amount: Balance, token_id: &AccountId) {
+        log_event(Event::PoolAddStake(pool_amount_token(pool_id, account_id, amount, token_id)));
+    }
+
+    pub fn pool_add_rewards(pool_id: PoolId, account_id: &AccountId, amount: Balance, token_id: &AccountId) {
+        log_event(Event::PoolAddRewards(pool_amount_token(pool_id, account_id, amount, token_id)));
+    }
+
+    pub fn pool_withdraw_succeeded(pool_id: PoolId, account_id: &AccountId, amount: Balance, token_id: &AccountId) {
+        log_event(Event::PoolWithdrawSucceeded(pool_amount_token(
+            pool_id, account_id, amount, token_id,
+        )));
+    }
+
+    pub fn pool_withdraw_failed(pool_id: PoolId, account_id: &AccountId, amount: Balance, token_id: &AccountId) {
+        log_event(Event::PoolWithdrawFailed(pool_amount_token(pool_id, account_id, amount, token_id)));
     }
 }
 
 pub mod u128_dec_format {
     use near_sdk::serde::Serializer;
+    #[cfg(not(target_arch = "wasm32"))]
+    use near_sdk::serde::{de, Deserialize, Deserializer};
 
     pub fn serialize<S>(num: &u128, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -78,4 +518,12 @@ pub mod u128_dec_format {
     {
         serializer.serialize_str(&num.to_string())
     }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<u128, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        String::deserialize(deserializer)?.parse().map_err(de::Error::custom)
+    }
 }
@@ -0,0 +1,148 @@
+use crate::accounting::{bps_of, INITIAL_VIRTUAL_PRICE};
+use crate::*;
+
+/// Hard cap of the performance fee, 20%
+pub const MAX_PERFORMANCE_FEE_BPS: u32 = 2_000;
+/// Hard cap of the exit fee, 10%
+pub const MAX_EXIT_FEE_BPS: u32 = 1_000;
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct ExitFee {
+    // at call time, exit fee of the account in basis points
+    pub exit_fee_bps: u32,
+    // at call time, exit fee in staked token to unstake all shares
+    pub amount: WrappedBalance,
+    /// when exit fee drops to 0
+    pub fee_free_time_in_sec: u32,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Set fee in basis points taken from distributed reward, reward up to call time is distributed with the old fee.
+    pub fn set_performance_fee(&mut self, performance_fee_bps: u32) {
+        self.assert_owner();
+        assert!(performance_fee_bps <= MAX_PERFORMANCE_FEE_BPS, "ERR_FEE_TOO_HIGH");
+        self.distribute_reward();
+        self.performance_fee_bps = performance_fee_bps;
+    }
+
+    pub fn get_performance_fee(&self) -> u32 {
+        self.performance_fee_bps
+    }
+
+    /// Set account which receives performance fee as shares, no fee is taken without it.
+    pub fn set_treasury(&mut self, treasury_id: Option<AccountId>) {
+        self.assert_owner();
+        self.distribute_reward();
+        self.treasury_id = treasury_id;
+    }
+
+    pub fn get_treasury(&self) -> Option<AccountId> {
+        self.treasury_id.clone()
+    }
+
+    pub fn get_accrued_performance_fee(&self) -> WrappedBalance {
+        self.accrued_performance_fee.into()
+    }
+
+    /// Set exit fee in basis points charged on unstake right after a stake.
+    /// It decays linearly to 0 in `exit_fee_duration_in_sec` since the last stake of the account.
+    pub fn set_exit_fee(&mut self, exit_fee_bps: u32, exit_fee_duration_in_sec: u32, exit_fee_to_treasury: bool) {
+        self.assert_owner();
+        assert!(exit_fee_bps <= MAX_EXIT_FEE_BPS, "ERR_FEE_TOO_HIGH");
+        self.exit_fee_bps = exit_fee_bps;
+        self.exit_fee_duration_in_sec = exit_fee_duration_in_sec;
+        self.exit_fee_to_treasury = exit_fee_to_treasury;
+    }
+
+    pub fn get_exit_fee(&self, account_id: AccountId) -> ExitFee {
+        let cur_time = nano_to_sec(env::block_timestamp());
+        let shares = self.shares.get(&account_id).unwrap_or_default();
+        let unlocked = if shares == 0 {
+            0
+        } else {
+            (U256::from(shares) * U256::from(self.internal_virtual_price_at(cur_time)) / U256::from(INITIAL_VIRTUAL_PRICE)).as_u128()
+        };
+        let exit_fee_bps = self.internal_exit_fee_bps(&account_id, cur_time);
+        ExitFee {
+            exit_fee_bps,
+            amount: bps_of(unlocked, exit_fee_bps).into(),
+            fee_free_time_in_sec: self
+                .internal_get_account(&account_id)
+                .last_stake_time_in_sec
+                .map(|time| time.saturating_add(self.exit_fee_duration_in_sec))
+                .unwrap_or(0),
+        }
+    }
+}
+
+impl Contract {
+    /// Fee in tokens and shares to mint for it, once `reward` is added to locked_token_amount.
+    /// Shares are minted at the price after distribution, so the treasury owns exactly the fee.
+    pub(crate) fn try_performance_fee(&self, reward: Balance, locked_token_amount: Balance) -> (Balance, Balance) {
+        if self.treasury_id.is_none() || self.performance_fee_bps == 0 || self.ledger.total_staked == 0 {
+            return (0, 0);
+        }
+        let fee = bps_of(reward, self.performance_fee_bps);
+        (fee, self.fee_shares(fee, locked_token_amount))
+    }
+
+    /// Shares to mint for `fee` which is already a part of `locked_token_amount`
+    fn fee_shares(&self, fee: Balance, locked_token_amount: Balance) -> Balance {
+        if fee == 0 || locked_token_amount <= fee {
+            return 0;
+        }
+        (U256::from(fee) * U256::from(self.ledger.total_staked) / U256::from(locked_token_amount - fee)).as_u128()
+    }
+
+    pub(crate) fn internal_exit_fee_bps(&self, account_id: &AccountId, cur_timestamp_in_sec: u32) -> u32 {
+        self.internal_exit_fee_bps_since(self.internal_get_account(account_id).last_stake_time_in_sec, cur_timestamp_in_sec)
+    }
+
+    /// Exit fee of shares staked at `stake_time_in_sec`, it decays to 0 over exit_fee_duration
+    pub(crate) fn internal_exit_fee_bps_since(&self, stake_time_in_sec: Option<u32>, cur_timestamp_in_sec: u32) -> u32 {
+        if self.exit_fee_bps == 0 {
+            return 0;
+        }
+        match stake_time_in_sec {
+            Some(last_stake_time) => {
+                let elapsed = cur_timestamp_in_sec.saturating_sub(last_stake_time);
+                if elapsed >= self.exit_fee_duration_in_sec {
+                    0
+                } else {
+                    (self.exit_fee_bps as u64 * (self.exit_fee_duration_in_sec - elapsed) as u64 / self.exit_fee_duration_in_sec as u64)
+                        as u32
+                }
+            }
+            None => 0,
+        }
+    }
+
+    /// `exit_fee` is left in locked_token_amount, so it either raises the virtual price or is minted to the treasury.
+    pub(crate) fn internal_charge_exit_fee(&mut self, account_id: &AccountId, exit_fee: Balance) {
+        if self.exit_fee_to_treasury {
+            if let Some(treasury_id) = self.treasury_id.clone() {
+                let minted = self.fee_shares(exit_fee, self.ledger.locked_token_amount);
+                if minted > 0 {
+                    self.ledger.mint(minted);
+                    self.internal_deposit(&treasury_id, minted);
+                }
+            }
+        }
+        events::emit::exit_fee(account_id, exit_fee, &self.token_id);
+    }
+
+    pub(crate) fn internal_charge_performance_fee(&mut self, reward: Balance) {
+        let (fee, minted) = self.try_performance_fee(reward, self.ledger.locked_token_amount);
+        if minted == 0 {
+            return;
+        }
+        let treasury_id = self.treasury_id.clone().unwrap();
+        self.ledger.mint(minted);
+        self.internal_deposit(&treasury_id, minted);
+        self.accrued_performance_fee += fee;
+        events::emit::performance_fee(&treasury_id, fee, minted, &self.token_id);
+    }
+}
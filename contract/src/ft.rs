@@ -1,3 +1,4 @@
+use crate::recovery::TokenBalanceReport;
 use crate::*;
 use near_contract_standards::fungible_token::core::ext_ft_core;
 use near_contract_standards::fungible_token::receiver::FungibleTokenReceiver;
@@ -5,49 +6,242 @@ use near_sdk::json_types::U128;
 use near_sdk::{serde_json, PromiseOrValue, ONE_YOCTO};
 use serde::Deserialize;
 
-const GAS_FOR_FT_TRANSFER: Gas = Gas(Gas::ONE_TERA.0 * 20);
-const GAS_FOR_AFTER_FT_TRANSFER: Gas = Gas(Gas::ONE_TERA.0 * 20);
+pub(crate) const GAS_FOR_FT_TRANSFER: Gas = Gas(Gas::ONE_TERA.0 * 20);
+pub(crate) const GAS_FOR_AFTER_FT_TRANSFER: Gas = Gas(Gas::ONE_TERA.0 * 20);
 
 #[derive(Deserialize)]
 #[cfg_attr(not(target_arch = "wasm32"), derive(Debug, Serialize))]
-#[serde(crate = "near_sdk::serde")]
+#[serde(crate = "near_sdk::serde", from = "TokenReceiverMsgJson")]
 pub enum TokenReceiverMsg {
+    Stake {
+        referrer_id: Option<AccountId>,
+        pool_id: Option<PoolId>,
+    },
+    AddRewards {
+        pool_id: Option<PoolId>,
+    },
+    AddReferralPool,
+    AddBoostRewards,
+    GrantStake {
+        beneficiary_id: AccountId,
+        /// now if not set
+        start_time_in_sec: Option<u32>,
+        cliff_duration_in_sec: u32,
+        duration_in_sec: u32,
+        revocable: bool,
+    },
+    LockStake {
+        lock_duration_in_sec: u32,
+    },
+    /// sent with an approved reward token, it's swapped to the staked token and added as reward
+    SwapRewards {
+        min_amount_out: WrappedBalance,
+    },
+}
+
+impl TokenReceiverMsg {
+    /// the pool to route the transfer to, None for the main pool
+    fn pool_id(&self) -> Option<PoolId> {
+        match self {
+            TokenReceiverMsg::Stake { pool_id, .. } | TokenReceiverMsg::AddRewards { pool_id } => *pool_id,
+            TokenReceiverMsg::AddReferralPool
+            | TokenReceiverMsg::AddBoostRewards
+            | TokenReceiverMsg::GrantStake { .. }
+            | TokenReceiverMsg::LockStake { .. }
+            | TokenReceiverMsg::SwapRewards { .. } => None,
+        }
+    }
+}
+
+/// Accepts both `"Stake"` and `{"Stake": {"referrer_id": "alice.near", "pool_id": 1}}`,
+/// grants and locks only as `{"GrantStake": {"beneficiary_id": "alice.near", "cliff_duration_in_sec": 0, "duration_in_sec": 100}}`
+/// and `{"LockStake": {"lock_duration_in_sec": 100}}`, swaps only as `{"SwapRewards": {"min_amount_out": "100"}}`
+#[derive(Deserialize)]
+#[serde(crate = "near_sdk::serde", untagged)]
+enum TokenReceiverMsgJson {
+    Plain(PlainTokenReceiverMsg),
+    Stake {
+        #[serde(rename = "Stake")]
+        stake: StakeMsg,
+    },
+    AddRewards {
+        #[serde(rename = "AddRewards")]
+        add_rewards: AddRewardsMsg,
+    },
+    GrantStake {
+        #[serde(rename = "GrantStake")]
+        grant_stake: GrantStakeMsg,
+    },
+    LockStake {
+        #[serde(rename = "LockStake")]
+        lock_stake: LockStakeMsg,
+    },
+    SwapRewards {
+        #[serde(rename = "SwapRewards")]
+        swap_rewards: SwapRewardsMsg,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+enum PlainTokenReceiverMsg {
     Stake,
     AddRewards,
+    AddReferralPool,
+    AddBoostRewards,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+struct StakeMsg {
+    referrer_id: Option<AccountId>,
+    pool_id: Option<PoolId>,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+struct AddRewardsMsg {
+    pool_id: Option<PoolId>,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+struct GrantStakeMsg {
+    beneficiary_id: AccountId,
+    start_time_in_sec: Option<u32>,
+    cliff_duration_in_sec: u32,
+    duration_in_sec: u32,
+    #[serde(default)]
+    revocable: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+struct LockStakeMsg {
+    lock_duration_in_sec: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+struct SwapRewardsMsg {
+    min_amount_out: WrappedBalance,
+}
+
+impl From<TokenReceiverMsgJson> for TokenReceiverMsg {
+    fn from(msg: TokenReceiverMsgJson) -> Self {
+        match msg {
+            TokenReceiverMsgJson::Plain(PlainTokenReceiverMsg::Stake) => TokenReceiverMsg::Stake {
+                referrer_id: None,
+                pool_id: None,
+            },
+            TokenReceiverMsgJson::Plain(PlainTokenReceiverMsg::AddRewards) => TokenReceiverMsg::AddRewards { pool_id: None },
+            TokenReceiverMsgJson::Plain(PlainTokenReceiverMsg::AddReferralPool) => TokenReceiverMsg::AddReferralPool,
+            TokenReceiverMsgJson::Plain(PlainTokenReceiverMsg::AddBoostRewards) => TokenReceiverMsg::AddBoostRewards,
+            TokenReceiverMsgJson::Stake { stake } => TokenReceiverMsg::Stake {
+                referrer_id: stake.referrer_id,
+                pool_id: stake.pool_id,
+            },
+            TokenReceiverMsgJson::AddRewards { add_rewards } => TokenReceiverMsg::AddRewards {
+                pool_id: add_rewards.pool_id,
+            },
+            TokenReceiverMsgJson::GrantStake { grant_stake } => TokenReceiverMsg::GrantStake {
+                beneficiary_id: grant_stake.beneficiary_id,
+                start_time_in_sec: grant_stake.start_time_in_sec,
+                cliff_duration_in_sec: grant_stake.cliff_duration_in_sec,
+                duration_in_sec: grant_stake.duration_in_sec,
+                revocable: grant_stake.revocable,
+            },
+            TokenReceiverMsgJson::LockStake { lock_stake } => TokenReceiverMsg::LockStake {
+                lock_duration_in_sec: lock_stake.lock_duration_in_sec,
+            },
+            TokenReceiverMsgJson::SwapRewards { swap_rewards } => TokenReceiverMsg::SwapRewards {
+                min_amount_out: swap_rewards.min_amount_out,
+            },
+        }
+    }
 }
 
 #[near_bindgen]
 impl FungibleTokenReceiver for Contract {
     fn ft_on_transfer(&mut self, sender_id: AccountId, amount: WrappedBalance, msg: String) -> PromiseOrValue<WrappedBalance> {
         let token_id = env::predecessor_account_id();
-        assert_eq!(self.token_id, token_id, "ERR_ILLEGAL_TOKEN");
-
-        // Checkpoint
-        self.distribute_reward();
-
         let amount: Balance = amount.0;
         assert!(amount > 0, "ERR_ZERO_DEPOSIT");
 
         let token_receiver_msg: TokenReceiverMsg = serde_json::from_str(&msg).expect("ERR_ILLEGAL_MSG");
+        if let Some(pool_id) = token_receiver_msg.pool_id() {
+            self.internal_pool_on_transfer(pool_id, &token_id, &sender_id, amount, token_receiver_msg);
+            return PromiseOrValue::Value(U128(0));
+        }
+
+        if let TokenReceiverMsg::SwapRewards { min_amount_out } = token_receiver_msg {
+            self.internal_swap_rewards(&token_id, &sender_id, amount, min_amount_out.0);
+            return PromiseOrValue::Value(U128(0));
+        }
+
+        assert_eq!(self.token_id, token_id, "ERR_ILLEGAL_TOKEN");
+
+        // Checkpoint
+        self.distribute_reward();
 
         match token_receiver_msg {
-            TokenReceiverMsg::Stake => {
+            TokenReceiverMsg::Stake { referrer_id, .. } => {
+                assert!(amount >= self.min_stake_amount, "ERR_STAKE_BELOW_MINIMUM");
+                if let Some(referrer_id) = referrer_id {
+                    self.internal_link_referrer(&sender_id, &referrer_id);
+                }
                 self.internal_stake(&sender_id, amount);
                 events::emit::add_stake(&sender_id, amount, &token_id);
                 PromiseOrValue::Value(U128(0))
             }
-            TokenReceiverMsg::AddRewards => {
+            TokenReceiverMsg::AddRewards { .. } => {
                 self.internal_add_reward(&sender_id, amount);
                 events::emit::add_rewards(&sender_id, amount, &token_id);
                 PromiseOrValue::Value(U128(0))
             }
+            TokenReceiverMsg::AddReferralPool => {
+                self.internal_add_referral_pool(&sender_id, amount);
+                events::emit::add_referral_pool(&sender_id, amount, &token_id);
+                PromiseOrValue::Value(U128(0))
+            }
+            TokenReceiverMsg::AddBoostRewards => {
+                self.internal_add_boost_reward(&sender_id, amount);
+                events::emit::add_boost_rewards(&sender_id, amount, &token_id);
+                PromiseOrValue::Value(U128(0))
+            }
+            TokenReceiverMsg::GrantStake {
+                beneficiary_id,
+                start_time_in_sec,
+                cliff_duration_in_sec,
+                duration_in_sec,
+                revocable,
+            } => {
+                let start_time_in_sec = start_time_in_sec.unwrap_or_else(|| nano_to_sec(env::block_timestamp()));
+                let grant = Grant::new(start_time_in_sec, cliff_duration_in_sec, duration_in_sec, revocable);
+                self.internal_grant_stake(&sender_id, &beneficiary_id, amount, grant);
+                PromiseOrValue::Value(U128(0))
+            }
+            TokenReceiverMsg::LockStake { lock_duration_in_sec } => {
+                self.internal_lock_stake(&sender_id, amount, lock_duration_in_sec);
+                events::emit::add_stake(&sender_id, amount, &token_id);
+                PromiseOrValue::Value(U128(0))
+            }
+            TokenReceiverMsg::SwapRewards { .. } => unreachable!(),
         }
     }
 }
 
 impl Contract {
-    pub fn internal_ft_transfer(&mut self, account_id: &AccountId, unlocked: Balance, amount: Balance) -> Promise {
+    pub fn internal_ft_transfer(
+        &mut self,
+        account_id: &AccountId,
+        unlocked: Balance,
+        amount: Balance,
+        principal: Balance,
+        vested: Balance,
+    ) -> Promise {
         let token_id = self.token_id.clone();
+        self.pending_transfer_amount += unlocked;
         ext_ft_core::ext(token_id.clone())
             .with_attached_deposit(ONE_YOCTO)
             .with_static_gas(GAS_FOR_FT_TRANSFER)
@@ -55,12 +249,80 @@ impl Contract {
             .then(
                 ext_self::ext(env::current_account_id())
                     .with_static_gas(GAS_FOR_AFTER_FT_TRANSFER)
-                    .callback_post_unstake(account_id.clone(), unlocked.into(), amount.into()),
+                    .callback_post_unstake(account_id.clone(), unlocked.into(), amount.into(), principal.into(), vested.into()),
+            )
+    }
+
+    pub fn internal_reward_transfer(&mut self, receiver_id: &AccountId, amount: Balance) -> Promise {
+        let token_id = self.token_id.clone();
+        self.pending_transfer_amount += amount;
+        ext_ft_core::ext(token_id)
+            .with_attached_deposit(ONE_YOCTO)
+            .with_static_gas(GAS_FOR_FT_TRANSFER)
+            .ft_transfer(receiver_id.clone(), amount.into(), None)
+            .then(
+                ext_self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_AFTER_FT_TRANSFER)
+                    .callback_post_withdraw_reward(receiver_id.clone(), amount.into()),
             )
     }
 }
 
 #[ext_contract(ext_self)]
 pub trait ExtSelf {
-    fn callback_post_unstake(&mut self, sender_id: AccountId, amount: WrappedBalance, share: WrappedBalance);
+    fn callback_post_unstake(
+        &mut self,
+        sender_id: AccountId,
+        amount: WrappedBalance,
+        share: WrappedBalance,
+        principal: WrappedBalance,
+        vested: WrappedBalance,
+    );
+    fn callback_post_pool_unstake(&mut self, pool_id: PoolId, sender_id: AccountId, amount: WrappedBalance, share: WrappedBalance);
+    fn callback_post_withdraw_reward(&mut self, receiver_id: AccountId, amount: WrappedBalance);
+    fn callback_post_claim_referral_earnings(&mut self, account_id: AccountId, amount: WrappedBalance);
+    fn callback_post_claim_vested(&mut self, account_id: AccountId, amount: WrappedBalance);
+    fn callback_post_nft_withdraw(&mut self, account_id: AccountId, collection_id: AccountId, token_id: TokenId, boost_bps: u32);
+    fn callback_post_claim_boost_reward(&mut self, account_id: AccountId, amount: WrappedBalance);
+    fn callback_post_dex_deposit(
+        &mut self,
+        sender_id: AccountId,
+        token_in: AccountId,
+        pool_id: u64,
+        amount_in: WrappedBalance,
+        min_amount_out: WrappedBalance,
+        dex_id: AccountId,
+    ) -> PromiseOrValue<()>;
+    fn callback_post_swap(
+        &mut self,
+        sender_id: AccountId,
+        token_in: AccountId,
+        amount_in: WrappedBalance,
+        dex_id: AccountId,
+    ) -> PromiseOrValue<()>;
+    fn callback_post_slash(&mut self, slash_id: u64);
+    fn callback_post_strategy_deploy(&mut self, amount: WrappedBalance);
+    fn callback_pre_strategy_withdraw(&mut self, amount: WrappedBalance) -> PromiseOrValue<()>;
+    fn callback_post_strategy_withdraw(&mut self, amount: WrappedBalance, balance_before: WrappedBalance);
+    fn callback_post_strategy_balance(&mut self) -> PromiseOrValue<()>;
+    fn callback_post_strategy_harvest(&mut self, amount: WrappedBalance, balance_before: WrappedBalance);
+    fn callback_post_dex_withdraw(
+        &mut self,
+        sender_id: AccountId,
+        token_in: AccountId,
+        amount_in: WrappedBalance,
+        amount_out: WrappedBalance,
+        deposit_before: WrappedBalance,
+    );
+    fn callback_pre_dex_withdraw(
+        &mut self,
+        sender_id: AccountId,
+        token_in: AccountId,
+        amount_in: WrappedBalance,
+        amount_out: WrappedBalance,
+        dex_id: AccountId,
+    ) -> PromiseOrValue<()>;
+    fn callback_token_balance_report(&self) -> TokenBalanceReport;
+    fn callback_sweep_surplus(&mut self, receiver_id: AccountId, operation_nonce: u64) -> Promise;
+    fn callback_post_recover_tokens(&mut self, token_id: AccountId, receiver_id: AccountId, amount: WrappedBalance);
 }
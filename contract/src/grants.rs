@@ -0,0 +1,134 @@
+use crate::*;
+#[cfg(not(target_arch = "wasm32"))]
+use near_sdk::serde::Deserialize;
+
+/// Shares staked for a beneficiary which unlock linearly from start_time to end_time, nothing before the cliff
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct Grant {
+    pub shares: Balance,
+    pub start_time_in_sec: u32,
+    pub cliff_time_in_sec: u32,
+    pub end_time_in_sec: u32,
+    /// the owner can take back unvested shares to the treasury
+    pub revocable: bool,
+}
+
+impl Grant {
+    /// Grant without shares, unlocking in `duration_in_sec` from `start_time_in_sec`
+    pub(crate) fn new(start_time_in_sec: u32, cliff_duration_in_sec: u32, duration_in_sec: u32, revocable: bool) -> Self {
+        assert!(duration_in_sec > 0, "ERR_ZERO_DURATION");
+        assert!(cliff_duration_in_sec <= duration_in_sec, "ERR_CLIFF_AFTER_END");
+        Self {
+            shares: 0,
+            start_time_in_sec,
+            cliff_time_in_sec: start_time_in_sec + cliff_duration_in_sec,
+            end_time_in_sec: start_time_in_sec + duration_in_sec,
+            revocable,
+        }
+    }
+
+    fn vested_shares_at(&self, timestamp_in_sec: u32) -> Balance {
+        if timestamp_in_sec < self.cliff_time_in_sec {
+            0
+        } else if timestamp_in_sec >= self.end_time_in_sec {
+            self.shares
+        } else {
+            let elapsed = timestamp_in_sec - self.start_time_in_sec;
+            let duration = self.end_time_in_sec - self.start_time_in_sec;
+            (U256::from(self.shares) * U256::from(elapsed) / U256::from(duration)).as_u128()
+        }
+    }
+
+    fn locked_shares_at(&self, timestamp_in_sec: u32) -> Balance {
+        self.shares - self.vested_shares_at(timestamp_in_sec)
+    }
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, Deserialize))]
+pub struct GrantView {
+    pub shares: WrappedBalance,
+    // at call time, shares which can be unstaked
+    pub vested_shares: WrappedBalance,
+    // at call time, shares which can't be unstaked yet
+    pub locked_shares: WrappedBalance,
+    pub start_time_in_sec: u32,
+    pub cliff_time_in_sec: u32,
+    pub end_time_in_sec: u32,
+    pub revocable: bool,
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn get_grant(&self, account_id: AccountId) -> Option<GrantView> {
+        let cur_time = nano_to_sec(env::block_timestamp());
+        self.grants.get(&account_id).map(|grant| GrantView {
+            shares: grant.shares.into(),
+            vested_shares: grant.vested_shares_at(cur_time).into(),
+            locked_shares: grant.locked_shares_at(cur_time).into(),
+            start_time_in_sec: grant.start_time_in_sec,
+            cliff_time_in_sec: grant.cliff_time_in_sec,
+            end_time_in_sec: grant.end_time_in_sec,
+            revocable: grant.revocable,
+        })
+    }
+
+    /// Move unvested shares of the grant to the treasury, vested shares stay with the beneficiary.
+    /// Requirements:
+    /// * Can only be called by the owner.
+    /// * The grant must be revocable and the treasury must be set.
+    pub fn revoke_grant(&mut self, beneficiary_id: AccountId) -> WrappedBalance {
+        self.assert_owner();
+        let grant = self.grants.get(&beneficiary_id).expect("ERR_NO_GRANT");
+        assert!(grant.revocable, "ERR_GRANT_NOT_REVOCABLE");
+        let treasury_id = self.treasury_id.clone().expect("ERR_NO_TREASURY");
+
+        // Checkpoint
+        self.distribute_reward();
+
+        let locked_shares = grant.locked_shares_at(nano_to_sec(env::block_timestamp()));
+        self.grants.remove(&beneficiary_id);
+        if locked_shares > 0 {
+            let principal = self.internal_take_cost_basis(&beneficiary_id, locked_shares);
+            self.internal_withdraw(&beneficiary_id, locked_shares);
+            self.internal_deposit(&treasury_id, locked_shares);
+            self.internal_add_cost_basis(&treasury_id, principal);
+        }
+
+        events::emit::revoke_grant(&beneficiary_id, &treasury_id, locked_shares);
+        locked_shares.into()
+    }
+}
+
+impl Contract {
+    /// Stake `amount` for the beneficiary, the minted shares unlock by the `grant` schedule.
+    /// Requirements:
+    /// * `sender_id` must be the owner or the treasury.
+    /// * The beneficiary must not have a grant with locked shares.
+    pub(crate) fn internal_grant_stake(&mut self, sender_id: &AccountId, beneficiary_id: &AccountId, amount: Balance, mut grant: Grant) {
+        assert!(
+            sender_id == &self.owner_id || Some(sender_id) == self.treasury_id.as_ref(),
+            "ERR_NOT_AN_OWNER_OR_TREASURY"
+        );
+        if let Some(prev_grant) = self.grants.get(beneficiary_id) {
+            assert_eq!(
+                prev_grant.locked_shares_at(nano_to_sec(env::block_timestamp())),
+                0,
+                "ERR_GRANT_EXISTS"
+            );
+        }
+
+        grant.shares = self.internal_stake(beneficiary_id, amount);
+        self.grants.insert(beneficiary_id, &grant);
+        events::emit::grant_stake(beneficiary_id, amount, grant.shares, &self.token_id);
+    }
+
+    /// Shares of the account which can't be unstaked because of its grant
+    pub(crate) fn internal_locked_grant_shares(&self, account_id: &AccountId) -> Balance {
+        self.grants
+            .get(account_id)
+            .map(|grant| grant.locked_shares_at(nano_to_sec(env::block_timestamp())))
+            .unwrap_or(0)
+    }
+}
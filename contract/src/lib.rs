@@ -1,21 +1,66 @@
+use crate::account::{Account, AccountV1, VAccount};
+use crate::accounting::{Ledger, WeightLedger, MIN_TOTAL_STAKED};
+use crate::boosts::BoostNft;
+use crate::checkpoints::Checkpoint;
+use crate::grants::Grant;
+use crate::points::Season;
+use crate::polls::{Poll, PollId};
+use crate::pools::Pool;
+use crate::positions::Position;
+use crate::price_history::{PriceSnapshot, DEFAULT_PRICE_SNAPSHOT_INTERVAL_IN_SEC};
+use crate::strategy::QueuedWithdraw;
 use crate::utils::*;
+use crate::vesting::Vesting;
+use near_contract_standards::non_fungible_token::{NonFungibleToken, TokenId};
 use near_sdk::json_types::U128;
 use near_sdk::{
     borsh::{self, BorshDeserialize, BorshSerialize},
-    collections::UnorderedMap,
+    collections::{LookupMap, LookupSet, UnorderedMap, UnorderedSet, Vector},
     env, ext_contract, log, near_bindgen,
     serde::Serialize,
-    AccountId, Balance, BorshStorageKey, Gas, PanicOnDefault, Promise,
+    AccountId, Balance, BorshStorageKey, CryptoHash, Gas, PanicOnDefault, Promise,
 };
 
 pub type WrappedBalance = U128;
 
+// types of the contract interface, shared with the client
+pub use crate::boosts::BoostView;
+pub use crate::events::{Event, EventLog};
+pub use crate::ft::TokenReceiverMsg;
+pub use crate::grants::GrantView;
+pub use crate::points::{AccountPoints, SeasonId, SeasonView};
+pub use crate::pools::{PoolId, PoolView};
+pub use crate::positions::PositionView;
+pub use crate::slashing::{Slash, UnstakeCooldown};
+pub use crate::strategy::{QueuedWithdrawView, StrategyView};
+pub use crate::utils::U256;
+pub use crate::vesting::VestingView;
+pub use crate::views::{apr_bps, reward_end_time_in_sec, ContractMetadata};
+
 mod account;
+pub mod accounting;
 mod admin;
-mod events;
+mod boosts;
+mod checkpoints;
+mod delegation;
+pub mod events;
+mod fee;
 mod ft;
+mod grants;
+mod points;
+mod polls;
+mod pools;
+mod positions;
+mod price_history;
+mod recovery;
+mod referral;
+mod slashing;
 mod staking;
+mod strategy;
+mod swaps;
+mod upgrade;
 mod utils;
+mod vesting;
 mod views;
 
 //type TimestampU128 = u128;
@@ -23,6 +68,37 @@ mod views;
 #[derive(BorshSerialize, BorshStorageKey)]
 enum StorageKey {
     Deposits,
+    PriceHistory,
+    Accounts,
+    SharesCheckpoints,
+    SharesCheckpointsPerAccount { account_hash: CryptoHash },
+    TotalStakedCheckpoints,
+    DelegatedPower,
+    Proposers,
+    Polls,
+    PollVotes,
+    Referees,
+    RefereesPerAccount { account_hash: CryptoHash },
+    ReferralEarnings,
+    Pools,
+    PoolShares { pool_id: PoolId },
+    Seasons,
+    SeasonPoints,
+    Vestings,
+    Grants,
+    PositionsNft,
+    PositionsNftMetadata,
+    PositionsNftEnumeration,
+    PositionsNftApprovals,
+    Positions,
+    BoostCollections,
+    BoostNfts,
+    PendingNftWithdraws,
+    RewardTokens,
+    WithdrawQueue,
+    Slashes,
+    VAccounts,
+    PositionOwners,
 }
 
 #[near_bindgen]
@@ -30,43 +106,251 @@ enum StorageKey {
 pub struct Contract {
     // admin account to configure this contract
     owner_id: AccountId,
+    // account allowed to withdraw undistributed reward besides the owner
+    reward_manager_id: Option<AccountId>,
+    // factory which deployed this contract, it can upgrade the code
+    factory_id: Option<AccountId>,
     // token id to stake
     token_id: AccountId,
-    // at prev_distribution_time, reward token that haven't distribute yet
-    pub undistributed_reward: Balance,
-    /// at prev_distribution_time, backend staked token amount
-    pub locked_token_amount: Balance,
-    /// the previous distribution time in seconds
-    pub prev_distribution_time_in_sec: u32,
-    /// when would the reward starts to distribute
-    pub reward_genesis_time_in_sec: u32,
-    pub reward_per_sec: Balance,
+    /// reward stream and share supply of the main pool
+    pub ledger: Ledger,
     /// current account number in contract
     pub account_number: u64,
-    // total amount of staked token
-    pub total_staked: Balance,
+    /// staked token sent out by ft_transfer which is waiting for a callback
+    pub pending_transfer_amount: Balance,
+    // raised by every swap and strategy operation, a sweep checks that none started while it read the balance
+    pub operation_nonce: u64,
+    /// fee in basis points taken from distributed reward
+    pub performance_fee_bps: u32,
+    // account which gets performance fee as shares
+    pub treasury_id: Option<AccountId>,
+    /// total performance fee taken, in staked token
+    pub accrued_performance_fee: Balance,
+    /// exit fee in basis points right after a stake, decays to 0 in exit_fee_duration_in_sec
+    pub exit_fee_bps: u32,
+    pub exit_fee_duration_in_sec: u32,
+    // exit fee goes to the treasury as shares, otherwise it stays with the remaining stakers
+    pub exit_fee_to_treasury: bool,
+    /// shares can't be unstaked earlier than this after a stake
+    pub min_holding_period_in_sec: u32,
+    /// smallest stake to the main pool, every stake adds share checkpoints stored by the contract
+    pub min_stake_amount: Balance,
+    /// share of referee reward in basis points which the referrer earns
+    pub referral_fee_bps: u32,
+    // tokens to pay referral earnings from
+    pub referral_pool: Balance,
+    // referral earnings which are not claimed yet
+    pub total_referral_earnings: Balance,
 
     // staked amount of every staking user
     pub shares: UnorderedMap<AccountId, Balance>,
+    // other data of staking users
+    pub accounts: LookupMap<AccountId, VAccount>,
+    // accounts stored before the upgrade to versioned accounts, moved to `accounts` on their first write
+    pub legacy_accounts: LookupMap<AccountId, AccountV1>,
+    // history of shares of every staking user, for voting power
+    pub shares_checkpoints: LookupMap<AccountId, Vector<Checkpoint>>,
+    pub total_staked_checkpoints: Vector<Checkpoint>,
+    // shares delegated to every delegatee
+    pub delegated_power: LookupMap<AccountId, Balance>,
+
+    // accounts allowed to create polls besides the owner
+    pub proposers: UnorderedSet<AccountId>,
+    pub polls: Vector<Poll>,
+    // option voted by every account in every poll
+    pub poll_votes: LookupMap<(PollId, AccountId), u32>,
+
+    // accounts linked to every referrer
+    pub referees: LookupMap<AccountId, Vector<AccountId>>,
+    pub referral_earnings: LookupMap<AccountId, Balance>,
+
+    // ring buffer of virtual price snapshots, recorded by distribute_reward
+    pub price_history: Vector<PriceSnapshot>,
+    /// index of the oldest snapshot once the ring buffer is full
+    pub price_history_head: u64,
+    pub last_price_snapshot_time_in_sec: Option<u32>,
+    pub price_snapshot_interval_in_sec: u32,
+
+    // pools hosted next to the main one, keyed by pool id
+    pub pools: UnorderedMap<PoolId, Pool>,
+
+    // shares × seconds of all stakers in the current season up to total_points_time
+    pub total_points: U256,
+    pub total_points_time_in_sec: u32,
+    pub season_start_time_in_sec: u32,
+    pub seasons: Vector<Season>,
+    // points of accounts in the finished season of their last update, recorded once they change shares in a later one,
+    // seasons in which shares didn't change are read from the checkpoints
+    pub season_points: LookupMap<(SeasonId, AccountId), U256>,
+
+    /// reward part of unstaked tokens vests over this period, 0 sends it with the principal
+    pub reward_vesting_duration_in_sec: u32,
+    // reward vesting of every account which unstaked
+    pub vestings: LookupMap<AccountId, Vesting>,
+    // reward vesting or vested, but not claimed yet
+    pub total_vesting: Balance,
+
+    // stake grant of every beneficiary, its shares unlock over time
+    pub grants: LookupMap<AccountId, Grant>,
+
+    // NEP-171 tokens of locked positions, their shares are held by the contract account
+    pub positions_nft: NonFungibleToken,
+    pub positions: LookupMap<TokenId, Position>,
+    // accounts with shares of locked positions, they are in the points ranking without shares of their own
+    pub position_owners: UnorderedSet<AccountId>,
+    pub next_position_id: u64,
+    /// extra boost of the longest lock in basis points
+    pub max_lock_boost_bps: u32,
+
+    /// reward stream split by weight, raised by deposited NFTs and locks
+    pub boost_ledger: WeightLedger,
+    // boost in basis points of every approved NFT collection
+    pub boost_collections: UnorderedMap<AccountId, u32>,
+    // NFT deposited by every account in every collection
+    pub boost_nfts: LookupMap<(AccountId, AccountId), BoostNft>,
+    // account and collection of every NFT which is being sent back
+    pub pending_nft_withdraws: LookupSet<(AccountId, AccountId)>,
+
+    // Ref-style exchange which swaps reward tokens to the staked token
+    pub swap_dex_id: Option<AccountId>,
+    // reward swap which waits for a callback, one runs at a time
+    pub swap_in_progress: bool,
+    // exchange pool id of every approved reward token
+    pub reward_tokens: UnorderedMap<AccountId, u64>,
+
+    // lending contract which a part of locked tokens is deployed to
+    pub strategy_id: Option<AccountId>,
+    /// part of locked_token_amount held by the strategy
+    pub deployed_amount: Balance,
+    /// part of locked_token_amount which is kept in the contract for unstakes
+    pub liquidity_buffer_bps: u32,
+    // deploy, withdraw or harvest which waits for a callback, one runs at a time
+    pub strategy_in_progress: bool,
+    // unstakes waiting for tokens from the strategy, from head to tail
+    pub withdraw_queue: LookupMap<u64, QueuedWithdraw>,
+    pub withdraw_queue_head: u64,
+    pub withdraw_queue_tail: u64,
+    /// unlocked tokens in the withdraw queue
+    pub total_queued_withdraw: Balance,
+
+    // account allowed to slash locked tokens to cover a shortfall
+    pub slasher_id: Option<AccountId>,
+    /// part of locked tokens which slashes of a slash period can take together
+    pub max_slash_bps: u32,
+    /// start of the current slash period, locked tokens at its start and tokens slashed in it
+    pub slash_period_start_in_sec: u32,
+    pub slash_period_locked_amount: Balance,
+    pub slashed_in_period: Balance,
+    /// unstakes must wait this long after start_unstake_cooldown, then fit in the unstake window
+    pub unstake_cooldown_in_sec: u32,
+    pub unstake_window_in_sec: u32,
+    // history of all slashes
+    pub slashes: Vector<Slash>,
+    /// tokens taken by successful slashes
+    pub total_slashed: Balance,
 }
 
 #[near_bindgen]
 impl Contract {
     #[init]
-    pub fn new(owner_id: AccountId, token_id: AccountId) -> Self {
+    pub fn new(owner_id: AccountId, token_id: AccountId, factory_id: Option<AccountId>) -> Self {
         let initial_reward_genisis_time = DURATION_30DAYS_IN_SEC + nano_to_sec(env::block_timestamp());
         Self {
             owner_id,
+            reward_manager_id: None,
+            factory_id,
             token_id,
-            undistributed_reward: 0,
-            locked_token_amount: 0,
-            prev_distribution_time_in_sec: initial_reward_genisis_time,
-            reward_genesis_time_in_sec: initial_reward_genisis_time,
-            reward_per_sec: 0,
+            ledger: Ledger::new(0, initial_reward_genisis_time),
             account_number: 0,
-            total_staked: 0,
+            pending_transfer_amount: 0,
+            operation_nonce: 0,
+            performance_fee_bps: 0,
+            treasury_id: None,
+            accrued_performance_fee: 0,
+            exit_fee_bps: 0,
+            exit_fee_duration_in_sec: 0,
+            exit_fee_to_treasury: false,
+            min_holding_period_in_sec: 0,
+            min_stake_amount: MIN_TOTAL_STAKED,
+            referral_fee_bps: 0,
+            referral_pool: 0,
+            total_referral_earnings: 0,
 
             shares: UnorderedMap::new(StorageKey::Deposits),
+            accounts: LookupMap::new(StorageKey::VAccounts),
+            legacy_accounts: LookupMap::new(StorageKey::Accounts),
+            shares_checkpoints: LookupMap::new(StorageKey::SharesCheckpoints),
+            total_staked_checkpoints: Vector::new(StorageKey::TotalStakedCheckpoints),
+            delegated_power: LookupMap::new(StorageKey::DelegatedPower),
+
+            proposers: UnorderedSet::new(StorageKey::Proposers),
+            polls: Vector::new(StorageKey::Polls),
+            poll_votes: LookupMap::new(StorageKey::PollVotes),
+
+            referees: LookupMap::new(StorageKey::Referees),
+            referral_earnings: LookupMap::new(StorageKey::ReferralEarnings),
+
+            price_history: Vector::new(StorageKey::PriceHistory),
+            price_history_head: 0,
+            last_price_snapshot_time_in_sec: None,
+            price_snapshot_interval_in_sec: DEFAULT_PRICE_SNAPSHOT_INTERVAL_IN_SEC,
+
+            pools: UnorderedMap::new(StorageKey::Pools),
+
+            total_points: U256::zero(),
+            total_points_time_in_sec: nano_to_sec(env::block_timestamp()),
+            season_start_time_in_sec: nano_to_sec(env::block_timestamp()),
+            seasons: Vector::new(StorageKey::Seasons),
+            season_points: LookupMap::new(StorageKey::SeasonPoints),
+
+            reward_vesting_duration_in_sec: 0,
+            vestings: LookupMap::new(StorageKey::Vestings),
+            total_vesting: 0,
+
+            grants: LookupMap::new(StorageKey::Grants),
+
+            positions_nft: NonFungibleToken::new(
+                StorageKey::PositionsNft,
+                env::current_account_id(),
+                Some(StorageKey::PositionsNftMetadata),
+                Some(StorageKey::PositionsNftEnumeration),
+                Some(StorageKey::PositionsNftApprovals),
+            ),
+            positions: LookupMap::new(StorageKey::Positions),
+            position_owners: UnorderedSet::new(StorageKey::PositionOwners),
+            next_position_id: 0,
+            max_lock_boost_bps: 0,
+
+            boost_ledger: WeightLedger {
+                prev_distribution_time_in_sec: nano_to_sec(env::block_timestamp()),
+                ..Default::default()
+            },
+            boost_collections: UnorderedMap::new(StorageKey::BoostCollections),
+            boost_nfts: LookupMap::new(StorageKey::BoostNfts),
+            pending_nft_withdraws: LookupSet::new(StorageKey::PendingNftWithdraws),
+
+            swap_dex_id: None,
+            swap_in_progress: false,
+            reward_tokens: UnorderedMap::new(StorageKey::RewardTokens),
+
+            strategy_id: None,
+            deployed_amount: 0,
+            liquidity_buffer_bps: 0,
+            strategy_in_progress: false,
+            withdraw_queue: LookupMap::new(StorageKey::WithdrawQueue),
+            withdraw_queue_head: 0,
+            withdraw_queue_tail: 0,
+            total_queued_withdraw: 0,
+
+            slasher_id: None,
+            max_slash_bps: 0,
+            slash_period_start_in_sec: 0,
+            slash_period_locked_amount: 0,
+            slashed_in_period: 0,
+            unstake_cooldown_in_sec: 0,
+            unstake_window_in_sec: 0,
+            slashes: Vector::new(StorageKey::Slashes),
+            total_slashed: 0,
         }
     }
 }
@@ -0,0 +1,251 @@
+use crate::*;
+#[cfg(not(target_arch = "wasm32"))]
+use near_sdk::serde::Deserialize;
+use std::cmp::{max, min};
+
+/// Seasons are numbered from 0, the current one is `seasons.len()`
+pub type SeasonId = u64;
+
+/// A finished season
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct Season {
+    pub start_time_in_sec: u32,
+    pub end_time_in_sec: u32,
+    pub total_points: U256,
+    /// points of a reset season are discarded
+    pub reset: bool,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, Deserialize))]
+pub struct SeasonView {
+    pub season_id: SeasonId,
+    pub start_time_in_sec: u32,
+    /// None for the current season
+    pub end_time_in_sec: Option<u32>,
+    // at call time for the current season
+    pub total_points: U256,
+    pub reset: bool,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, Deserialize))]
+pub struct AccountPoints {
+    pub account_id: AccountId,
+    pub points: U256,
+}
+
+/// `points` grown by `shares` held from `from_time` to `to_time`
+fn accrue(points: U256, shares: Balance, from_time: u32, to_time: u32) -> U256 {
+    points + U256::from(shares) * U256::from(to_time.saturating_sub(from_time))
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Finish the current season keeping its points for `get_season_points`, and start counting a new one from now.
+    /// Requirements:
+    /// * Can only be called by the owner.
+    pub fn start_season(&mut self) -> SeasonId {
+        self.assert_owner();
+        self.internal_finish_season(false)
+    }
+
+    /// Discard points of the current season and start counting a new one from now.
+    /// Requirements:
+    /// * Can only be called by the owner.
+    pub fn reset_season(&mut self) -> SeasonId {
+        self.assert_owner();
+        self.internal_finish_season(true)
+    }
+
+    pub fn get_season(&self, season_id: Option<SeasonId>) -> SeasonView {
+        let cur_season_id = self.seasons.len();
+        let season_id = season_id.unwrap_or(cur_season_id);
+        if season_id == cur_season_id {
+            return SeasonView {
+                season_id,
+                start_time_in_sec: self.season_start_time_in_sec,
+                end_time_in_sec: None,
+                total_points: self.internal_total_points(),
+                reset: false,
+            };
+        }
+        let season = self.seasons.get(season_id).expect("ERR_NO_SEASON");
+        SeasonView {
+            season_id,
+            start_time_in_sec: season.start_time_in_sec,
+            end_time_in_sec: Some(season.end_time_in_sec),
+            total_points: season.total_points,
+            reset: season.reset,
+        }
+    }
+
+    /// Points of the account in the current season at call time
+    pub fn get_points(&self, account_id: AccountId) -> U256 {
+        self.internal_season_points(&account_id, self.seasons.len())
+    }
+
+    pub fn get_total_points(&self) -> U256 {
+        self.internal_total_points()
+    }
+
+    /// Points of the account in a finished or the current season
+    pub fn get_season_points(&self, account_id: AccountId, season_id: SeasonId) -> U256 {
+        assert!(season_id <= self.seasons.len(), "ERR_NO_SEASON");
+        self.internal_season_points(&account_id, season_id)
+    }
+
+    /// Stakers from `from_index` in the order they first staked, then owners of locked positions without shares,
+    /// with their points in the current season at call time, ranked within the page, the most first.
+    /// Pages are merged by the caller to rank all stakers.
+    /// Points of locked positions are counted for their owners, the contract itself is skipped.
+    pub fn get_points_ranking(&self, from_index: Option<u64>, limit: Option<u64>) -> Vec<AccountPoints> {
+        let season_id = self.seasons.len();
+        let account_ids = self.shares.keys_as_vector();
+        let position_owners = self.position_owners.as_vector();
+        let from_index = from_index.unwrap_or(0);
+        let to_index = min(
+            account_ids.len() + position_owners.len(),
+            from_index.saturating_add(limit.unwrap_or(100)),
+        );
+        let mut ranking: Vec<AccountPoints> = (from_index..to_index)
+            .filter_map(|index| {
+                if index < account_ids.len() {
+                    account_ids.get(index)
+                } else {
+                    // owners with shares are ranked with the stakers
+                    position_owners
+                        .get(index - account_ids.len())
+                        .filter(|account_id| self.shares.get(account_id).is_none())
+                }
+            })
+            .filter(|account_id| account_id != &env::current_account_id())
+            .map(|account_id| AccountPoints {
+                points: self.internal_season_points(&account_id, season_id),
+                account_id,
+            })
+            .filter(|account_points| !account_points.points.is_zero())
+            .collect();
+        ranking.sort_by(|a, b| b.points.cmp(&a.points).then_with(|| a.account_id.cmp(&b.account_id)));
+        ranking
+    }
+}
+
+impl Contract {
+    /// Accrue points of the account and the total for the time since their last update,
+    /// must be called before shares change.
+    pub(crate) fn internal_accrue_points(&mut self, account_id: &AccountId) {
+        let cur_time = nano_to_sec(env::block_timestamp());
+        let season_id = self.seasons.len();
+
+        let mut account = self.internal_get_account(account_id);
+        let shares = self.internal_points_shares(account_id, &account);
+        if account.points_season_id < season_id {
+            // keep points of the season of the last update, the later finished ones are read from the checkpoints
+            let prev_season_id = account.points_season_id;
+            let points = self.internal_account_season_points(account_id, &account, shares, prev_season_id);
+            if !points.is_zero() {
+                self.season_points.insert(&(prev_season_id, account_id.clone()), &points);
+            }
+            account.points = U256::zero();
+            account.points_season_id = season_id;
+        }
+        account.points = accrue(
+            account.points,
+            shares,
+            max(account.points_time_in_sec, self.season_start_time_in_sec),
+            cur_time,
+        );
+        account.points_time_in_sec = cur_time;
+        self.internal_set_account(account_id, &account);
+
+        self.internal_accrue_total_points(cur_time);
+    }
+
+    /// Accrue the total points up to `cur_time`, must be called before the share supply changes
+    pub(crate) fn internal_accrue_total_points(&mut self, cur_time: u32) {
+        self.total_points = accrue(self.total_points, self.ledger.total_staked, self.total_points_time_in_sec, cur_time);
+        self.total_points_time_in_sec = cur_time;
+    }
+
+    fn internal_total_points(&self) -> U256 {
+        accrue(
+            self.total_points,
+            self.ledger.total_staked,
+            self.total_points_time_in_sec,
+            nano_to_sec(env::block_timestamp()),
+        )
+    }
+
+    fn internal_finish_season(&mut self, reset: bool) -> SeasonId {
+        let cur_time = nano_to_sec(env::block_timestamp());
+        let season = Season {
+            start_time_in_sec: self.season_start_time_in_sec,
+            end_time_in_sec: cur_time,
+            total_points: if reset { U256::zero() } else { self.internal_total_points() },
+            reset,
+        };
+        self.seasons.push(&season);
+        self.season_start_time_in_sec = cur_time;
+        self.total_points = U256::zero();
+        self.total_points_time_in_sec = cur_time;
+        self.seasons.len()
+    }
+
+    fn internal_season_points(&self, account_id: &AccountId, season_id: SeasonId) -> U256 {
+        let account = self.internal_get_account(account_id);
+        let shares = self.internal_points_shares(account_id, &account);
+        self.internal_account_season_points(account_id, &account, shares, season_id)
+    }
+
+    /// Shares the points of the account grow by, its own and those of its locked positions
+    pub(crate) fn internal_points_shares(&self, account_id: &AccountId, account: &Account) -> Balance {
+        if account_id == &env::current_account_id() {
+            // the contract holds the shares of the positions, their owners get the points
+            return 0;
+        }
+        self.shares.get(account_id).unwrap_or_default() + account.position_shares
+    }
+
+    /// Add or remove shares of a locked position owned by the account
+    pub(crate) fn internal_update_position_shares(&mut self, owner_id: &AccountId, shares: Balance, add: bool) {
+        self.internal_accrue_points(owner_id);
+        let mut account = self.internal_get_account(owner_id);
+        if add {
+            account.position_shares += shares;
+        } else {
+            account.position_shares -= shares;
+        }
+        self.internal_set_account(owner_id, &account);
+        self.internal_checkpoint_shares(owner_id);
+        // the owner shows in the points ranking while it holds positions
+        if account.position_shares > 0 {
+            self.position_owners.insert(owner_id);
+        } else {
+            self.position_owners.remove(owner_id);
+        }
+    }
+
+    /// Points of the account holding `shares` since its last update
+    fn internal_account_season_points(&self, account_id: &AccountId, account: &Account, shares: Balance, season_id: SeasonId) -> U256 {
+        let (start_time, end_time, reset) = match self.seasons.get(season_id) {
+            Some(season) => (season.start_time_in_sec, season.end_time_in_sec, season.reset),
+            None => (self.season_start_time_in_sec, nano_to_sec(env::block_timestamp()), false),
+        };
+        if reset {
+            U256::zero()
+        } else if account.points_season_id == season_id {
+            accrue(account.points, shares, max(account.points_time_in_sec, start_time), end_time)
+        } else if account.points_season_id < season_id {
+            // shares didn't change during the season
+            accrue(U256::zero(), shares, start_time, end_time)
+        } else {
+            // without a record shares didn't change during the season, or its points are zero
+            self.season_points
+                .get(&(season_id, account_id.clone()))
+                .unwrap_or_else(|| accrue(U256::zero(), self.internal_shares_at(account_id, start_time), start_time, end_time))
+        }
+    }
+}
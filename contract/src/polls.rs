@@ -0,0 +1,103 @@
+use crate::*;
+use near_sdk::serde::Deserialize;
+
+pub const MIN_POLL_OPTIONS: usize = 2;
+pub const MAX_POLL_OPTIONS: usize = 16;
+
+pub type PollId = u64;
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct Poll {
+    pub proposer_id: AccountId,
+    pub title: String,
+    pub options: Vec<String>,
+    /// voting power is taken from shares at the end of this second
+    pub snapshot_time_in_sec: u32,
+    pub deadline_in_sec: u32,
+    // total voted shares for every option
+    pub votes: Vec<WrappedBalance>,
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn add_proposer(&mut self, account_id: AccountId) {
+        self.assert_owner();
+        self.proposers.insert(&account_id);
+    }
+
+    pub fn remove_proposer(&mut self, account_id: AccountId) {
+        self.assert_owner();
+        self.proposers.remove(&account_id);
+    }
+
+    pub fn get_proposers(&self) -> Vec<AccountId> {
+        self.proposers.to_vec()
+    }
+
+    /// Create a poll, voting power of every staker is their shares right before the poll creation.
+    /// Requirements:
+    /// * Can only be called by the owner or a proposer.
+    /// * `deadline_in_sec` must be in the future.
+    pub fn create_poll(&mut self, title: String, options: Vec<String>, deadline_in_sec: u32) -> PollId {
+        let proposer_id = env::predecessor_account_id();
+        assert!(
+            proposer_id == self.owner_id || self.proposers.contains(&proposer_id),
+            "ERR_NOT_AN_OWNER_OR_PROPOSER"
+        );
+        assert!(
+            options.len() >= MIN_POLL_OPTIONS && options.len() <= MAX_POLL_OPTIONS,
+            "ERR_ILLEGAL_POLL_OPTIONS"
+        );
+        let cur_time = nano_to_sec(env::block_timestamp());
+        assert!(deadline_in_sec > cur_time, "ERR_DEADLINE_IS_PAST_TIME");
+
+        let poll_id = self.polls.len();
+        let poll = Poll {
+            proposer_id,
+            title,
+            votes: vec![0.into(); options.len()],
+            options,
+            snapshot_time_in_sec: cur_time - 1,
+            deadline_in_sec,
+        };
+        self.polls.push(&poll);
+
+        events::emit::create_poll(poll_id, &poll);
+        poll_id
+    }
+
+    /// Vote for `option` with shares of the predecessor account and its locked positions at the poll snapshot.
+    /// Tokens stay unlocked.
+    pub fn vote(&mut self, poll_id: PollId, option: u32) {
+        let account_id = env::predecessor_account_id();
+        let mut poll = self.polls.get(poll_id).expect("ERR_NO_POLL");
+        assert!(nano_to_sec(env::block_timestamp()) < poll.deadline_in_sec, "ERR_POLL_IS_CLOSED");
+        assert!((option as usize) < poll.options.len(), "ERR_ILLEGAL_OPTION");
+        assert!(self.poll_votes.get(&(poll_id, account_id.clone())).is_none(), "ERR_ALREADY_VOTED");
+
+        let weight = self.internal_shares_at(&account_id, poll.snapshot_time_in_sec);
+        assert!(weight > 0, "ERR_NO_VOTING_POWER");
+
+        poll.votes[option as usize] = (poll.votes[option as usize].0 + weight).into();
+        self.polls.replace(poll_id, &poll);
+        self.poll_votes.insert(&(poll_id, account_id.clone()), &option);
+
+        events::emit::vote(poll_id, &account_id, option, weight);
+    }
+
+    pub fn get_poll(&self, poll_id: PollId) -> Option<Poll> {
+        self.polls.get(poll_id)
+    }
+
+    pub fn get_polls(&self, from_index: Option<u64>, limit: Option<u64>) -> Vec<Poll> {
+        let from_index = from_index.unwrap_or(0);
+        let to_index = std::cmp::min(self.polls.len(), from_index.saturating_add(limit.unwrap_or(self.polls.len())));
+        (from_index..to_index).map(|index| self.polls.get(index).unwrap()).collect()
+    }
+
+    pub fn get_vote(&self, poll_id: PollId, account_id: AccountId) -> Option<u32> {
+        self.poll_votes.get(&(poll_id, account_id))
+    }
+}
@@ -0,0 +1,264 @@
+use crate::account::ShareBalances;
+use crate::ft::{ext_self, TokenReceiverMsg, GAS_FOR_AFTER_FT_TRANSFER, GAS_FOR_FT_TRANSFER};
+use crate::*;
+use near_contract_standards::fungible_token::core::ext_ft_core;
+#[cfg(not(target_arch = "wasm32"))]
+use near_sdk::serde::Deserialize;
+use near_sdk::{assert_one_yocto, PromiseResult, ONE_YOCTO};
+use std::cmp::min;
+
+/// Pools added by the owner are numbered from 1, no pool id means the main pool of the contract
+pub type PoolId = u64;
+
+/// A pool hosted next to the main one, with its own token, reward schedule and shares.
+/// It only stakes, unstakes and distributes reward. The unstake cooldown of the account applies to it,
+/// while the exit fee, holding period, share checkpoints, points, referrals and voting work with the main pool only.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct Pool {
+    pub token_id: AccountId,
+    /// reward stream and share supply of the pool
+    pub ledger: Ledger,
+    /// staked token sent out by ft_transfer which is waiting for a callback
+    pub pending_transfer_amount: Balance,
+    pub account_number: u64,
+    // staked amount of every staking user
+    pub shares: LookupMap<AccountId, Balance>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, Deserialize))]
+pub struct PoolView {
+    pub pool_id: PoolId,
+    pub token_id: AccountId,
+    // at call time, the amount of undistributed reward
+    pub cur_undistributed_reward: WrappedBalance,
+    // at call time, the amount of staked token
+    pub cur_locked_token_amount: WrappedBalance,
+    pub total_staked: WrappedBalance,
+    pub prev_distribution_time_in_sec: u32,
+    pub reward_genesis_time_in_sec: u32,
+    pub reward_per_sec: WrappedBalance,
+    pub account_number: u64,
+}
+
+impl Pool {
+    fn new(pool_id: PoolId, token_id: AccountId, reward_per_sec: Balance, reward_genesis_time_in_sec: u32) -> Self {
+        Self {
+            token_id,
+            ledger: Ledger::new(reward_per_sec, reward_genesis_time_in_sec),
+            pending_transfer_amount: 0,
+            account_number: 0,
+            shares: LookupMap::new(StorageKey::PoolShares { pool_id }),
+        }
+    }
+
+    fn distribute_reward(&mut self) {
+        self.ledger.distribute_reward(nano_to_sec(env::block_timestamp()));
+    }
+
+    fn stake(&mut self, account_id: &AccountId, amount: Balance) {
+        let minted = self.ledger.stake(amount);
+        assert!(minted > 0, "ERR_STAKE_TOO_SMALL");
+        self.deposit(account_id, minted);
+    }
+
+    /// burns `amount` shares and returns the unlocked token amount
+    fn unstake(&mut self, account_id: &AccountId, amount: Balance) -> Balance {
+        let (unlocked, _) = self.ledger.unstake(amount, 0);
+        self.withdraw(account_id, amount);
+        unlocked
+    }
+
+    fn deposit(&mut self, account_id: &AccountId, amount: Balance) {
+        if !self.shares.contains_key(account_id) {
+            self.account_number += 1;
+        }
+        self.shares.deposit_shares(account_id, amount);
+    }
+
+    fn withdraw(&mut self, account_id: &AccountId, amount: Balance) {
+        self.shares.withdraw_shares(account_id, amount);
+    }
+}
+
+impl Contract {
+    pub(crate) fn internal_get_pool(&self, pool_id: PoolId) -> Pool {
+        self.pools.get(&pool_id).expect("ERR_NO_POOL")
+    }
+
+    pub(crate) fn internal_pool_on_transfer(
+        &mut self,
+        pool_id: PoolId,
+        token_id: &AccountId,
+        sender_id: &AccountId,
+        amount: Balance,
+        msg: TokenReceiverMsg,
+    ) {
+        let mut pool = self.internal_get_pool(pool_id);
+        assert_eq!(&pool.token_id, token_id, "ERR_ILLEGAL_TOKEN");
+
+        // Checkpoint
+        pool.distribute_reward();
+
+        match msg {
+            TokenReceiverMsg::Stake { referrer_id, .. } => {
+                assert!(referrer_id.is_none(), "ERR_POOL_HAS_NO_REFERRALS");
+                pool.stake(sender_id, amount);
+                events::emit::pool_add_stake(pool_id, sender_id, amount, token_id);
+            }
+            TokenReceiverMsg::AddRewards { .. } => {
+                pool.ledger.undistributed_reward += amount;
+                events::emit::pool_add_rewards(pool_id, sender_id, amount, token_id);
+            }
+            TokenReceiverMsg::AddReferralPool => env::panic_str("ERR_POOL_HAS_NO_REFERRALS"),
+            TokenReceiverMsg::AddBoostRewards => env::panic_str("ERR_POOL_HAS_NO_BOOSTS"),
+            TokenReceiverMsg::SwapRewards { .. } => env::panic_str("ERR_POOL_HAS_NO_SWAPS"),
+            TokenReceiverMsg::GrantStake { .. } => env::panic_str("ERR_POOL_HAS_NO_GRANTS"),
+            TokenReceiverMsg::LockStake { .. } => env::panic_str("ERR_POOL_HAS_NO_POSITIONS"),
+        }
+        self.pools.insert(&pool_id, &pool);
+    }
+
+    pub(crate) fn internal_pool_unstake(&mut self, pool_id: PoolId, amount: Option<WrappedBalance>) -> Promise {
+        let mut pool = self.internal_get_pool(pool_id);
+        // Checkpoint
+        pool.distribute_reward();
+
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let amount: Balance = amount.unwrap_or(pool.shares.get(&account_id).unwrap_or_default().into()).into();
+        self.assert_unstake_cooldown_passed(&account_id);
+
+        let unlocked = pool.unstake(&account_id, amount);
+        pool.pending_transfer_amount += unlocked;
+        self.pools.insert(&pool_id, &pool);
+
+        ext_ft_core::ext(pool.token_id)
+            .with_attached_deposit(ONE_YOCTO)
+            .with_static_gas(GAS_FOR_FT_TRANSFER)
+            .ft_transfer(account_id.clone(), unlocked.into(), None)
+            .then(
+                ext_self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_AFTER_FT_TRANSFER)
+                    .callback_post_pool_unstake(pool_id, account_id, unlocked.into(), amount.into()),
+            )
+    }
+
+    pub(crate) fn internal_modify_pool_reward_per_sec(&mut self, pool_id: PoolId, reward_per_sec: Balance, distribute_before_change: bool) {
+        let mut pool = self.internal_get_pool(pool_id);
+        if distribute_before_change {
+            pool.distribute_reward();
+        }
+        pool.ledger.reward_per_sec = reward_per_sec;
+        self.pools.insert(&pool_id, &pool);
+    }
+
+    pub(crate) fn internal_reset_pool_reward_genesis_time_in_sec(&mut self, pool_id: PoolId, reward_genesis_time_in_sec: u32) {
+        let mut pool = self.internal_get_pool(pool_id);
+        if pool.ledger.reward_genesis_time_in_sec < nano_to_sec(env::block_timestamp()) {
+            env::panic_str("ERR_REWARD_GENESIS_TIME_PASSED");
+        }
+        pool.ledger.reward_genesis_time_in_sec = reward_genesis_time_in_sec;
+        pool.ledger.prev_distribution_time_in_sec = reward_genesis_time_in_sec;
+        self.pools.insert(&pool_id, &pool);
+    }
+
+    /// tokens backing stakes and rewards of the pools which stake `token_id`
+    pub(crate) fn internal_pools_accounted_balance(&self, token_id: &AccountId) -> Balance {
+        self.pools
+            .values()
+            .filter(|pool| &pool.token_id == token_id)
+            .map(|pool| pool.ledger.locked_token_amount + pool.ledger.undistributed_reward + pool.pending_transfer_amount)
+            .sum()
+    }
+
+    fn internal_pool_view(&self, pool_id: PoolId, pool: Pool) -> PoolView {
+        let to_be_distributed = pool.ledger.try_distribute_reward(nano_to_sec(env::block_timestamp()));
+        PoolView {
+            pool_id,
+            token_id: pool.token_id,
+            cur_undistributed_reward: (pool.ledger.undistributed_reward - to_be_distributed).into(),
+            cur_locked_token_amount: (pool.ledger.locked_token_amount + to_be_distributed).into(),
+            total_staked: pool.ledger.total_staked.into(),
+            prev_distribution_time_in_sec: pool.ledger.prev_distribution_time_in_sec,
+            reward_genesis_time_in_sec: pool.ledger.reward_genesis_time_in_sec,
+            reward_per_sec: pool.ledger.reward_per_sec.into(),
+            account_number: pool.account_number,
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Add a pool which stakes `token_id`, stake and rewards go to it with `pool_id` in the `ft_transfer_call` msg.
+    /// Reward starts in 30 days by default.
+    /// Requirements:
+    /// * Can only be called by the owner.
+    pub fn add_pool(
+        &mut self,
+        token_id: AccountId,
+        reward_per_sec: Option<WrappedBalance>,
+        reward_genesis_time_in_sec: Option<u32>,
+    ) -> PoolId {
+        self.assert_owner();
+        let cur_time = nano_to_sec(env::block_timestamp());
+        let reward_genesis_time_in_sec = reward_genesis_time_in_sec.unwrap_or(cur_time + DURATION_30DAYS_IN_SEC);
+        assert!(reward_genesis_time_in_sec >= cur_time, "ERR_RESET_TIME_IS_PAST_TIME");
+
+        let pool_id = self.pools.len() + 1;
+        let pool = Pool::new(pool_id, token_id, reward_per_sec.unwrap_or(U128(0)).0, reward_genesis_time_in_sec);
+        events::emit::add_pool(pool_id, &pool.token_id);
+        self.pools.insert(&pool_id, &pool);
+        pool_id
+    }
+
+    #[private]
+    pub fn callback_post_pool_unstake(&mut self, pool_id: PoolId, sender_id: AccountId, amount: WrappedBalance, share: WrappedBalance) {
+        assert_eq!(env::promise_results_count(), 1, "Err: expected 1 promise result from unstake");
+        let mut pool = self.internal_get_pool(pool_id);
+        pool.pending_transfer_amount -= amount.0;
+
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(_) => {
+                events::emit::pool_withdraw_succeeded(pool_id, &sender_id, amount.0, &pool.token_id);
+            }
+            PromiseResult::Failed => {
+                // This restakes the sent tokens at the current price like the main pool,
+                // the old shares would take reward distributed since the unstake from other stakers.
+                // Checkpoint
+                pool.distribute_reward();
+                let minted = pool.ledger.stake(amount.0);
+                pool.deposit(&sender_id, minted);
+                log!(
+                    "Account {} unstake of {} shares from pool {} failed, {} tokens are staked back.",
+                    sender_id,
+                    share.0,
+                    pool_id,
+                    amount.0
+                );
+
+                events::emit::pool_withdraw_failed(pool_id, &sender_id, amount.0, &pool.token_id);
+            }
+        };
+        self.pools.insert(&pool_id, &pool);
+    }
+
+    pub fn get_pool(&self, pool_id: PoolId) -> Option<PoolView> {
+        self.pools.get(&pool_id).map(|pool| self.internal_pool_view(pool_id, pool))
+    }
+
+    pub fn get_pools(&self, from_index: Option<u64>, limit: Option<u64>) -> Vec<PoolView> {
+        let from_index = from_index.unwrap_or(0);
+        let to_index = min(self.pools.len(), from_index.saturating_add(limit.unwrap_or(self.pools.len())));
+        // pool ids are consecutive starting from 1
+        (from_index..to_index)
+            .map(|index| self.internal_pool_view(index + 1, self.internal_get_pool(index + 1)))
+            .collect()
+    }
+
+    pub fn get_number_of_pools(&self) -> u64 {
+        self.pools.len()
+    }
+}
@@ -0,0 +1,408 @@
+use crate::accounting::WeightStake;
+use crate::*;
+use near_contract_standards::non_fungible_token::core::{NonFungibleTokenCore, NonFungibleTokenResolver};
+use near_contract_standards::non_fungible_token::events::{NftBurn, NftMint};
+use near_contract_standards::non_fungible_token::metadata::{
+    NFTContractMetadata, NonFungibleTokenMetadataProvider, TokenMetadata, NFT_METADATA_SPEC,
+};
+use near_contract_standards::non_fungible_token::Token;
+#[cfg(not(target_arch = "wasm32"))]
+use near_sdk::serde::Deserialize;
+use near_sdk::{assert_one_yocto, serde_json, PromiseOrValue};
+use std::collections::HashMap;
+
+/// The longest lock, it gets the whole max_lock_boost
+pub const MAX_LOCK_DURATION_IN_SEC: u32 = 4 * DURATION_1YEAR_IN_SEC;
+/// Hard cap of the extra boost of the longest lock, 3x on top of 1x
+pub const MAX_LOCK_BOOST_BPS: u32 = 30_000;
+
+/// Shares locked until lock_end_time, held by the contract for the owner of the NFT with the same token id
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct Position {
+    /// staked token of the shares, the part of unstaked tokens above it is reward
+    pub amount: Balance,
+    pub shares: Balance,
+    pub lock_end_time_in_sec: u32,
+    /// reward weight multiplier in basis points, 10_000 is 1x
+    pub boost_bps: u32,
+    /// weight of the position in the boost reward stream
+    pub boost_stake: WeightStake,
+    /// when the shares were staked, the holding period and the exit fee count from it
+    pub stake_time_in_sec: u32,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, Deserialize))]
+pub struct PositionView {
+    pub token_id: TokenId,
+    pub owner_id: AccountId,
+    pub amount: WrappedBalance,
+    pub shares: WrappedBalance,
+    // at call time, staked token to unlock by the shares
+    pub cur_amount: WrappedBalance,
+    pub lock_end_time_in_sec: u32,
+    pub boost_bps: u32,
+    pub stake_time_in_sec: u32,
+}
+
+near_contract_standards::impl_non_fungible_token_approval!(Contract, positions_nft);
+near_contract_standards::impl_non_fungible_token_enumeration!(Contract, positions_nft);
+
+/// Transfers of a position move its shares to the points of the new owner
+#[near_bindgen]
+impl NonFungibleTokenCore for Contract {
+    #[payable]
+    fn nft_transfer(&mut self, receiver_id: AccountId, token_id: TokenId, approval_id: Option<u64>, memo: Option<String>) {
+        let owner_id = self.positions_nft.owner_by_id.get(&token_id).expect("Token not found");
+        self.positions_nft
+            .nft_transfer(receiver_id.clone(), token_id.clone(), approval_id, memo);
+        self.internal_transfer_position_shares(&owner_id, &receiver_id, &token_id);
+    }
+
+    #[payable]
+    fn nft_transfer_call(
+        &mut self,
+        receiver_id: AccountId,
+        token_id: TokenId,
+        approval_id: Option<u64>,
+        memo: Option<String>,
+        msg: String,
+    ) -> PromiseOrValue<bool> {
+        let owner_id = self.positions_nft.owner_by_id.get(&token_id).expect("Token not found");
+        let result = self
+            .positions_nft
+            .nft_transfer_call(receiver_id.clone(), token_id.clone(), approval_id, memo, msg);
+        self.internal_transfer_position_shares(&owner_id, &receiver_id, &token_id);
+        result
+    }
+
+    fn nft_token(&self, token_id: TokenId) -> Option<Token> {
+        self.positions_nft.nft_token(token_id)
+    }
+}
+
+#[near_bindgen]
+impl NonFungibleTokenResolver for Contract {
+    #[private]
+    fn nft_resolve_transfer(
+        &mut self,
+        previous_owner_id: AccountId,
+        receiver_id: AccountId,
+        token_id: TokenId,
+        approved_account_ids: Option<HashMap<AccountId, u64>>,
+    ) -> bool {
+        let transferred = self.positions_nft.nft_resolve_transfer(
+            previous_owner_id.clone(),
+            receiver_id.clone(),
+            token_id.clone(),
+            approved_account_ids,
+        );
+        if !transferred {
+            // the position went back to the previous owner
+            self.internal_transfer_position_shares(&receiver_id, &previous_owner_id, &token_id);
+        }
+        transferred
+    }
+}
+
+#[near_bindgen]
+impl NonFungibleTokenMetadataProvider for Contract {
+    fn nft_metadata(&self) -> NFTContractMetadata {
+        NFTContractMetadata {
+            spec: NFT_METADATA_SPEC.to_string(),
+            name: format!("Locked {} stake", self.token_id),
+            symbol: "LOCKED".to_string(),
+            icon: None,
+            base_uri: None,
+            reference: None,
+            reference_hash: None,
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Set the extra boost of the longest lock, shorter locks get a linear part of it.
+    /// Requirements:
+    /// * Can only be called by the owner.
+    /// * `max_lock_boost_bps` must not exceed 30_000.
+    pub fn set_max_lock_boost(&mut self, max_lock_boost_bps: u32) {
+        self.assert_owner();
+        assert!(max_lock_boost_bps <= MAX_LOCK_BOOST_BPS, "ERR_BOOST_TOO_HIGH");
+        self.max_lock_boost_bps = max_lock_boost_bps;
+    }
+
+    pub fn get_max_lock_boost(&self) -> u32 {
+        self.max_lock_boost_bps
+    }
+
+    pub fn get_position(&self, token_id: TokenId) -> Option<PositionView> {
+        let position = self.positions.get(&token_id)?;
+        let cur_time = nano_to_sec(env::block_timestamp());
+        let cur_amount = (U256::from(position.shares) * U256::from(self.internal_virtual_price_at(cur_time))
+            / U256::from(accounting::INITIAL_VIRTUAL_PRICE))
+        .as_u128();
+        Some(PositionView {
+            owner_id: self.positions_nft.owner_by_id.get(&token_id).unwrap(),
+            token_id,
+            amount: position.amount.into(),
+            shares: position.shares.into(),
+            cur_amount: cur_amount.into(),
+            lock_end_time_in_sec: position.lock_end_time_in_sec,
+            boost_bps: position.boost_bps,
+            stake_time_in_sec: position.stake_time_in_sec,
+        })
+    }
+
+    /// Merge `other_token_id` into `token_id`, the merged position is locked until the later lock end.
+    /// Requirements:
+    /// * The predecessor account should own both positions.
+    /// * Requires attached deposit of exactly 1 yoctoNEAR.
+    #[payable]
+    pub fn merge_positions(&mut self, token_id: TokenId, other_token_id: TokenId) {
+        assert_one_yocto();
+        assert_ne!(token_id, other_token_id, "ERR_SAME_POSITION");
+        let owner_id = env::predecessor_account_id();
+        let mut position = self.internal_get_own_position(&owner_id, &token_id);
+        let other = self.internal_get_own_position(&owner_id, &other_token_id);
+
+        let shares = position.shares + other.shares;
+        position.boost_bps = ((U256::from(position.boost_bps) * U256::from(position.shares)
+            + U256::from(other.boost_bps) * U256::from(other.shares))
+            / U256::from(shares))
+        .as_u32();
+        position.amount += other.amount;
+        position.shares = shares;
+        position.lock_end_time_in_sec = std::cmp::max(position.lock_end_time_in_sec, other.lock_end_time_in_sec);
+        position.stake_time_in_sec = std::cmp::max(position.stake_time_in_sec, other.stake_time_in_sec);
+
+        self.internal_burn_position(&owner_id, &other_token_id);
+        self.internal_set_position(&token_id, &mut position);
+    }
+
+    /// Move `shares` of the position to a new one with the same lock end and boost, returns its token id.
+    /// Requirements:
+    /// * The predecessor account should own the position.
+    /// * `shares` must be positive and less than the position shares.
+    /// * Both positions must keep at least `min_stake_amount` of tokens.
+    /// * Requires attached deposit of exactly 1 yoctoNEAR.
+    #[payable]
+    pub fn split_position(&mut self, token_id: TokenId, shares: WrappedBalance) -> TokenId {
+        assert_one_yocto();
+        let owner_id = env::predecessor_account_id();
+        let mut position = self.internal_get_own_position(&owner_id, &token_id);
+        let shares: Balance = shares.into();
+        assert!(shares > 0 && shares < position.shares, "ERR_ILLEGAL_SPLIT");
+
+        let amount = (U256::from(position.amount) * U256::from(shares) / U256::from(position.shares)).as_u128();
+        assert!(
+            amount >= self.min_stake_amount && position.amount - amount >= self.min_stake_amount,
+            "ERR_POSITION_BELOW_MINIMUM"
+        );
+        position.amount -= amount;
+        position.shares -= shares;
+        self.internal_set_position(&token_id, &mut position);
+
+        self.internal_mint_position(
+            &owner_id,
+            Position {
+                amount,
+                shares,
+                lock_end_time_in_sec: position.lock_end_time_in_sec,
+                boost_bps: position.boost_bps,
+                boost_stake: WeightStake::default(),
+                stake_time_in_sec: position.stake_time_in_sec,
+            },
+        )
+    }
+
+    /// Burn the position and send its tokens to the predecessor account, like `unstake`.
+    /// Requirements:
+    /// * The predecessor account should own the position.
+    /// * The lock of the position should be expired.
+    /// * The shares of the position should be held for at least `min_holding_period_in_sec`.
+    /// * While the unstake cooldown is on, the predecessor must be in its unstake window.
+    /// The exit fee counts from the stake of the position.
+    /// * Requires attached deposit of exactly 1 yoctoNEAR.
+    #[payable]
+    pub fn unstake_position(&mut self, token_id: TokenId) -> PromiseOrValue<()> {
+        // Checkpoint
+        self.distribute_reward();
+
+        assert_one_yocto();
+        let owner_id = env::predecessor_account_id();
+        let position = self.internal_get_own_position(&owner_id, &token_id);
+        assert!(
+            nano_to_sec(env::block_timestamp()) >= position.lock_end_time_in_sec,
+            "ERR_POSITION_IS_LOCKED"
+        );
+        let cur_time = nano_to_sec(env::block_timestamp());
+        assert!(
+            cur_time >= position.stake_time_in_sec.saturating_add(self.min_holding_period_in_sec),
+            "ERR_HOLDING_PERIOD_NOT_PASSED"
+        );
+        self.assert_unstake_cooldown_passed(&owner_id);
+
+        self.internal_burn_position(&owner_id, &token_id);
+        let exit_fee_bps = self.internal_exit_fee_bps_since(Some(position.stake_time_in_sec), cur_time);
+        self.internal_unstake_shares(
+            &env::current_account_id(),
+            &owner_id,
+            position.shares,
+            exit_fee_bps,
+            position.amount,
+        )
+    }
+}
+
+impl Contract {
+    /// Stake `amount` locked for `lock_duration_in_sec` and mint its NFT to the account.
+    /// The contract pays the storage of the position, so `amount` must be at least `min_stake_amount`.
+    pub(crate) fn internal_lock_stake(&mut self, account_id: &AccountId, amount: Balance, lock_duration_in_sec: u32) -> TokenId {
+        assert!(amount >= self.min_stake_amount, "ERR_STAKE_BELOW_MINIMUM");
+        assert!(
+            lock_duration_in_sec > 0 && lock_duration_in_sec <= MAX_LOCK_DURATION_IN_SEC,
+            "ERR_ILLEGAL_LOCK_DURATION"
+        );
+        assert!(
+            lock_duration_in_sec >= self.min_holding_period_in_sec,
+            "ERR_LOCK_SHORTER_THAN_HOLDING_PERIOD"
+        );
+        let cur_time = nano_to_sec(env::block_timestamp());
+        let shares = self.ledger.stake(amount);
+        assert!(shares > 0, "ERR_STAKE_TOO_SMALL");
+        self.internal_deposit(&env::current_account_id(), shares);
+
+        let boost_bps = FULL_BASIS_POINTS as u32
+            + (self.max_lock_boost_bps as u64 * lock_duration_in_sec as u64 / MAX_LOCK_DURATION_IN_SEC as u64) as u32;
+        self.internal_mint_position(
+            account_id,
+            Position {
+                amount,
+                shares,
+                lock_end_time_in_sec: cur_time + lock_duration_in_sec,
+                boost_bps,
+                boost_stake: WeightStake::default(),
+                stake_time_in_sec: cur_time,
+            },
+        )
+    }
+
+    pub(crate) fn internal_get_own_position(&self, owner_id: &AccountId, token_id: &TokenId) -> Position {
+        let position = self.positions.get(token_id).expect("ERR_NO_POSITION");
+        assert_eq!(
+            self.positions_nft.owner_by_id.get(token_id).as_ref(),
+            Some(owner_id),
+            "ERR_NOT_POSITION_OWNER"
+        );
+        position
+    }
+
+    fn internal_mint_position(&mut self, owner_id: &AccountId, mut position: Position) -> TokenId {
+        let token_id = self.next_position_id.to_string();
+        self.next_position_id += 1;
+        self.internal_update_position_weight(&mut position);
+        // storage is paid by the contract like the one of shares
+        let metadata = self.internal_position_metadata(&token_id, &position);
+        self.positions_nft
+            .internal_mint_with_refund(token_id.clone(), owner_id.clone(), Some(metadata), None);
+        self.positions.insert(&token_id, &position);
+        self.internal_update_position_shares(owner_id, position.shares, true);
+        NftMint {
+            owner_id,
+            token_ids: &[&token_id],
+            memo: None,
+        }
+        .emit();
+        token_id
+    }
+
+    /// Save the position with its new weight and render its NFT metadata again
+    fn internal_set_position(&mut self, token_id: &TokenId, position: &mut Position) {
+        self.internal_update_position_weight(position);
+        let metadata = self.internal_position_metadata(token_id, position);
+        if let Some(token_metadata_by_id) = self.positions_nft.token_metadata_by_id.as_mut() {
+            token_metadata_by_id.insert(token_id, &metadata);
+        }
+        let prev_shares = self.positions.insert(token_id, position).unwrap().shares;
+        let owner_id = self.positions_nft.owner_by_id.get(token_id).unwrap();
+        if position.shares > prev_shares {
+            self.internal_update_position_shares(&owner_id, position.shares - prev_shares, true);
+        } else if position.shares < prev_shares {
+            self.internal_update_position_shares(&owner_id, prev_shares - position.shares, false);
+        }
+    }
+
+    fn internal_transfer_position_shares(&mut self, from_id: &AccountId, to_id: &AccountId, token_id: &TokenId) {
+        let shares = self.positions.get(token_id).unwrap().shares;
+        self.internal_update_position_shares(from_id, shares, false);
+        self.internal_update_position_shares(to_id, shares, true);
+    }
+
+    fn internal_burn_position(&mut self, owner_id: &AccountId, token_id: &TokenId) {
+        let mut position = self.positions.remove(token_id).unwrap();
+        self.internal_close_position_stake(owner_id, &mut position);
+        self.internal_update_position_shares(owner_id, position.shares, false);
+        let nft = &mut self.positions_nft;
+        nft.owner_by_id.remove(token_id);
+        if let Some(token_metadata_by_id) = nft.token_metadata_by_id.as_mut() {
+            token_metadata_by_id.remove(token_id);
+        }
+        if let Some(tokens_per_owner) = nft.tokens_per_owner.as_mut() {
+            let mut owner_tokens = tokens_per_owner.get(owner_id).unwrap();
+            owner_tokens.remove(token_id);
+            if owner_tokens.is_empty() {
+                tokens_per_owner.remove(owner_id);
+            } else {
+                tokens_per_owner.insert(owner_id, &owner_tokens);
+            }
+        }
+        if let Some(approvals_by_id) = nft.approvals_by_id.as_mut() {
+            approvals_by_id.remove(token_id);
+        }
+        if let Some(next_approval_id_by_id) = nft.next_approval_id_by_id.as_mut() {
+            next_approval_id_by_id.remove(token_id);
+        }
+        NftBurn {
+            owner_id,
+            token_ids: &[token_id],
+            authorized_id: None,
+            memo: None,
+        }
+        .emit();
+    }
+
+    fn internal_position_metadata(&self, token_id: &TokenId, position: &Position) -> TokenMetadata {
+        TokenMetadata {
+            title: Some(format!("Locked stake #{}", token_id)),
+            description: Some(format!(
+                "{} of {} staked as {} shares, locked until {}, reward boost {}.{:02}x",
+                position.amount,
+                self.token_id,
+                position.shares,
+                format_utc(position.lock_end_time_in_sec),
+                position.boost_bps / 10_000,
+                position.boost_bps % 10_000 / 100
+            )),
+            media: None,
+            media_hash: None,
+            copies: Some(1),
+            issued_at: None,
+            expires_at: None,
+            starts_at: None,
+            updated_at: None,
+            extra: Some(
+                serde_json::json!({
+                    "amount": WrappedBalance::from(position.amount),
+                    "shares": WrappedBalance::from(position.shares),
+                    "lock_end_time_in_sec": position.lock_end_time_in_sec,
+                    "boost_bps": position.boost_bps,
+                })
+                .to_string(),
+            ),
+            reference: None,
+            reference_hash: None,
+        }
+    }
+}
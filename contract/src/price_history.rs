@@ -0,0 +1,122 @@
+use crate::*;
+use near_sdk::serde::Deserialize;
+use std::cmp::{max, min};
+
+pub const PRICE_HISTORY_CAPACITY: u64 = 1000;
+pub const DEFAULT_PRICE_SNAPSHOT_INTERVAL_IN_SEC: u32 = 60 * 60;
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct PriceSnapshot {
+    pub timestamp_in_sec: u32,
+    pub locked_token_amount: WrappedBalance,
+    pub total_staked: WrappedBalance,
+    pub virtual_price: WrappedBalance,
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn set_price_snapshot_interval(&mut self, price_snapshot_interval_in_sec: u32) {
+        self.assert_owner();
+        assert!(price_snapshot_interval_in_sec > 0, "ERR_ZERO_INTERVAL");
+        self.price_snapshot_interval_in_sec = price_snapshot_interval_in_sec;
+    }
+
+    pub fn get_price_snapshot_interval(&self) -> u32 {
+        self.price_snapshot_interval_in_sec
+    }
+
+    /// Return price snapshots from the oldest one stored, `from_index` counts from it
+    pub fn get_price_history(&self, from_index: Option<u64>, limit: Option<u64>) -> Vec<PriceSnapshot> {
+        let from_index = from_index.unwrap_or(0);
+        let len = self.price_history.len();
+        let to_index = min(len, from_index.saturating_add(limit.unwrap_or(len)));
+        (from_index..to_index)
+            .map(|index| self.internal_get_price_snapshot(index))
+            .collect()
+    }
+
+    /// Time-weighted average virtual price since `from_timestamp_in_sec` till now.
+    /// The price is linearly interpolated between snapshots and the current virtual price.
+    /// Only snapshots in the window are read, the first one is found by binary search.
+    pub fn get_time_weighted_average_price(&self, from_timestamp_in_sec: u32) -> WrappedBalance {
+        let cur_time = nano_to_sec(env::block_timestamp());
+        let cur_price = self.internal_virtual_price_at(cur_time);
+
+        let len = self.price_history.len();
+        // the last snapshot at or before the window start, the oldest one if the window starts earlier
+        let first_index = self.internal_first_snapshot_after(from_timestamp_in_sec).saturating_sub(1);
+        let mut points: Vec<(u32, Balance)> = (first_index..len)
+            .map(|index| {
+                let snapshot = self.internal_get_price_snapshot(index);
+                (snapshot.timestamp_in_sec, snapshot.virtual_price.0)
+            })
+            .collect();
+        points.push((cur_time, cur_price));
+
+        let start_time = max(from_timestamp_in_sec, points[0].0);
+        if start_time >= cur_time {
+            return cur_price.into();
+        }
+
+        let mut weighted_sum: u128 = 0;
+        for window in points.windows(2) {
+            let (t0, p0) = window[0];
+            let (t1, p1) = window[1];
+            if t1 <= start_time || t1 <= t0 {
+                continue;
+            }
+            let from = max(t0, start_time);
+            // price at `from` on the line between two points
+            let p_from = (p0 * (t1 - from) as u128 + p1 * (from - t0) as u128) / (t1 - t0) as u128;
+            weighted_sum += (p_from + p1) * (t1 - from) as u128 / 2;
+        }
+
+        (weighted_sum / (cur_time - start_time) as u128).into()
+    }
+}
+
+impl Contract {
+    fn internal_get_price_snapshot(&self, index: u64) -> PriceSnapshot {
+        let len = self.price_history.len();
+        let oldest = if len < PRICE_HISTORY_CAPACITY { 0 } else { self.price_history_head };
+        self.price_history.get((oldest + index) % len).expect("ERR_NO_PRICE_SNAPSHOT")
+    }
+
+    /// Index of the oldest snapshot taken after `timestamp_in_sec`, snapshots are ordered by time
+    fn internal_first_snapshot_after(&self, timestamp_in_sec: u32) -> u64 {
+        let (mut low, mut high) = (0, self.price_history.len());
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if self.internal_get_price_snapshot(mid).timestamp_in_sec <= timestamp_in_sec {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        low
+    }
+
+    pub(crate) fn internal_record_price_snapshot(&mut self, cur_timestamp_in_sec: u32) {
+        if let Some(last_snapshot_time) = self.last_price_snapshot_time_in_sec {
+            if cur_timestamp_in_sec < last_snapshot_time.saturating_add(self.price_snapshot_interval_in_sec) {
+                return;
+            }
+        }
+
+        let snapshot = PriceSnapshot {
+            timestamp_in_sec: cur_timestamp_in_sec,
+            locked_token_amount: self.ledger.locked_token_amount.into(),
+            total_staked: self.ledger.total_staked.into(),
+            virtual_price: self.internal_virtual_price_at(cur_timestamp_in_sec).into(),
+        };
+        if self.price_history.len() < PRICE_HISTORY_CAPACITY {
+            self.price_history.push(&snapshot);
+        } else {
+            self.price_history.replace(self.price_history_head, &snapshot);
+            self.price_history_head = (self.price_history_head + 1) % PRICE_HISTORY_CAPACITY;
+        }
+        self.last_price_snapshot_time_in_sec = Some(cur_timestamp_in_sec);
+    }
+}
@@ -0,0 +1,176 @@
+use crate::accounting::bps_of;
+use crate::ft::{ext_self, GAS_FOR_AFTER_FT_TRANSFER, GAS_FOR_FT_TRANSFER};
+use crate::*;
+use near_contract_standards::fungible_token::core::ext_ft_core;
+use near_sdk::{assert_one_yocto, PromiseResult, ONE_YOCTO};
+
+const GAS_FOR_FT_BALANCE_OF: Gas = Gas(Gas::ONE_TERA.0 * 10);
+const GAS_FOR_AFTER_FT_BALANCE_OF: Gas = Gas(Gas::ONE_TERA.0 * 10);
+const GAS_FOR_SWEEP_SURPLUS: Gas = Gas(GAS_FOR_FT_TRANSFER.0 + GAS_FOR_AFTER_FT_TRANSFER.0 + Gas::ONE_TERA.0 * 10);
+/// Part of the accounted balance kept out of a sweep, the token can credit a transfer before its ft_on_transfer runs
+const SWEEP_MARGIN_BPS: u32 = 100;
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct TokenBalanceReport {
+    // real balance of the staked token, from ft_balance_of
+    pub token_balance: WrappedBalance,
+    // locked_token_amount + undistributed_reward + referral tokens + vesting reward + boost reward + queued withdraws
+    // + pending_transfer_amount - tokens deployed to the strategy,
+    // with the same amounts of the added pools which stake this token
+    pub accounted_balance: WrappedBalance,
+    // tokens not backing any stake or reward
+    pub surplus: WrappedBalance,
+    // surplus above the margin of SWEEP_MARGIN_BPS of the accounted balance, sent by sweep_surplus
+    pub sweepable: WrappedBalance,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Send back tokens which are not the staked token, e.g. sent with a plain ft_transfer.
+    /// Requirements:
+    /// * Can only be called by the owner.
+    /// * `token_id` must differ from the staked token and tokens of the added pools, use `sweep_surplus` for it.
+    /// * Requires attached deposit of exactly 1 yoctoNEAR.
+    #[payable]
+    pub fn recover_tokens(&mut self, token_id: AccountId, amount: WrappedBalance, receiver_id: AccountId) -> Promise {
+        assert_one_yocto();
+        self.assert_owner();
+        assert_ne!(token_id, self.token_id, "ERR_CANNOT_RECOVER_STAKED_TOKEN");
+        assert!(
+            self.pools.values().all(|pool| pool.token_id != token_id),
+            "ERR_CANNOT_RECOVER_STAKED_TOKEN"
+        );
+        assert!(amount.0 > 0, "ERR_ZERO_AMOUNT");
+
+        self.internal_recover_transfer(&token_id, &receiver_id, amount.0)
+    }
+
+    /// Compare the real staked token balance with the contract accounting.
+    pub fn token_balance_report(&self) -> Promise {
+        ext_ft_core::ext(self.token_id.clone())
+            .with_static_gas(GAS_FOR_FT_BALANCE_OF)
+            .ft_balance_of(env::current_account_id())
+            .then(
+                ext_self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_AFTER_FT_BALANCE_OF)
+                    .callback_token_balance_report(),
+            )
+    }
+
+    /// Send the staked token surplus, verified by ft_balance_of, to `receiver_id`. A margin of the accounted
+    /// balance stays in the contract.
+    /// Requirements:
+    /// * Can only be called by the owner.
+    /// * No swap, strategy operation or staked token transfer should wait for a callback,
+    ///   and none should start until the balance is read.
+    /// * Requires attached deposit of exactly 1 yoctoNEAR.
+    #[payable]
+    pub fn sweep_surplus(&mut self, receiver_id: AccountId) -> Promise {
+        assert_one_yocto();
+        self.assert_owner();
+        self.internal_assert_nothing_in_flight();
+
+        ext_ft_core::ext(self.token_id.clone())
+            .with_static_gas(GAS_FOR_FT_BALANCE_OF)
+            .ft_balance_of(env::current_account_id())
+            .then(
+                ext_self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_SWEEP_SURPLUS)
+                    .callback_sweep_surplus(receiver_id, self.operation_nonce),
+            )
+    }
+
+    #[private]
+    pub fn callback_token_balance_report(&self, #[callback_unwrap] token_balance: WrappedBalance) -> TokenBalanceReport {
+        self.internal_token_balance_report(token_balance.0)
+    }
+
+    #[private]
+    pub fn callback_sweep_surplus(
+        &mut self,
+        receiver_id: AccountId,
+        operation_nonce: u64,
+        #[callback_unwrap] token_balance: WrappedBalance,
+    ) -> Promise {
+        // tokens of an operation which started and ended meanwhile may have been in the balance
+        assert_eq!(operation_nonce, self.operation_nonce, "ERR_OPERATION_IN_FLIGHT");
+        self.internal_assert_nothing_in_flight();
+        let surplus = self.internal_token_balance_report(token_balance.0).sweepable.0;
+        assert!(surplus > 0, "ERR_NO_SURPLUS");
+
+        let token_id = self.token_id.clone();
+        self.pending_transfer_amount += surplus;
+        self.internal_recover_transfer(&token_id, &receiver_id, surplus)
+    }
+
+    #[private]
+    pub fn callback_post_recover_tokens(&mut self, token_id: AccountId, receiver_id: AccountId, amount: WrappedBalance) {
+        assert_eq!(env::promise_results_count(), 1, "Err: expected 1 promise result from recover");
+        if token_id == self.token_id {
+            self.pending_transfer_amount -= amount.0;
+        }
+
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(_) => {
+                events::emit::recover_tokens_succeeded(&receiver_id, amount.0, &token_id);
+            }
+            PromiseResult::Failed => {
+                // Tokens stay in the contract and can be recovered again.
+                events::emit::recover_tokens_failed(&receiver_id, amount.0, &token_id);
+            }
+        };
+    }
+}
+
+impl Contract {
+    fn internal_token_balance_report(&self, token_balance: Balance) -> TokenBalanceReport {
+        let accounted_balance = self.ledger.locked_token_amount
+            + self.ledger.undistributed_reward
+            + self.referral_pool
+            + self.total_referral_earnings
+            + self.total_vesting
+            + self.boost_ledger.undistributed_reward
+            + self.boost_ledger.owed_reward
+            + self.total_queued_withdraw
+            + self.pending_transfer_amount
+            + self.internal_pools_accounted_balance(&self.token_id);
+        let accounted_balance = accounted_balance.saturating_sub(self.deployed_amount);
+        let surplus = token_balance.saturating_sub(accounted_balance);
+        TokenBalanceReport {
+            token_balance: token_balance.into(),
+            accounted_balance: accounted_balance.into(),
+            surplus: surplus.into(),
+            sweepable: surplus.saturating_sub(bps_of(accounted_balance, SWEEP_MARGIN_BPS)).into(),
+        }
+    }
+
+    /// Swapped, harvested or withdrawn tokens can arrive before a callback accounts them,
+    /// and a failed transfer comes back before its callback
+    fn internal_assert_nothing_in_flight(&self) {
+        assert!(
+            !self.swap_in_progress
+                && !self.strategy_in_progress
+                && self.pending_transfer_amount == 0
+                && self
+                    .pools
+                    .values()
+                    .all(|pool| pool.token_id != self.token_id || pool.pending_transfer_amount == 0),
+            "ERR_OPERATION_IN_FLIGHT"
+        );
+    }
+
+    fn internal_recover_transfer(&self, token_id: &AccountId, receiver_id: &AccountId, amount: Balance) -> Promise {
+        ext_ft_core::ext(token_id.clone())
+            .with_attached_deposit(ONE_YOCTO)
+            .with_static_gas(GAS_FOR_FT_TRANSFER)
+            .ft_transfer(receiver_id.clone(), amount.into(), None)
+            .then(
+                ext_self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_AFTER_FT_TRANSFER)
+                    .callback_post_recover_tokens(token_id.clone(), receiver_id.clone(), amount.into()),
+            )
+    }
+}
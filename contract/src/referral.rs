@@ -0,0 +1,171 @@
+use crate::ft::{ext_self, GAS_FOR_AFTER_FT_TRANSFER, GAS_FOR_FT_TRANSFER};
+use crate::*;
+use near_contract_standards::fungible_token::core::ext_ft_core;
+use near_sdk::env::sha256_array;
+use near_sdk::{assert_one_yocto, PromiseResult, ONE_YOCTO};
+
+/// Hard cap of the referral fee, 20% of the referee reward
+pub const MAX_REFERRAL_FEE_BPS: u32 = 2_000;
+/// Precision of the share price used to measure reward of referees
+const REFERRAL_PRICE_PRECISION: u128 = 1_000_000_000_000_000_000;
+/// Referees listed for a referrer, later stakers with the referrer stake without a link
+pub const MAX_REFEREES_PER_REFERRER: u64 = 1_000;
+
+#[near_bindgen]
+impl Contract {
+    /// Set share of the referee distributed reward which the referrer earns from the referral pool.
+    pub fn set_referral_fee(&mut self, referral_fee_bps: u32) {
+        self.assert_owner();
+        assert!(referral_fee_bps <= MAX_REFERRAL_FEE_BPS, "ERR_FEE_TOO_HIGH");
+        self.referral_fee_bps = referral_fee_bps;
+    }
+
+    pub fn get_referral_fee(&self) -> u32 {
+        self.referral_fee_bps
+    }
+
+    pub fn get_referral_pool(&self) -> WrappedBalance {
+        self.referral_pool.into()
+    }
+
+    pub fn get_referrer(&self, account_id: AccountId) -> Option<AccountId> {
+        self.internal_get_account(&account_id).referrer_id
+    }
+
+    pub fn get_referees(&self, referrer_id: AccountId, from_index: Option<u64>, limit: Option<u64>) -> Vec<AccountId> {
+        match self.referees.get(&referrer_id) {
+            Some(referees) => {
+                let from_index = from_index.unwrap_or(0);
+                let to_index = std::cmp::min(referees.len(), from_index.saturating_add(limit.unwrap_or(referees.len())));
+                (from_index..to_index).map(|index| referees.get(index).unwrap()).collect()
+            }
+            None => vec![],
+        }
+    }
+
+    /// Referral earnings of the account which can be claimed
+    pub fn get_referral_earnings(&self, account_id: AccountId) -> WrappedBalance {
+        self.referral_earnings.get(&account_id).unwrap_or_default().into()
+    }
+
+    /// Accrue referral earnings for the reward of `account_id` up to now, it happens on every stake and unstake too.
+    pub fn sync_referral(&mut self, account_id: AccountId) {
+        // Checkpoint
+        self.distribute_reward();
+        self.internal_sync_referral(&account_id);
+    }
+
+    /// Send referral earnings to the predecessor account.
+    /// Requirements:
+    /// * Requires attached deposit of exactly 1 yoctoNEAR.
+    #[payable]
+    pub fn claim_referral_earnings(&mut self) -> Promise {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let amount = self.referral_earnings.remove(&account_id).unwrap_or_default();
+        assert!(amount > 0, "ERR_NO_REFERRAL_EARNINGS");
+        self.total_referral_earnings -= amount;
+        self.pending_transfer_amount += amount;
+
+        ext_ft_core::ext(self.token_id.clone())
+            .with_attached_deposit(ONE_YOCTO)
+            .with_static_gas(GAS_FOR_FT_TRANSFER)
+            .ft_transfer(account_id.clone(), amount.into(), None)
+            .then(
+                ext_self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_AFTER_FT_TRANSFER)
+                    .callback_post_claim_referral_earnings(account_id, amount.into()),
+            )
+    }
+
+    #[private]
+    pub fn callback_post_claim_referral_earnings(&mut self, account_id: AccountId, amount: WrappedBalance) {
+        assert_eq!(env::promise_results_count(), 1, "Err: expected 1 promise result from claim");
+        self.pending_transfer_amount -= amount.0;
+
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(_) => {
+                events::emit::claim_referral_earnings_succeeded(&account_id, amount.0, &self.token_id);
+            }
+            PromiseResult::Failed => {
+                // This reverts the changes from claim_referral_earnings function.
+                self.internal_add_referral_earnings(&account_id, amount.0);
+                log!("Account {} claim failed and reverted.", account_id);
+
+                events::emit::claim_referral_earnings_failed(&account_id, amount.0, &self.token_id);
+            }
+        };
+    }
+}
+
+impl Contract {
+    pub(crate) fn internal_add_referral_pool(&mut self, account_id: &AccountId, amount: Balance) {
+        self.referral_pool += amount;
+        log!("{} add {} assets to referral pool", account_id, amount);
+    }
+
+    fn internal_referral_price(&self) -> u128 {
+        if self.ledger.total_staked == 0 {
+            REFERRAL_PRICE_PRECISION
+        } else {
+            (U256::from(self.ledger.locked_token_amount) * U256::from(REFERRAL_PRICE_PRECISION) / U256::from(self.ledger.total_staked))
+                .as_u128()
+        }
+    }
+
+    /// Link `account_id` to `referrer_id` on its first stake, must be called before the stake.
+    /// A referrer with MAX_REFEREES_PER_REFERRER referees gets no more links.
+    pub(crate) fn internal_link_referrer(&mut self, account_id: &AccountId, referrer_id: &AccountId) {
+        let mut account = self.internal_get_account(account_id);
+        if account.last_stake_time_in_sec.is_some() || account.referrer_id.is_some() || account_id == referrer_id {
+            return;
+        }
+        let mut referees = self.referees.get(referrer_id).unwrap_or_else(|| {
+            Vector::new(StorageKey::RefereesPerAccount {
+                account_hash: sha256_array(referrer_id.as_bytes()),
+            })
+        });
+        if referees.len() >= MAX_REFEREES_PER_REFERRER {
+            log!("Referrer {} has too many referees, {} is not linked.", referrer_id, account_id);
+            return;
+        }
+        account.referrer_id = Some(referrer_id.clone());
+        account.referral_price = self.internal_referral_price();
+        self.internal_set_account(account_id, &account);
+
+        referees.push(account_id);
+        self.referees.insert(referrer_id, &referees);
+
+        events::emit::link_referrer(account_id, referrer_id);
+    }
+
+    /// Accrue referral earnings for the share price growth since the last sync,
+    /// `internal_deposit` and `internal_withdraw` call it before shares change.
+    pub(crate) fn internal_sync_referral(&mut self, account_id: &AccountId) {
+        let mut account = self.internal_get_account(account_id);
+        let referrer_id = match account.referrer_id.clone() {
+            Some(referrer_id) => referrer_id,
+            None => return,
+        };
+        let price = self.internal_referral_price();
+        let shares = self.shares.get(account_id).unwrap_or_default();
+        if price > account.referral_price && shares > 0 && self.referral_fee_bps > 0 {
+            let reward = (U256::from(shares) * U256::from(price - account.referral_price) / U256::from(REFERRAL_PRICE_PRECISION)).as_u128();
+            let earnings = std::cmp::min(accounting::bps_of(reward, self.referral_fee_bps), self.referral_pool);
+            if earnings > 0 {
+                self.referral_pool -= earnings;
+                self.internal_add_referral_earnings(&referrer_id, earnings);
+                events::emit::referral_earnings(&referrer_id, earnings, &self.token_id);
+            }
+        }
+        account.referral_price = price;
+        self.internal_set_account(account_id, &account);
+    }
+
+    fn internal_add_referral_earnings(&mut self, account_id: &AccountId, amount: Balance) {
+        let earnings = self.referral_earnings.get(account_id).unwrap_or_default();
+        self.referral_earnings.insert(account_id, &(earnings + amount));
+        self.total_referral_earnings += amount;
+    }
+}
@@ -0,0 +1,226 @@
+use crate::accounting::bps_of;
+use crate::ft::{ext_self, GAS_FOR_AFTER_FT_TRANSFER, GAS_FOR_FT_TRANSFER};
+use crate::*;
+use near_contract_standards::fungible_token::core::ext_ft_core;
+#[cfg(not(target_arch = "wasm32"))]
+use near_sdk::serde::Deserialize;
+use near_sdk::{assert_one_yocto, PromiseResult, ONE_YOCTO};
+
+/// Hard cap of slashes in a slash period, 30% of locked tokens
+pub const MAX_SLASH_BPS: u32 = 3_000;
+
+/// Shortest slash period, it's the unstake cooldown when that is longer
+const MIN_SLASH_PERIOD_IN_SEC: u32 = 86_400;
+
+/// Slash which covered a shortfall, kept in the history
+#[derive(BorshSerialize, BorshDeserialize, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, Deserialize))]
+pub struct Slash {
+    pub slasher_id: AccountId,
+    pub receiver_id: AccountId,
+    pub amount: WrappedBalance,
+    /// locked tokens right before the slash
+    pub locked_token_amount: WrappedBalance,
+    pub timestamp_in_sec: u32,
+    pub reason: Option<String>,
+    /// the transfer failed and the tokens were returned to stakers
+    pub reverted: bool,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, Deserialize))]
+pub struct UnstakeCooldown {
+    pub cooldown_in_sec: u32,
+    pub unstake_window_in_sec: u32,
+    /// start of the unstake window of the account, NONE without a started cooldown
+    pub unstake_start_time_in_sec: Option<u32>,
+    pub unstake_end_time_in_sec: Option<u32>,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Set the account allowed to slash locked tokens, NONE turns slashing off.
+    /// Requirements:
+    /// * Can only be called by the owner.
+    pub fn set_slasher(&mut self, slasher_id: Option<AccountId>) {
+        self.assert_owner();
+        self.slasher_id = slasher_id;
+    }
+
+    pub fn get_slasher(&self) -> Option<AccountId> {
+        self.slasher_id.clone()
+    }
+
+    /// Set the part of locked tokens which slashes of a slash period can take together, in basis points.
+    /// Requirements:
+    /// * Can only be called by the owner.
+    /// * `max_slash_bps` must not exceed 3_000.
+    pub fn set_max_slash(&mut self, max_slash_bps: u32) {
+        self.assert_owner();
+        assert!(max_slash_bps <= MAX_SLASH_BPS, "ERR_SLASH_TOO_HIGH");
+        self.max_slash_bps = max_slash_bps;
+    }
+
+    pub fn get_max_slash(&self) -> u32 {
+        self.max_slash_bps
+    }
+
+    /// Require `start_unstake_cooldown` and `cooldown_in_sec` before an unstake, 0 turns the cooldown off.
+    /// The unstake is possible for `unstake_window_in_sec` after the cooldown, then it must be started again.
+    /// Requirements:
+    /// * Can only be called by the owner.
+    /// * `unstake_window_in_sec` must be positive while the cooldown is on.
+    pub fn set_unstake_cooldown(&mut self, cooldown_in_sec: u32, unstake_window_in_sec: u32) {
+        self.assert_owner();
+        assert!(cooldown_in_sec == 0 || unstake_window_in_sec > 0, "ERR_ZERO_UNSTAKE_WINDOW");
+        self.unstake_cooldown_in_sec = cooldown_in_sec;
+        self.unstake_window_in_sec = unstake_window_in_sec;
+    }
+
+    pub fn get_unstake_cooldown(&self, account_id: AccountId) -> UnstakeCooldown {
+        let unstake_start_time = self
+            .internal_get_account(&account_id)
+            .cooldown_start_time_in_sec
+            .map(|start_time| start_time.saturating_add(self.unstake_cooldown_in_sec));
+        UnstakeCooldown {
+            cooldown_in_sec: self.unstake_cooldown_in_sec,
+            unstake_window_in_sec: self.unstake_window_in_sec,
+            unstake_start_time_in_sec: unstake_start_time,
+            unstake_end_time_in_sec: unstake_start_time.map(|start_time| start_time.saturating_add(self.unstake_window_in_sec)),
+        }
+    }
+
+    /// Start the cooldown of the predecessor, its shares and positions can be unstaked once it passes.
+    /// Requirements:
+    /// * The cooldown must be on.
+    pub fn start_unstake_cooldown(&mut self) {
+        assert!(self.unstake_cooldown_in_sec > 0, "ERR_NO_UNSTAKE_COOLDOWN");
+        let account_id = env::predecessor_account_id();
+        let cur_time = nano_to_sec(env::block_timestamp());
+        let mut account = self.internal_get_account(&account_id);
+        account.cooldown_start_time_in_sec = Some(cur_time);
+        self.internal_set_account(&account_id, &account);
+        events::emit::start_unstake_cooldown(&account_id, cur_time.saturating_add(self.unstake_cooldown_in_sec));
+    }
+
+    /// Take `amount` of locked tokens to cover a shortfall and send it to `receiver_id`,
+    /// it lowers the virtual price for all stakers of the main pool.
+    /// Requirements:
+    /// * Can only be called by the slasher.
+    /// * Together with earlier slashes of the slash period, `amount` must not exceed `max_slash_bps`
+    ///   of locked tokens at the period start. A period lasts the unstake cooldown, at least a day.
+    /// * The contract must hold `amount`, tokens deployed to the strategy can't be slashed.
+    /// * Requires attached deposit of exactly 1 yoctoNEAR.
+    #[payable]
+    pub fn slash(&mut self, amount: WrappedBalance, receiver_id: AccountId, reason: Option<String>) -> Promise {
+        // Checkpoint
+        self.distribute_reward();
+
+        assert_one_yocto();
+        let slasher_id = env::predecessor_account_id();
+        assert_eq!(Some(&slasher_id), self.slasher_id.as_ref(), "ERR_NOT_A_SLASHER");
+        let amount: Balance = amount.into();
+        assert!(amount > 0, "ERR_ZERO_AMOUNT");
+        let cur_time = nano_to_sec(env::block_timestamp());
+        if cur_time >= self.slash_period_start_in_sec.saturating_add(self.internal_slash_period_in_sec()) {
+            self.slash_period_start_in_sec = cur_time;
+            self.slash_period_locked_amount = self.ledger.locked_token_amount;
+            self.slashed_in_period = 0;
+        }
+        assert!(
+            self.slashed_in_period + amount <= bps_of(self.slash_period_locked_amount, self.max_slash_bps),
+            "ERR_SLASH_TOO_HIGH"
+        );
+        assert!(amount <= self.internal_liquid_amount(), "ERR_NOT_ENOUGH_LIQUID_TOKENS");
+        self.slashed_in_period += amount;
+
+        let slash = Slash {
+            slasher_id,
+            receiver_id: receiver_id.clone(),
+            amount: amount.into(),
+            locked_token_amount: self.ledger.locked_token_amount.into(),
+            timestamp_in_sec: cur_time,
+            reason,
+            reverted: false,
+        };
+        self.ledger.locked_token_amount -= amount;
+        self.slashes.push(&slash);
+        let slash_id = self.slashes.len() - 1;
+        events::emit::slash(slash_id, &slash, &self.token_id);
+
+        self.pending_transfer_amount += amount;
+        ext_ft_core::ext(self.token_id.clone())
+            .with_attached_deposit(ONE_YOCTO)
+            .with_static_gas(GAS_FOR_FT_TRANSFER)
+            .ft_transfer(receiver_id, amount.into(), None)
+            .then(
+                ext_self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_AFTER_FT_TRANSFER)
+                    .callback_post_slash(slash_id),
+            )
+    }
+
+    /// Slashes in the order they were made, reverted ones included
+    pub fn get_slashes(&self, from_index: Option<u64>, limit: Option<u64>) -> Vec<Slash> {
+        let from_index = from_index.unwrap_or(0);
+        let to_index = std::cmp::min(from_index.saturating_add(limit.unwrap_or(100)), self.slashes.len());
+        (from_index..to_index).map(|index| self.slashes.get(index).unwrap()).collect()
+    }
+
+    pub fn get_total_slashed(&self) -> WrappedBalance {
+        self.total_slashed.into()
+    }
+
+    #[private]
+    pub fn callback_post_slash(&mut self, slash_id: u64) {
+        assert_eq!(env::promise_results_count(), 1, "Err: expected 1 promise result from slash");
+        let mut slash = self.slashes.get(slash_id).unwrap();
+        self.pending_transfer_amount -= slash.amount.0;
+
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(_) => {
+                self.total_slashed += slash.amount.0;
+                events::emit::slash_succeeded(&slash.receiver_id, slash.amount.0, &self.token_id);
+            }
+            PromiseResult::Failed => {
+                // This reverts the changes from slash function, the history keeps the attempt.
+                self.ledger.locked_token_amount += slash.amount.0;
+                if slash.timestamp_in_sec >= self.slash_period_start_in_sec {
+                    self.slashed_in_period -= slash.amount.0;
+                }
+                log!("Slash of {} to {} failed and reverted.", slash.amount.0, slash.receiver_id);
+
+                events::emit::slash_failed(&slash.receiver_id, slash.amount.0, &self.token_id);
+                slash.reverted = true;
+                self.slashes.replace(slash_id, &slash);
+            }
+        };
+    }
+}
+
+impl Contract {
+    fn internal_slash_period_in_sec(&self) -> u32 {
+        std::cmp::max(self.unstake_cooldown_in_sec, MIN_SLASH_PERIOD_IN_SEC)
+    }
+
+    /// Unstakes of the account must be in its unstake window while the cooldown is on
+    pub(crate) fn assert_unstake_cooldown_passed(&self, account_id: &AccountId) {
+        if self.unstake_cooldown_in_sec == 0 {
+            return;
+        }
+        let start_time = self
+            .internal_get_account(account_id)
+            .cooldown_start_time_in_sec
+            .expect("ERR_UNSTAKE_COOLDOWN_NOT_STARTED");
+        let cur_time = nano_to_sec(env::block_timestamp());
+        let unstake_start_time = start_time.saturating_add(self.unstake_cooldown_in_sec);
+        assert!(cur_time >= unstake_start_time, "ERR_UNSTAKE_COOLDOWN_NOT_PASSED");
+        assert!(
+            cur_time <= unstake_start_time.saturating_add(self.unstake_window_in_sec),
+            "ERR_UNSTAKE_WINDOW_PASSED"
+        );
+    }
+}
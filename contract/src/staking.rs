@@ -1,43 +1,126 @@
 use crate::*;
-use near_sdk::{assert_one_yocto, PromiseResult};
-use std::cmp::{max, min};
+use near_sdk::{assert_one_yocto, PromiseOrValue, PromiseResult};
 
 impl Contract {
-    pub fn internal_stake(&mut self, account_id: &AccountId, amount: Balance) {
-        let mut minted = amount;
-        if self.total_staked != 0 {
-            assert!(self.locked_token_amount > 0, "ERR_INTERNAL");
-            minted = (U256::from(amount) * U256::from(self.total_staked) / U256::from(self.locked_token_amount)).as_u128();
+    /// Stake `amount` for the account, returns the minted shares
+    pub fn internal_stake(&mut self, account_id: &AccountId, amount: Balance) -> Balance {
+        let cur_time = nano_to_sec(env::block_timestamp());
+        let minted = self.ledger.stake(amount);
+        assert!(minted > 0, "ERR_STAKE_TOO_SMALL");
+        self.internal_deposit(account_id, minted);
+
+        let mut account = self.internal_get_account(account_id);
+        // a stake within the holding period of the previous one is held together with it
+        if self.internal_holding_shares(&account) == 0 {
+            account.holding_shares = 0;
         }
+        account.holding_shares += minted;
+        account.last_stake_time_in_sec = Some(cur_time);
+        account.cost_basis += amount;
+        self.internal_set_account(account_id, &account);
+        minted
+    }
 
-        assert!(minted > 0, "ERR_STAKE_TOO_SMALL");
+    /// the time when all shares of the account can be unstaked
+    pub(crate) fn internal_unstake_available_time(&self, account_id: &AccountId) -> u32 {
+        let account = self.internal_get_account(account_id);
+        match account.last_stake_time_in_sec {
+            Some(stake_time) if account.holding_shares > 0 => stake_time.saturating_add(self.min_holding_period_in_sec),
+            _ => 0,
+        }
+    }
 
-        self.locked_token_amount += amount;
-        self.internal_deposit(account_id, minted);
+    /// shares of the account which are still in the holding period
+    pub(crate) fn internal_holding_shares(&self, account: &Account) -> Balance {
+        match account.last_stake_time_in_sec {
+            Some(stake_time) if nano_to_sec(env::block_timestamp()) < stake_time.saturating_add(self.min_holding_period_in_sec) => {
+                account.holding_shares
+            }
+            _ => 0,
+        }
     }
 
     pub fn internal_add_reward(&mut self, account_id: &AccountId, amount: Balance) {
-        self.undistributed_reward += amount;
+        self.ledger.undistributed_reward += amount;
         log!("{} add {} assets as reward", account_id, amount);
     }
 
-    pub(crate) fn try_distribute_reward(&self, cur_timestamp_in_sec: u32) -> Balance {
-        if cur_timestamp_in_sec > self.reward_genesis_time_in_sec && cur_timestamp_in_sec > self.prev_distribution_time_in_sec {
-            let ideal_amount = self.reward_per_sec * (cur_timestamp_in_sec - self.prev_distribution_time_in_sec) as u128;
-            min(ideal_amount, self.undistributed_reward)
+    pub(crate) fn internal_virtual_price_at(&self, timestamp_in_sec: u32) -> Balance {
+        let new_reward = self.ledger.try_distribute_reward(timestamp_in_sec);
+        let locked_token_amount = self.ledger.locked_token_amount + new_reward;
+        let (_, fee_shares) = self.try_performance_fee(new_reward, locked_token_amount);
+        accounting::virtual_price(locked_token_amount, self.ledger.total_staked + fee_shares)
+    }
+
+    /// Burn `shares` held by `holder_id` and send their tokens to the account, `exit_fee_bps` of them stays locked.
+    /// Shared by `unstake` and `unstake_position`, the guards are checked by the caller.
+    pub(crate) fn internal_unstake_shares(
+        &mut self,
+        holder_id: &AccountId,
+        account_id: &AccountId,
+        shares: Balance,
+        exit_fee_bps: u32,
+        principal: Balance,
+    ) -> PromiseOrValue<()> {
+        // the shares leave at the price before the exit fee, which is reward of the remaining stakers
+        self.internal_withdraw(holder_id, shares);
+        let (unlocked, exit_fee) = self.ledger.unstake(shares, exit_fee_bps);
+        self.internal_checkpoint_total_staked();
+        if exit_fee > 0 {
+            self.internal_charge_exit_fee(account_id, exit_fee);
+        }
+        self.internal_send_unstaked(account_id, unlocked, shares, principal)
+    }
+
+    /// Send `unlocked` tokens of the burned `shares` to the account,
+    /// they wait in the withdraw queue while the strategy holds the tokens.
+    pub(crate) fn internal_send_unstaked(
+        &mut self,
+        account_id: &AccountId,
+        unlocked: Balance,
+        shares: Balance,
+        principal: Balance,
+    ) -> PromiseOrValue<()> {
+        if self.internal_should_queue_withdraw() {
+            self.internal_queue_withdraw(account_id, unlocked, shares, principal);
+            return PromiseOrValue::Value(());
+        }
+        self.internal_pay_unstaked(account_id, unlocked, shares, principal)
+    }
+
+    /// Send `unlocked` tokens of the burned `shares` to the account.
+    /// While reward vesting is on, the part above `principal` vests instead.
+    pub(crate) fn internal_pay_unstaked(
+        &mut self,
+        account_id: &AccountId,
+        unlocked: Balance,
+        shares: Balance,
+        principal: Balance,
+    ) -> PromiseOrValue<()> {
+        let vested = if self.reward_vesting_duration_in_sec > 0 {
+            unlocked.saturating_sub(principal)
         } else {
             0
+        };
+        if vested > 0 {
+            self.internal_vest_reward(account_id, vested);
         }
+        let unlocked = unlocked - vested;
+        if unlocked == 0 {
+            return PromiseOrValue::Value(());
+        }
+        self.internal_ft_transfer(account_id, unlocked, shares, principal, vested).into()
     }
 
     pub(crate) fn distribute_reward(&mut self) {
         let cur_time = nano_to_sec(env::block_timestamp());
-        let new_reward = self.try_distribute_reward(cur_time);
+        // the share supply changes only after the checkpoint
+        self.internal_accrue_total_points(cur_time);
+        let new_reward = self.ledger.distribute_reward(cur_time);
         if new_reward > 0 {
-            self.undistributed_reward -= new_reward;
-            self.locked_token_amount += new_reward;
+            self.internal_charge_performance_fee(new_reward);
         }
-        self.prev_distribution_time_in_sec = max(cur_time, self.reward_genesis_time_in_sec);
+        self.internal_record_price_snapshot(cur_time);
     }
 }
 
@@ -48,9 +131,19 @@ impl Contract {
     /// * The predecessor account should be registered.
     /// * `amount` must be a positive integer or NONE to unstake all
     /// * The predecessor account should have at least the `amount` of shares.
+    /// * Shares minted less than `min_holding_period_in_sec` ago can't be unstaked, a stake within the period
+    ///   of the previous one holds the shares of both until it passes.
+    /// * Shares of the predecessor grant which are not vested yet can't be unstaked.
+    /// * While the unstake cooldown is on, the predecessor must be in its unstake window.
     /// * Requires attached deposit of exactly 1 yoctoNEAR.
+    /// * `pool_id` to unstake from one of the added pools, NONE for the main pool. Only the unstake cooldown applies to added pools.
+    /// While reward vesting is on, tokens above the cost basis of the shares vest and are sent by `claim_vested`.
     #[payable]
-    pub fn unstake(&mut self, amount: Option<WrappedBalance>) -> Promise {
+    pub fn unstake(&mut self, amount: Option<WrappedBalance>, pool_id: Option<PoolId>) -> PromiseOrValue<()> {
+        if let Some(pool_id) = pool_id {
+            return self.internal_pool_unstake(pool_id, amount).into();
+        }
+
         // Checkpoint
         self.distribute_reward();
 
@@ -58,19 +151,80 @@ impl Contract {
         let account_id = env::predecessor_account_id();
         let amount: Balance = amount.unwrap_or(self.shares.get(&account_id).unwrap_or_default().into()).into();
 
-        assert!(self.total_staked > 0, "ERR_EMPTY_TOTAL_SUPPLY");
-        let unlocked = (U256::from(amount) * U256::from(self.locked_token_amount) / U256::from(self.total_staked)).as_u128();
+        assert!(self.ledger.total_staked > 0, "ERR_EMPTY_TOTAL_SUPPLY");
+        assert!(
+            self.shares.get(&account_id).unwrap_or_default().saturating_sub(amount)
+                >= self.internal_holding_shares(&self.internal_get_account(&account_id)),
+            "ERR_HOLDING_PERIOD_NOT_PASSED"
+        );
+        self.assert_unstake_cooldown_passed(&account_id);
+        assert!(
+            self.shares.get(&account_id).unwrap_or_default().saturating_sub(amount) >= self.internal_locked_grant_shares(&account_id),
+            "ERR_GRANT_NOT_VESTED"
+        );
+        let exit_fee_bps = self.internal_exit_fee_bps(&account_id, nano_to_sec(env::block_timestamp()));
+        let principal = self.internal_take_cost_basis(&account_id, amount);
+        self.internal_unstake_shares(&account_id, &account_id, amount, exit_fee_bps, principal)
+    }
+
+    /// withdraw not yet distributed reward and send it to `receiver_id` or to the predecessor account.
+    /// Requirements:
+    /// * Can only be called by the owner or the reward manager.
+    /// * `amount` must not exceed the undistributed reward at call time.
+    /// * Requires attached deposit of exactly 1 yoctoNEAR.
+    #[payable]
+    pub fn withdraw_reward(&mut self, amount: WrappedBalance, receiver_id: Option<AccountId>) -> Promise {
+        // Checkpoint
+        self.distribute_reward();
+
+        assert_one_yocto();
+        self.assert_owner_or_reward_manager();
+        let receiver_id = receiver_id.unwrap_or_else(env::predecessor_account_id);
+        let amount: Balance = amount.into();
+
+        assert!(amount > 0, "ERR_ZERO_AMOUNT");
+        assert!(amount <= self.ledger.undistributed_reward, "ERR_NOT_ENOUGH_UNDISTRIBUTED_REWARD");
+        self.ledger.undistributed_reward -= amount;
 
-        self.internal_withdraw(&account_id, amount);
-        assert!(self.total_staked >= 10u128.pow(18), "ERR_KEEP_AT_LEAST_ONE_STAKED_TOKEN");
-        self.locked_token_amount -= unlocked;
+        events::emit::withdraw_reward(&receiver_id, amount, &self.token_id);
+        self.internal_reward_transfer(&receiver_id, amount)
+    }
+
+    #[private]
+    pub fn callback_post_withdraw_reward(&mut self, receiver_id: AccountId, amount: WrappedBalance) {
+        assert_eq!(
+            env::promise_results_count(),
+            1,
+            "Err: expected 1 promise result from withdraw_reward"
+        );
+        self.pending_transfer_amount -= amount.0;
 
-        self.internal_ft_transfer(&account_id, unlocked, amount)
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(_) => {
+                events::emit::withdraw_reward_succeeded(&receiver_id, amount.0, &self.token_id);
+            }
+            PromiseResult::Failed => {
+                // This reverts the changes from withdraw_reward function.
+                self.ledger.undistributed_reward += amount.0;
+                log!("Withdraw of {} reward to {} failed and reverted.", amount.0, receiver_id);
+
+                events::emit::withdraw_reward_failed(&receiver_id, amount.0, &self.token_id);
+            }
+        };
     }
 
     #[private]
-    pub fn callback_post_unstake(&mut self, sender_id: AccountId, amount: WrappedBalance, share: WrappedBalance) {
+    pub fn callback_post_unstake(
+        &mut self,
+        sender_id: AccountId,
+        amount: WrappedBalance,
+        share: WrappedBalance,
+        principal: WrappedBalance,
+        vested: WrappedBalance,
+    ) {
         assert_eq!(env::promise_results_count(), 1, "Err: expected 1 promise result from unstake");
+        self.pending_transfer_amount -= amount.0;
 
         match env::promise_result(0) {
             PromiseResult::NotReady => unreachable!(),
@@ -78,13 +232,27 @@ impl Contract {
                 events::emit::withdraw_succeeded(&sender_id, amount.0, &self.token_id);
             }
             PromiseResult::Failed => {
-                // This reverts the changes from unstake function.
+                // This restakes the sent tokens, the exit fee taken by unstake function is kept:
+                // it already raised the virtual price or was minted to the treasury.
                 // If account doesn't exit, the unlock token stay in contract.
-                if self.shares.get(&sender_id).is_some() {
-                    self.locked_token_amount += amount.0;
-                    self.internal_deposit(&sender_id, share.0);
-                    log!("Account {} unstake failed and reverted.", sender_id,);
+                if vested.0 > 0 {
+                    // The reward part is vesting already, the principal can be claimed with it.
+                    self.internal_add_vested(&sender_id, amount.0);
+                    log!("Account {} unstake failed, the principal goes to vesting.", sender_id);
+                } else if self.shares.get(&sender_id).is_some() {
+                    // Checkpoint
+                    self.distribute_reward();
+                    let minted = self.ledger.stake(amount.0);
+                    self.internal_deposit(&sender_id, minted);
+                    self.internal_add_cost_basis(&sender_id, principal.0);
+                    log!(
+                        "Account {} unstake of {} shares failed, {} tokens are staked back.",
+                        sender_id,
+                        share.0,
+                        amount.0
+                    );
                 } else {
+                    // It can be taken back by the owner with sweep_surplus.
                     log!("Account {} has unregisterd. unlocking token goes to contract.", sender_id);
                 }
 
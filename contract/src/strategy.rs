@@ -0,0 +1,399 @@
+use crate::accounting::bps_of;
+use crate::ft::{ext_self, GAS_FOR_AFTER_FT_TRANSFER, GAS_FOR_FT_TRANSFER};
+use crate::*;
+use near_contract_standards::fungible_token::core::ext_ft_core;
+use near_sdk::json_types::U128;
+#[cfg(not(target_arch = "wasm32"))]
+use near_sdk::serde::Deserialize;
+use near_sdk::{assert_one_yocto, serde_json, PromiseOrValue, PromiseResult, ONE_YOCTO};
+
+/// Queued withdraws paid by a call, every one is a transfer with a callback
+const MAX_QUEUED_WITHDRAWS_PER_CALL: u64 = 3;
+
+const GAS_FOR_STRATEGY_DEPOSIT: Gas = Gas(Gas::ONE_TERA.0 * 50);
+const GAS_FOR_AFTER_STRATEGY_DEPOSIT: Gas = Gas(Gas::ONE_TERA.0 * 10);
+const GAS_FOR_STRATEGY_BALANCE: Gas = Gas(Gas::ONE_TERA.0 * 10);
+const GAS_FOR_STRATEGY_WITHDRAW: Gas = Gas(Gas::ONE_TERA.0 * 40);
+const GAS_FOR_AFTER_STRATEGY_HARVEST: Gas = Gas(Gas::ONE_TERA.0 * 10);
+const GAS_FOR_AFTER_STRATEGY_BALANCE: Gas =
+    Gas(GAS_FOR_STRATEGY_WITHDRAW.0 + GAS_FOR_STRATEGY_BALANCE.0 + GAS_FOR_AFTER_STRATEGY_HARVEST.0 + Gas::ONE_TERA.0 * 10);
+const GAS_FOR_AFTER_STRATEGY_WITHDRAW: Gas =
+    Gas((GAS_FOR_FT_TRANSFER.0 + GAS_FOR_AFTER_FT_TRANSFER.0 + Gas::ONE_TERA.0 * 5) * MAX_QUEUED_WITHDRAWS_PER_CALL + Gas::ONE_TERA.0 * 10);
+const GAS_FOR_BEFORE_STRATEGY_WITHDRAW: Gas =
+    Gas(GAS_FOR_STRATEGY_WITHDRAW.0 + GAS_FOR_STRATEGY_BALANCE.0 + GAS_FOR_AFTER_STRATEGY_WITHDRAW.0 + Gas::ONE_TERA.0 * 10);
+
+/// Unstake which waits for the strategy to return tokens, paid like an unstake
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct QueuedWithdraw {
+    pub account_id: AccountId,
+    pub unlocked: Balance,
+    pub shares: Balance,
+    pub principal: Balance,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, Deserialize))]
+pub struct StrategyView {
+    pub strategy_id: Option<AccountId>,
+    // part of locked_token_amount held by the strategy
+    pub deployed_amount: WrappedBalance,
+    pub liquidity_buffer_bps: u32,
+    // locked and queued tokens held by the contract
+    pub liquid_amount: WrappedBalance,
+    pub total_queued: WrappedBalance,
+    pub queue_length: u64,
+    // deploy, withdraw or harvest which waits for a callback
+    pub in_progress: bool,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, Deserialize))]
+pub struct QueuedWithdrawView {
+    pub account_id: AccountId,
+    pub amount: WrappedBalance,
+}
+
+/// Lending contract, tokens are supplied with `ft_transfer_call`.
+/// A failed transfer of `withdraw` must go back to the supply, the received amount is measured by `get_balance`.
+#[ext_contract(ext_strategy)]
+#[allow(dead_code)]
+pub trait ExtStrategy {
+    fn withdraw(&mut self, amount: WrappedBalance) -> Promise;
+    fn get_balance(&self, account_id: AccountId) -> WrappedBalance;
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Set the lending contract which locked tokens are deployed to, NONE stops deploys.
+    /// Requirements:
+    /// * Can only be called by the owner.
+    /// * Nothing should be deployed to the current strategy.
+    /// * No strategy operation should wait for a callback.
+    pub fn set_strategy(&mut self, strategy_id: Option<AccountId>) {
+        self.assert_owner();
+        assert_eq!(self.deployed_amount, 0, "ERR_STRATEGY_HAS_DEPLOYED_TOKENS");
+        assert!(!self.strategy_in_progress, "ERR_STRATEGY_IN_PROGRESS");
+        self.strategy_id = strategy_id;
+    }
+
+    /// Set the part of locked tokens which can't be deployed, it pays unstakes right away.
+    /// Requirements:
+    /// * Can only be called by the owner.
+    /// * `liquidity_buffer_bps` must not exceed 10_000.
+    pub fn set_liquidity_buffer(&mut self, liquidity_buffer_bps: u32) {
+        self.assert_owner();
+        assert!(liquidity_buffer_bps as u128 <= FULL_BASIS_POINTS, "ERR_BUFFER_TOO_HIGH");
+        self.liquidity_buffer_bps = liquidity_buffer_bps;
+    }
+
+    pub fn get_strategy(&self) -> StrategyView {
+        StrategyView {
+            strategy_id: self.strategy_id.clone(),
+            deployed_amount: self.deployed_amount.into(),
+            liquidity_buffer_bps: self.liquidity_buffer_bps,
+            liquid_amount: self.internal_liquid_amount().into(),
+            total_queued: self.total_queued_withdraw.into(),
+            queue_length: self.withdraw_queue_tail - self.withdraw_queue_head,
+            in_progress: self.strategy_in_progress,
+        }
+    }
+
+    /// Queued withdraws in payment order
+    pub fn get_withdraw_queue(&self, from_index: Option<u64>, limit: Option<u64>) -> Vec<QueuedWithdrawView> {
+        let from_index = self.withdraw_queue_head + from_index.unwrap_or(0);
+        let to_index = std::cmp::min(from_index + limit.unwrap_or(100), self.withdraw_queue_tail);
+        (from_index..to_index)
+            .map(|index| {
+                let queued = self.withdraw_queue.get(&index).unwrap();
+                QueuedWithdrawView {
+                    account_id: queued.account_id,
+                    amount: queued.unlocked.into(),
+                }
+            })
+            .collect()
+    }
+
+    /// Supply `amount` of locked tokens to the strategy.
+    /// Requirements:
+    /// * Can only be called by the owner.
+    /// * The withdraw queue must be empty and no strategy operation should wait for a callback.
+    /// * Locked tokens left in the contract must cover the liquidity buffer.
+    /// * Requires attached deposit of exactly 1 yoctoNEAR.
+    #[payable]
+    pub fn deploy_to_strategy(&mut self, amount: WrappedBalance) -> Promise {
+        assert_one_yocto();
+        self.assert_owner();
+        let strategy_id = self.strategy_id.clone().expect("ERR_NO_STRATEGY");
+        let amount: Balance = amount.into();
+        assert!(amount > 0, "ERR_ZERO_AMOUNT");
+        assert_eq!(self.total_queued_withdraw, 0, "ERR_WITHDRAW_QUEUE_NOT_EMPTY");
+        self.internal_start_strategy_operation();
+        let liquid_amount = self.internal_liquid_amount();
+        assert!(
+            amount <= liquid_amount && liquid_amount - amount >= bps_of(self.ledger.locked_token_amount, self.liquidity_buffer_bps),
+            "ERR_BUFFER_TOO_LOW"
+        );
+        self.deployed_amount += amount;
+
+        events::emit::strategy_deploy(&strategy_id, amount, &self.token_id);
+        ext_ft_core::ext(self.token_id.clone())
+            .with_attached_deposit(ONE_YOCTO)
+            .with_static_gas(GAS_FOR_STRATEGY_DEPOSIT)
+            .ft_transfer_call(strategy_id, amount.into(), None, String::new())
+            .then(
+                ext_self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_AFTER_STRATEGY_DEPOSIT)
+                    .callback_post_strategy_deploy(amount.into()),
+            )
+    }
+
+    /// Take `amount` back from the strategy, it pays queued withdraws first.
+    /// Requirements:
+    /// * Can only be called by the owner.
+    /// * `amount` must not exceed the deployed amount.
+    /// * No strategy operation should wait for a callback.
+    /// * Requires attached deposit of exactly 1 yoctoNEAR.
+    #[payable]
+    pub fn withdraw_from_strategy(&mut self, amount: WrappedBalance) -> Promise {
+        assert_one_yocto();
+        self.assert_owner();
+        let amount: Balance = amount.into();
+        assert!(amount > 0 && amount <= self.deployed_amount, "ERR_ILLEGAL_AMOUNT");
+        self.internal_strategy_withdraw(amount)
+    }
+
+    /// Record a loss of the strategy, `amount` of deployed tokens which won't come back.
+    /// It lowers the virtual price for all stakers of the main pool.
+    /// Requirements:
+    /// * Can only be called by the owner.
+    /// * `amount` must not exceed the deployed amount and must be less than locked tokens.
+    /// * Requires attached deposit of exactly 1 yoctoNEAR.
+    #[payable]
+    pub fn write_down_strategy(&mut self, amount: WrappedBalance) {
+        // Checkpoint
+        self.distribute_reward();
+
+        assert_one_yocto();
+        self.assert_owner();
+        let strategy_id = self.strategy_id.clone().expect("ERR_NO_STRATEGY");
+        let amount: Balance = amount.into();
+        assert!(amount > 0 && amount <= self.deployed_amount, "ERR_ILLEGAL_AMOUNT");
+        assert!(amount < self.ledger.locked_token_amount, "ERR_WRITE_DOWN_TOO_HIGH");
+        self.deployed_amount -= amount;
+        self.ledger.locked_token_amount -= amount;
+        events::emit::strategy_write_down(&strategy_id, amount, &self.token_id);
+    }
+
+    /// Pay queued withdraws, or take tokens for them and the liquidity buffer back from the strategy.
+    /// Anyone can call it, `get_strategy` shows the queue.
+    pub fn process_withdraw_queue(&mut self) -> PromiseOrValue<()> {
+        assert!(self.withdraw_queue_tail > self.withdraw_queue_head, "ERR_WITHDRAW_QUEUE_EMPTY");
+        if self.internal_pay_withdraw_queue() > 0 {
+            return PromiseOrValue::Value(());
+        }
+        let locked_token_amount = self.ledger.locked_token_amount;
+        let amount = std::cmp::min(
+            self.deployed_amount,
+            (self.deployed_amount + bps_of(locked_token_amount, self.liquidity_buffer_bps)).saturating_sub(locked_token_amount),
+        );
+        assert!(amount > 0, "ERR_NOTHING_TO_WITHDRAW");
+        self.internal_strategy_withdraw(amount).into()
+    }
+
+    /// Move the strategy yield, its balance above the deployed amount, to undistributed reward.
+    /// Anyone can call it while no strategy operation waits for a callback.
+    pub fn harvest_strategy(&mut self) -> Promise {
+        let strategy_id = self.strategy_id.clone().expect("ERR_NO_STRATEGY");
+        self.internal_start_strategy_operation();
+        ext_strategy::ext(strategy_id)
+            .with_static_gas(GAS_FOR_STRATEGY_BALANCE)
+            .get_balance(env::current_account_id())
+            .then(
+                ext_self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_AFTER_STRATEGY_BALANCE)
+                    .callback_post_strategy_balance(),
+            )
+    }
+
+    #[private]
+    pub fn callback_post_strategy_deploy(&mut self, amount: WrappedBalance) {
+        assert_eq!(env::promise_results_count(), 1, "Err: expected 1 promise result from deploy");
+        // ft_transfer_call returns the used amount, the rest is refunded to the contract
+        let used = match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(value) => serde_json::from_slice::<U128>(&value).map(|used| used.0).unwrap_or(0),
+            PromiseResult::Failed => 0,
+        };
+        if used < amount.0 {
+            self.deployed_amount -= amount.0 - used;
+            log!("Deploy of {} to the strategy refunded {}.", amount.0, amount.0 - used);
+        }
+        self.strategy_in_progress = false;
+    }
+
+    #[private]
+    pub fn callback_pre_strategy_withdraw(&mut self, amount: WrappedBalance) -> PromiseOrValue<()> {
+        let balance_before = match Self::internal_strategy_balance_result() {
+            Some(balance_before) => balance_before,
+            None => {
+                log!("Withdraw of {} from the strategy failed, its balance is unknown.", amount.0);
+                self.strategy_in_progress = false;
+                return PromiseOrValue::Value(());
+            }
+        };
+        let strategy_id = self.strategy_id.clone().unwrap();
+        ext_strategy::ext(strategy_id.clone())
+            .with_attached_deposit(ONE_YOCTO)
+            .with_static_gas(GAS_FOR_STRATEGY_WITHDRAW)
+            .withdraw(amount)
+            .then(
+                ext_strategy::ext(strategy_id)
+                    .with_static_gas(GAS_FOR_STRATEGY_BALANCE)
+                    .get_balance(env::current_account_id()),
+            )
+            .then(
+                ext_self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_AFTER_STRATEGY_WITHDRAW)
+                    .callback_post_strategy_withdraw(amount, balance_before.into()),
+            )
+            .into()
+    }
+
+    /// Lower the deployed amount by the tokens which left the strategy balance and pay the queue with them
+    #[private]
+    pub fn callback_post_strategy_withdraw(&mut self, amount: WrappedBalance, balance_before: WrappedBalance) {
+        self.strategy_in_progress = false;
+        let received = Self::internal_strategy_balance_result()
+            .map(|balance_after| std::cmp::min(amount.0, balance_before.0.saturating_sub(balance_after)))
+            .unwrap_or(0);
+        if received == 0 {
+            log!("Withdraw of {} from the strategy failed.", amount.0);
+            return;
+        }
+        self.deployed_amount -= received;
+        events::emit::strategy_withdraw(self.strategy_id.as_ref().unwrap(), received, &self.token_id);
+        self.internal_pay_withdraw_queue();
+    }
+
+    #[private]
+    pub fn callback_post_strategy_balance(&mut self) -> PromiseOrValue<()> {
+        let balance = Self::internal_strategy_balance_result().unwrap_or(0);
+        let amount = balance.saturating_sub(self.deployed_amount);
+        if amount == 0 {
+            self.strategy_in_progress = false;
+            return PromiseOrValue::Value(());
+        }
+        let strategy_id = self.strategy_id.clone().unwrap();
+        ext_strategy::ext(strategy_id.clone())
+            .with_attached_deposit(ONE_YOCTO)
+            .with_static_gas(GAS_FOR_STRATEGY_WITHDRAW)
+            .withdraw(amount.into())
+            .then(
+                ext_strategy::ext(strategy_id)
+                    .with_static_gas(GAS_FOR_STRATEGY_BALANCE)
+                    .get_balance(env::current_account_id()),
+            )
+            .then(
+                ext_self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_AFTER_STRATEGY_HARVEST)
+                    .callback_post_strategy_harvest(amount.into(), balance.into()),
+            )
+            .into()
+    }
+
+    /// Add the yield which left the strategy balance as reward
+    #[private]
+    pub fn callback_post_strategy_harvest(&mut self, amount: WrappedBalance, balance_before: WrappedBalance) {
+        self.strategy_in_progress = false;
+        let strategy_id = self.strategy_id.clone().unwrap();
+        let received = Self::internal_strategy_balance_result()
+            .map(|balance_after| std::cmp::min(amount.0, balance_before.0.saturating_sub(balance_after)))
+            .unwrap_or(0);
+        if received == 0 {
+            log!("Harvest of {} from the strategy failed.", amount.0);
+            return;
+        }
+        // Checkpoint
+        self.distribute_reward();
+        self.internal_add_reward(&strategy_id, received);
+        events::emit::strategy_harvest(&strategy_id, received, &self.token_id);
+    }
+}
+
+impl Contract {
+    /// Locked and queued tokens held by the contract, the rest of them is deployed
+    pub(crate) fn internal_liquid_amount(&self) -> Balance {
+        (self.ledger.locked_token_amount + self.total_queued_withdraw).saturating_sub(self.deployed_amount)
+    }
+
+    /// Whether `unlocked` tokens, already taken from locked_token_amount, must wait in the withdraw queue
+    pub(crate) fn internal_should_queue_withdraw(&self) -> bool {
+        self.withdraw_queue_tail > self.withdraw_queue_head
+            || self.ledger.locked_token_amount + self.total_queued_withdraw < self.deployed_amount
+    }
+
+    pub(crate) fn internal_queue_withdraw(&mut self, account_id: &AccountId, unlocked: Balance, shares: Balance, principal: Balance) {
+        self.withdraw_queue.insert(
+            &self.withdraw_queue_tail,
+            &QueuedWithdraw {
+                account_id: account_id.clone(),
+                unlocked,
+                shares,
+                principal,
+            },
+        );
+        self.withdraw_queue_tail += 1;
+        self.total_queued_withdraw += unlocked;
+        events::emit::queue_withdraw(account_id, unlocked, &self.token_id);
+    }
+
+    /// Pay queued withdraws in order while the contract holds enough tokens, returns how many were paid
+    fn internal_pay_withdraw_queue(&mut self) -> u64 {
+        let mut paid = 0;
+        while paid < MAX_QUEUED_WITHDRAWS_PER_CALL && self.withdraw_queue_head < self.withdraw_queue_tail {
+            let queued = self.withdraw_queue.get(&self.withdraw_queue_head).unwrap();
+            if (self.ledger.locked_token_amount + self.total_queued_withdraw).saturating_sub(queued.unlocked) < self.deployed_amount {
+                break;
+            }
+            self.withdraw_queue.remove(&self.withdraw_queue_head);
+            self.withdraw_queue_head += 1;
+            self.total_queued_withdraw -= queued.unlocked;
+            self.internal_pay_unstaked(&queued.account_id, queued.unlocked, queued.shares, queued.principal);
+            paid += 1;
+        }
+        paid
+    }
+
+    /// Withdraw `amount` from the strategy, its balance before and after the withdraw tells how much arrived
+    fn internal_strategy_withdraw(&mut self, amount: Balance) -> Promise {
+        let strategy_id = self.strategy_id.clone().expect("ERR_NO_STRATEGY");
+        self.internal_start_strategy_operation();
+        ext_strategy::ext(strategy_id)
+            .with_static_gas(GAS_FOR_STRATEGY_BALANCE)
+            .get_balance(env::current_account_id())
+            .then(
+                ext_self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_BEFORE_STRATEGY_WITHDRAW)
+                    .callback_pre_strategy_withdraw(amount.into()),
+            )
+    }
+
+    /// Balances are measured one operation at a time, another one would move them meanwhile
+    fn internal_start_strategy_operation(&mut self) {
+        assert!(!self.strategy_in_progress, "ERR_STRATEGY_IN_PROGRESS");
+        self.strategy_in_progress = true;
+        self.operation_nonce += 1;
+    }
+
+    /// Strategy balance of the contract returned by `get_balance`, NONE if the call failed
+    fn internal_strategy_balance_result() -> Option<Balance> {
+        assert_eq!(env::promise_results_count(), 1, "Err: expected 1 promise result from get_balance");
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(value) => serde_json::from_slice::<U128>(&value).map(|balance| balance.0).ok(),
+            PromiseResult::Failed => None,
+        }
+    }
+}
@@ -0,0 +1,268 @@
+use crate::ft::ext_self;
+use crate::*;
+use near_contract_standards::fungible_token::core::ext_ft_core;
+use near_sdk::json_types::U128;
+use near_sdk::serde::Deserialize;
+use near_sdk::{serde_json, PromiseOrValue, PromiseResult, ONE_YOCTO};
+
+const GAS_FOR_DEX_DEPOSIT: Gas = Gas(Gas::ONE_TERA.0 * 50);
+const GAS_FOR_SWAP: Gas = Gas(Gas::ONE_TERA.0 * 20);
+const GAS_FOR_DEX_WITHDRAW: Gas = Gas(Gas::ONE_TERA.0 * 40);
+const GAS_FOR_GET_DEPOSIT: Gas = Gas(Gas::ONE_TERA.0 * 5);
+const GAS_FOR_AFTER_DEX_WITHDRAW: Gas = Gas(Gas::ONE_TERA.0 * 10);
+const GAS_FOR_BEFORE_DEX_WITHDRAW: Gas =
+    Gas(GAS_FOR_DEX_WITHDRAW.0 + GAS_FOR_GET_DEPOSIT.0 + GAS_FOR_AFTER_DEX_WITHDRAW.0 + Gas::ONE_TERA.0 * 10);
+const GAS_FOR_AFTER_SWAP: Gas = Gas(GAS_FOR_GET_DEPOSIT.0 + GAS_FOR_BEFORE_DEX_WITHDRAW.0 + Gas::ONE_TERA.0 * 10);
+const GAS_FOR_AFTER_DEX_DEPOSIT: Gas = Gas(GAS_FOR_SWAP.0 + GAS_FOR_AFTER_SWAP.0 + Gas::ONE_TERA.0 * 10);
+
+/// Swap action of Ref Finance
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SwapAction {
+    pub pool_id: u64,
+    pub token_in: AccountId,
+    pub amount_in: Option<WrappedBalance>,
+    pub token_out: AccountId,
+    pub min_amount_out: WrappedBalance,
+}
+
+#[ext_contract(ext_dex)]
+#[allow(dead_code)]
+pub trait ExtDex {
+    fn swap(&mut self, actions: Vec<SwapAction>, referral_id: Option<AccountId>) -> WrappedBalance;
+    fn withdraw(&mut self, token_id: AccountId, amount: WrappedBalance, unregister: Option<bool>) -> Promise;
+    fn get_deposit(&self, account_id: AccountId, token_id: AccountId) -> WrappedBalance;
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Set the Ref-style exchange which swaps reward tokens, NONE stops swaps.
+    /// Requirements:
+    /// * Can only be called by the owner.
+    /// * The contract must be registered in the exchange and its reward token pools.
+    pub fn set_swap_dex(&mut self, dex_id: Option<AccountId>) {
+        self.assert_owner();
+        self.swap_dex_id = dex_id;
+    }
+
+    pub fn get_swap_dex(&self) -> Option<AccountId> {
+        self.swap_dex_id.clone()
+    }
+
+    /// Accept `token_id` as reward, it's swapped to the staked token in the exchange pool `dex_pool_id`.
+    /// Requirements:
+    /// * Can only be called by the owner.
+    /// * `token_id` must differ from the staked token.
+    pub fn set_reward_token(&mut self, token_id: AccountId, dex_pool_id: u64) {
+        self.assert_owner();
+        assert_ne!(token_id, self.token_id, "ERR_ILLEGAL_TOKEN");
+        self.reward_tokens.insert(&token_id, &dex_pool_id);
+    }
+
+    /// Stop accepting `token_id` as reward.
+    /// Requirements:
+    /// * Can only be called by the owner.
+    pub fn remove_reward_token(&mut self, token_id: AccountId) {
+        self.assert_owner();
+        self.reward_tokens.remove(&token_id);
+    }
+
+    /// Approved reward tokens with their exchange pool ids
+    pub fn get_reward_tokens(&self) -> Vec<(AccountId, u64)> {
+        self.reward_tokens.to_vec()
+    }
+
+    #[private]
+    pub fn callback_post_dex_deposit(
+        &mut self,
+        sender_id: AccountId,
+        token_in: AccountId,
+        pool_id: u64,
+        amount_in: WrappedBalance,
+        min_amount_out: WrappedBalance,
+        dex_id: AccountId,
+    ) -> PromiseOrValue<()> {
+        assert_eq!(env::promise_results_count(), 1, "Err: expected 1 promise result from deposit");
+        // ft_transfer_call returns the used amount, the rest is refunded to the contract
+        let used = match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(value) => serde_json::from_slice::<U128>(&value).map(|used| used.0).unwrap_or(0),
+            PromiseResult::Failed => 0,
+        };
+        if used != amount_in.0 {
+            log!("Swap deposit of {} {} failed.", amount_in.0, token_in);
+            events::emit::swap_rewards_failed(&sender_id, amount_in.0, &token_in);
+            self.swap_in_progress = false;
+            return PromiseOrValue::Value(());
+        }
+
+        // the pool approved at deposit time, the token may be removed meanwhile
+        ext_dex::ext(dex_id.clone())
+            .with_attached_deposit(ONE_YOCTO)
+            .with_static_gas(GAS_FOR_SWAP)
+            .swap(
+                vec![SwapAction {
+                    pool_id,
+                    token_in: token_in.clone(),
+                    amount_in: Some(amount_in),
+                    token_out: self.token_id.clone(),
+                    min_amount_out,
+                }],
+                None,
+            )
+            .then(
+                ext_self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_AFTER_SWAP)
+                    .callback_post_swap(sender_id, token_in, amount_in, dex_id),
+            )
+            .into()
+    }
+
+    #[private]
+    pub fn callback_post_swap(
+        &mut self,
+        sender_id: AccountId,
+        token_in: AccountId,
+        amount_in: WrappedBalance,
+        dex_id: AccountId,
+    ) -> PromiseOrValue<()> {
+        assert_eq!(env::promise_results_count(), 1, "Err: expected 1 promise result from swap");
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(value) => {
+                let amount_out: WrappedBalance = serde_json::from_slice(&value).expect("ERR_ILLEGAL_SWAP_RESULT");
+                // the exchange deposit before the withdraw tells how much of it leaves
+                ext_dex::ext(dex_id.clone())
+                    .with_static_gas(GAS_FOR_GET_DEPOSIT)
+                    .get_deposit(env::current_account_id(), self.token_id.clone())
+                    .then(
+                        ext_self::ext(env::current_account_id())
+                            .with_static_gas(GAS_FOR_BEFORE_DEX_WITHDRAW)
+                            .callback_pre_dex_withdraw(sender_id, token_in, amount_in, amount_out, dex_id),
+                    )
+                    .into()
+            }
+            PromiseResult::Failed => {
+                // e.g. slippage, take the reward token back, the owner can recover it
+                log!("Swap of {} {} failed.", amount_in.0, token_in);
+                events::emit::swap_rewards_failed(&sender_id, amount_in.0, &token_in);
+                self.swap_in_progress = false;
+                ext_dex::ext(dex_id)
+                    .with_attached_deposit(ONE_YOCTO)
+                    .with_static_gas(GAS_FOR_DEX_WITHDRAW)
+                    .withdraw(token_in, amount_in, None)
+                    .into()
+            }
+        }
+    }
+
+    #[private]
+    pub fn callback_pre_dex_withdraw(
+        &mut self,
+        sender_id: AccountId,
+        token_in: AccountId,
+        amount_in: WrappedBalance,
+        amount_out: WrappedBalance,
+        dex_id: AccountId,
+    ) -> PromiseOrValue<()> {
+        let deposit_before = match Self::internal_dex_deposit_result() {
+            Some(deposit_before) => deposit_before,
+            None => {
+                // the swapped tokens stay in the exchange deposit of the contract
+                log!("Exchange deposit of {} before the withdraw is unknown.", self.token_id);
+                events::emit::swap_rewards_failed(&sender_id, amount_in.0, &token_in);
+                self.swap_in_progress = false;
+                return PromiseOrValue::Value(());
+            }
+        };
+        ext_dex::ext(dex_id.clone())
+            .with_attached_deposit(ONE_YOCTO)
+            .with_static_gas(GAS_FOR_DEX_WITHDRAW)
+            .withdraw(self.token_id.clone(), amount_out, None)
+            .then(
+                ext_dex::ext(dex_id)
+                    .with_static_gas(GAS_FOR_GET_DEPOSIT)
+                    .get_deposit(env::current_account_id(), self.token_id.clone()),
+            )
+            .then(
+                ext_self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_AFTER_DEX_WITHDRAW)
+                    .callback_post_dex_withdraw(sender_id, token_in, amount_in, amount_out, deposit_before.into()),
+            )
+            .into()
+    }
+
+    /// Add the swapped tokens which arrived as reward. Ref keeps a failed transfer in the exchange deposit
+    /// and still reports the withdraw as successful, so the exchange deposit of the contract is compared instead.
+    #[private]
+    pub fn callback_post_dex_withdraw(
+        &mut self,
+        sender_id: AccountId,
+        token_in: AccountId,
+        amount_in: WrappedBalance,
+        amount_out: WrappedBalance,
+        deposit_before: WrappedBalance,
+    ) {
+        self.swap_in_progress = false;
+        let received = Self::internal_dex_deposit_result()
+            .map(|deposit_after| std::cmp::min(amount_out.0, deposit_before.0.saturating_sub(deposit_after)))
+            .unwrap_or(0);
+        if received == 0 {
+            // the swapped tokens stay in the exchange deposit of the contract
+            log!("Withdraw of {} swapped from {} {} failed.", amount_out.0, amount_in.0, token_in);
+            events::emit::swap_rewards_failed(&sender_id, amount_in.0, &token_in);
+            return;
+        }
+        // Checkpoint
+        self.distribute_reward();
+        self.internal_add_reward(&sender_id, received);
+        events::emit::swap_rewards_succeeded(&sender_id, &token_in, amount_in.0, received, &self.token_id);
+    }
+}
+
+impl Contract {
+    /// Deposit `amount` of an approved reward token to the exchange, it's swapped to the staked token
+    /// for at least `min_amount_out` and added to undistributed_reward. Swaps run one at a time,
+    /// the exchange deposit of the staked token measures what the withdraw sends out.
+    pub(crate) fn internal_swap_rewards(
+        &mut self,
+        token_in: &AccountId,
+        sender_id: &AccountId,
+        amount: Balance,
+        min_amount_out: Balance,
+    ) -> Promise {
+        let pool_id = self.reward_tokens.get(token_in).expect("ERR_REWARD_TOKEN_NOT_APPROVED");
+        let dex_id = self.swap_dex_id.clone().expect("ERR_NO_SWAP_DEX");
+        assert!(min_amount_out > 0, "ERR_ZERO_MIN_AMOUNT_OUT");
+        assert!(!self.swap_in_progress, "ERR_SWAP_IN_PROGRESS");
+        self.swap_in_progress = true;
+        self.operation_nonce += 1;
+
+        ext_ft_core::ext(token_in.clone())
+            .with_attached_deposit(ONE_YOCTO)
+            .with_static_gas(GAS_FOR_DEX_DEPOSIT)
+            .ft_transfer_call(dex_id.clone(), amount.into(), None, String::new())
+            .then(
+                ext_self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_AFTER_DEX_DEPOSIT)
+                    .callback_post_dex_deposit(
+                        sender_id.clone(),
+                        token_in.clone(),
+                        pool_id,
+                        amount.into(),
+                        min_amount_out.into(),
+                        dex_id,
+                    ),
+            )
+    }
+
+    /// Exchange deposit of the contract returned by `get_deposit`, NONE if the call failed
+    fn internal_dex_deposit_result() -> Option<Balance> {
+        assert_eq!(env::promise_results_count(), 1, "Err: expected 1 promise result from get_deposit");
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(value) => serde_json::from_slice::<U128>(&value).map(|deposit| deposit.0).ok(),
+            PromiseResult::Failed => None,
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use crate::*;
+use near_sdk::collections::{LookupMap, UnorderedMap, UnorderedSet, Vector};
+
+const GAS_FOR_MIGRATE: Gas = Gas(Gas::ONE_TERA.0 * 20);
+/// Storage key of the contract state, written by near_bindgen
+const STATE_KEY: &[u8] = b"STATE";
+
+/// Contract state of the first upgradable release, before pools, points, vesting, positions and boosts
+#[derive(BorshDeserialize)]
+pub struct OldContract {
+    owner_id: AccountId,
+    reward_manager_id: Option<AccountId>,
+    factory_id: Option<AccountId>,
+    token_id: AccountId,
+    undistributed_reward: Balance,
+    locked_token_amount: Balance,
+    prev_distribution_time_in_sec: u32,
+    reward_genesis_time_in_sec: u32,
+    reward_per_sec: Balance,
+    deferred_reward: Balance,
+    account_number: u64,
+    pending_transfer_amount: Balance,
+    performance_fee_bps: u32,
+    treasury_id: Option<AccountId>,
+    accrued_performance_fee: Balance,
+    exit_fee_bps: u32,
+    exit_fee_duration_in_sec: u32,
+    exit_fee_to_treasury: bool,
+    min_holding_period_in_sec: u32,
+    referral_fee_bps: u32,
+    referral_pool: Balance,
+    total_referral_earnings: Balance,
+    total_staked: Balance,
+
+    shares: UnorderedMap<AccountId, Balance>,
+    accounts: LookupMap<AccountId, AccountV1>,
+    shares_checkpoints: LookupMap<AccountId, Vector<Checkpoint>>,
+    total_staked_checkpoints: Vector<Checkpoint>,
+    delegated_power: LookupMap<AccountId, Balance>,
+
+    proposers: UnorderedSet<AccountId>,
+    polls: Vector<Poll>,
+    poll_votes: LookupMap<(PollId, AccountId), u32>,
+
+    referees: LookupMap<AccountId, Vector<AccountId>>,
+    referral_earnings: LookupMap<AccountId, Balance>,
+
+    price_history: Vector<PriceSnapshot>,
+    price_history_head: u64,
+    last_price_snapshot_time_in_sec: Option<u32>,
+    price_snapshot_interval_in_sec: u32,
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn get_factory(&self) -> Option<AccountId> {
+        self.factory_id.clone()
+    }
+
+    /// Deploy the new contract code passed as raw input and migrate the state.
+    /// Requirements:
+    /// * Can only be called by the owner or the factory which deployed the contract.
+    pub fn upgrade(&self) -> Promise {
+        let predecessor_id = env::predecessor_account_id();
+        assert!(
+            predecessor_id == self.owner_id || Some(&predecessor_id) == self.factory_id.as_ref(),
+            "ERR_NOT_AN_OWNER_OR_FACTORY"
+        );
+        let code = env::input().expect("ERR_NO_CODE");
+        Promise::new(env::current_account_id())
+            .deploy_contract(code)
+            .function_call("migrate".to_string(), vec![], 0, GAS_FOR_MIGRATE)
+    }
+
+    /// Called by `upgrade` after the new code is deployed. The current state is kept as it is,
+    /// the state of `OldContract` is converted: the added features start off, points accrue
+    /// from the upgrade and accounts are converted on read.
+    #[init(ignore_state)]
+    #[private]
+    pub fn migrate() -> Self {
+        let state = env::storage_read(STATE_KEY).expect("ERR_NO_STATE");
+        if let Ok(contract) = Self::try_from_slice(&state) {
+            return contract;
+        }
+        let old = OldContract::try_from_slice(&state).expect("ERR_UNKNOWN_STATE");
+        Self {
+            ledger: Ledger {
+                undistributed_reward: old.undistributed_reward,
+                locked_token_amount: old.locked_token_amount,
+                prev_distribution_time_in_sec: old.prev_distribution_time_in_sec,
+                reward_genesis_time_in_sec: old.reward_genesis_time_in_sec,
+                reward_per_sec: old.reward_per_sec,
+                deferred_reward: old.deferred_reward,
+                total_staked: old.total_staked,
+            },
+            account_number: old.account_number,
+            pending_transfer_amount: old.pending_transfer_amount,
+            performance_fee_bps: old.performance_fee_bps,
+            treasury_id: old.treasury_id,
+            accrued_performance_fee: old.accrued_performance_fee,
+            exit_fee_bps: old.exit_fee_bps,
+            exit_fee_duration_in_sec: old.exit_fee_duration_in_sec,
+            exit_fee_to_treasury: old.exit_fee_to_treasury,
+            min_holding_period_in_sec: old.min_holding_period_in_sec,
+            referral_fee_bps: old.referral_fee_bps,
+            referral_pool: old.referral_pool,
+            total_referral_earnings: old.total_referral_earnings,
+
+            shares: old.shares,
+            legacy_accounts: old.accounts,
+            shares_checkpoints: old.shares_checkpoints,
+            total_staked_checkpoints: old.total_staked_checkpoints,
+            delegated_power: old.delegated_power,
+
+            proposers: old.proposers,
+            polls: old.polls,
+            poll_votes: old.poll_votes,
+
+            referees: old.referees,
+            referral_earnings: old.referral_earnings,
+
+            price_history: old.price_history,
+            price_history_head: old.price_history_head,
+            last_price_snapshot_time_in_sec: old.last_price_snapshot_time_in_sec,
+            price_snapshot_interval_in_sec: old.price_snapshot_interval_in_sec,
+
+            reward_manager_id: old.reward_manager_id,
+
+            // the boost reward per weight starts from 0 with the shares of every staker as its weight
+            boost_ledger: WeightLedger {
+                prev_distribution_time_in_sec: nano_to_sec(env::block_timestamp()),
+                total_weight: old.total_staked,
+                ..Default::default()
+            },
+            ..Self::new(old.owner_id, old.token_id, old.factory_id)
+        }
+    }
+}
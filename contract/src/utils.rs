@@ -1,7 +1,13 @@
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+#[cfg(not(target_arch = "wasm32"))]
+use near_sdk::serde::{de, Deserialize, Deserializer};
+use near_sdk::serde::{Serialize, Serializer};
 use near_sdk::Timestamp;
 use uint::construct_uint;
 
 pub const DURATION_30DAYS_IN_SEC: u32 = 60 * 60 * 24 * 30;
+pub const DURATION_1YEAR_IN_SEC: u32 = 60 * 60 * 24 * 365;
+pub const FULL_BASIS_POINTS: u128 = 10_000;
 
 construct_uint! {
     /// 256-bit unsigned integer.
@@ -11,3 +17,52 @@ construct_uint! {
 pub fn nano_to_sec(nano: Timestamp) -> u32 {
     (nano / 1_000_000_000) as u32
 }
+
+/// `2022-01-22 01:00:00 UTC` of a unix timestamp, by the days-to-civil algorithm of H. Hinnant
+pub fn format_utc(timestamp_in_sec: u32) -> String {
+    let days = timestamp_in_sec / 86_400 + 719_468;
+    let seconds = timestamp_in_sec % 86_400;
+    let era = days / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        seconds / 3_600,
+        seconds % 3_600 / 60,
+        seconds % 60
+    )
+}
+
+impl BorshSerialize for U256 {
+    fn serialize<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        BorshSerialize::serialize(&self.0, writer)
+    }
+}
+
+impl BorshDeserialize for U256 {
+    fn deserialize(buf: &mut &[u8]) -> std::io::Result<Self> {
+        Ok(U256(<[u64; 4] as BorshDeserialize>::deserialize(buf)?))
+    }
+}
+
+/// JSON of U256 is a decimal string, like U128
+impl Serialize for U256 {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<'de> Deserialize<'de> for U256 {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        U256::from_dec_str(&<String as Deserialize>::deserialize(deserializer)?).map_err(de::Error::custom)
+    }
+}
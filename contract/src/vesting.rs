@@ -0,0 +1,208 @@
+use crate::ft::{ext_self, GAS_FOR_AFTER_FT_TRANSFER, GAS_FOR_FT_TRANSFER};
+use crate::*;
+use near_contract_standards::fungible_token::core::ext_ft_core;
+#[cfg(not(target_arch = "wasm32"))]
+use near_sdk::serde::Deserialize;
+use near_sdk::{assert_one_yocto, PromiseResult, ONE_YOCTO};
+
+/// Hard cap of the reward vesting duration, 1 year
+pub const MAX_REWARD_VESTING_DURATION_IN_SEC: u32 = 365 * 24 * 60 * 60;
+
+/// Reward part of unstaked tokens which is not claimed yet
+#[derive(BorshSerialize, BorshDeserialize, Default)]
+pub struct Vesting {
+    /// vested at start_time
+    pub vested: Balance,
+    /// vests linearly from start_time to end_time
+    pub locked: Balance,
+    pub start_time_in_sec: u32,
+    pub end_time_in_sec: u32,
+}
+
+impl Vesting {
+    fn vested_at(&self, timestamp_in_sec: u32) -> Balance {
+        if timestamp_in_sec >= self.end_time_in_sec {
+            self.vested + self.locked
+        } else if timestamp_in_sec <= self.start_time_in_sec {
+            self.vested
+        } else {
+            let elapsed = timestamp_in_sec - self.start_time_in_sec;
+            let duration = self.end_time_in_sec - self.start_time_in_sec;
+            self.vested + (U256::from(self.locked) * U256::from(elapsed) / U256::from(duration)).as_u128()
+        }
+    }
+
+    /// Start vesting `amount` for `duration_in_sec` from now.
+    /// The end time is averaged by the unvested amounts, so adding reward never speeds up vesting of the earlier one.
+    fn add(&mut self, amount: Balance, duration_in_sec: u32, cur_time_in_sec: u32) {
+        let vested = self.vested_at(cur_time_in_sec);
+        let unvested = self.vested + self.locked - vested;
+        let remaining = self.end_time_in_sec.saturating_sub(cur_time_in_sec);
+        let end_time_in_sec = cur_time_in_sec
+            + ((U256::from(unvested) * U256::from(remaining) + U256::from(amount) * U256::from(duration_in_sec))
+                / U256::from(unvested + amount))
+            .as_u32();
+        *self = Vesting {
+            vested,
+            locked: unvested + amount,
+            start_time_in_sec: cur_time_in_sec,
+            end_time_in_sec,
+        };
+    }
+
+    /// Take everything vested up to now
+    fn claim(&mut self, cur_time_in_sec: u32) -> Balance {
+        let vested = self.vested_at(cur_time_in_sec);
+        self.locked = self.vested + self.locked - vested;
+        self.vested = 0;
+        self.start_time_in_sec = std::cmp::max(self.start_time_in_sec, cur_time_in_sec);
+        vested
+    }
+
+    fn is_empty(&self) -> bool {
+        self.vested == 0 && self.locked == 0
+    }
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, Deserialize))]
+pub struct VestingView {
+    // at call time, amount which can be claimed
+    pub claimable: WrappedBalance,
+    // at call time, amount which vests until end_time
+    pub locked: WrappedBalance,
+    pub end_time_in_sec: u32,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Set the period over which the reward part of unstaked tokens vests, 0 sends it with the principal.
+    /// Requirements:
+    /// * Can only be called by the owner.
+    /// * `reward_vesting_duration_in_sec` must not exceed 1 year.
+    pub fn set_reward_vesting_duration(&mut self, reward_vesting_duration_in_sec: u32) {
+        self.assert_owner();
+        assert!(
+            reward_vesting_duration_in_sec <= MAX_REWARD_VESTING_DURATION_IN_SEC,
+            "ERR_VESTING_DURATION_TOO_LONG"
+        );
+        self.reward_vesting_duration_in_sec = reward_vesting_duration_in_sec;
+    }
+
+    pub fn get_reward_vesting_duration(&self) -> u32 {
+        self.reward_vesting_duration_in_sec
+    }
+
+    /// Staked token of the account shares which count as principal on unstake, the rest is reward
+    pub fn get_cost_basis(&self, account_id: AccountId) -> WrappedBalance {
+        self.internal_get_account(&account_id).cost_basis.into()
+    }
+
+    pub fn get_vesting(&self, account_id: AccountId) -> VestingView {
+        let vesting = self.vestings.get(&account_id).unwrap_or_default();
+        let claimable = vesting.vested_at(nano_to_sec(env::block_timestamp()));
+        VestingView {
+            claimable: claimable.into(),
+            locked: (vesting.vested + vesting.locked - claimable).into(),
+            end_time_in_sec: vesting.end_time_in_sec,
+        }
+    }
+
+    /// Reward vesting or vested, but not claimed yet
+    pub fn get_total_vesting(&self) -> WrappedBalance {
+        self.total_vesting.into()
+    }
+
+    /// Send vested reward to the predecessor account.
+    /// Requirements:
+    /// * Requires attached deposit of exactly 1 yoctoNEAR.
+    #[payable]
+    pub fn claim_vested(&mut self) -> Promise {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let mut vesting = self.vestings.get(&account_id).unwrap_or_default();
+        let amount = vesting.claim(nano_to_sec(env::block_timestamp()));
+        assert!(amount > 0, "ERR_NOTHING_VESTED");
+        self.internal_set_vesting(&account_id, &vesting);
+        self.total_vesting -= amount;
+        self.pending_transfer_amount += amount;
+
+        ext_ft_core::ext(self.token_id.clone())
+            .with_attached_deposit(ONE_YOCTO)
+            .with_static_gas(GAS_FOR_FT_TRANSFER)
+            .ft_transfer(account_id.clone(), amount.into(), None)
+            .then(
+                ext_self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_AFTER_FT_TRANSFER)
+                    .callback_post_claim_vested(account_id, amount.into()),
+            )
+    }
+
+    #[private]
+    pub fn callback_post_claim_vested(&mut self, account_id: AccountId, amount: WrappedBalance) {
+        assert_eq!(env::promise_results_count(), 1, "Err: expected 1 promise result from claim");
+        self.pending_transfer_amount -= amount.0;
+
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(_) => {
+                events::emit::claim_vested_succeeded(&account_id, amount.0, &self.token_id);
+            }
+            PromiseResult::Failed => {
+                // This reverts the changes from claim_vested function.
+                self.internal_add_vested(&account_id, amount.0);
+                log!("Account {} claim failed and reverted.", account_id);
+
+                events::emit::claim_vested_failed(&account_id, amount.0, &self.token_id);
+            }
+        };
+    }
+}
+
+impl Contract {
+    /// Principal of `shares` out of the account shares, taken from its cost basis, must be called before shares change.
+    pub(crate) fn internal_take_cost_basis(&mut self, account_id: &AccountId, shares: Balance) -> Balance {
+        let mut account = self.internal_get_account(account_id);
+        let account_shares = self.shares.get(account_id).unwrap_or_default();
+        let principal = if shares >= account_shares {
+            account.cost_basis
+        } else {
+            (U256::from(account.cost_basis) * U256::from(shares) / U256::from(account_shares)).as_u128()
+        };
+        account.cost_basis -= principal;
+        self.internal_set_account(account_id, &account);
+        principal
+    }
+
+    pub(crate) fn internal_add_cost_basis(&mut self, account_id: &AccountId, amount: Balance) {
+        let mut account = self.internal_get_account(account_id);
+        account.cost_basis += amount;
+        self.internal_set_account(account_id, &account);
+    }
+
+    /// Start vesting `reward` of the account for reward_vesting_duration
+    pub(crate) fn internal_vest_reward(&mut self, account_id: &AccountId, reward: Balance) {
+        let mut vesting = self.vestings.get(account_id).unwrap_or_default();
+        vesting.add(reward, self.reward_vesting_duration_in_sec, nano_to_sec(env::block_timestamp()));
+        self.internal_set_vesting(account_id, &vesting);
+        self.total_vesting += reward;
+        events::emit::vest_reward(account_id, reward, &self.token_id);
+    }
+
+    /// Make `amount` claimable right away
+    pub(crate) fn internal_add_vested(&mut self, account_id: &AccountId, amount: Balance) {
+        let mut vesting = self.vestings.get(account_id).unwrap_or_default();
+        vesting.vested += amount;
+        self.internal_set_vesting(account_id, &vesting);
+        self.total_vesting += amount;
+    }
+
+    fn internal_set_vesting(&mut self, account_id: &AccountId, vesting: &Vesting) {
+        if vesting.is_empty() {
+            self.vestings.remove(account_id);
+        } else {
+            self.vestings.insert(account_id, vesting);
+        }
+    }
+}
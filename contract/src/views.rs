@@ -1,12 +1,16 @@
 use crate::*;
+#[cfg(not(target_arch = "wasm32"))]
+use near_sdk::serde::Deserialize;
 use near_sdk::serde::Serialize;
+use std::cmp::{max, min};
 
 #[derive(Serialize)]
 #[serde(crate = "near_sdk::serde")]
-#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, Deserialize))]
 pub struct ContractMetadata {
     pub version: String,
     pub owner_id: AccountId,
+    pub reward_manager_id: Option<AccountId>,
     pub token_id: AccountId,
     // at prev_distribution_time, the amount of undistributed reward
     pub undistributed_reward: WrappedBalance,
@@ -20,6 +24,18 @@ pub struct ContractMetadata {
     pub prev_distribution_time_in_sec: u32,
     pub reward_genesis_time_in_sec: u32,
     pub reward_per_sec: WrappedBalance,
+    pub performance_fee_bps: u32,
+    pub treasury_id: Option<AccountId>,
+    // at prev_distribution_time, total performance fee taken
+    pub accrued_performance_fee: WrappedBalance,
+    pub exit_fee_bps: u32,
+    pub exit_fee_duration_in_sec: u32,
+    pub exit_fee_to_treasury: bool,
+    pub min_holding_period_in_sec: u32,
+    pub referral_fee_bps: u32,
+    pub referral_pool: WrappedBalance,
+    // at call time, the amount of reward deferred while nobody was staked
+    pub deferred_reward: WrappedBalance,
     /// current account number in contract
     pub account_number: WrappedBalance,
 }
@@ -28,41 +44,182 @@ pub struct ContractMetadata {
 impl Contract {
     /// Return contract basic info
     pub fn contract_metadata(&self) -> ContractMetadata {
-        let to_be_distributed = self.try_distribute_reward(nano_to_sec(env::block_timestamp()));
+        let to_be_distributed = self.ledger.try_distribute_reward(nano_to_sec(env::block_timestamp()));
         ContractMetadata {
             version: env!("CARGO_PKG_VERSION").to_string(),
             owner_id: self.owner_id.clone(),
+            reward_manager_id: self.reward_manager_id.clone(),
             token_id: self.token_id.clone(),
-            undistributed_reward: self.undistributed_reward.into(),
-            locked_token_amount: self.locked_token_amount.into(),
-            cur_undistributed_reward: (self.undistributed_reward - to_be_distributed).into(),
-            cur_locked_token_amount: (self.locked_token_amount + to_be_distributed).into(),
-            total_staked: self.total_staked.into(),
-            prev_distribution_time_in_sec: self.prev_distribution_time_in_sec,
-            reward_genesis_time_in_sec: self.reward_genesis_time_in_sec,
-            reward_per_sec: self.reward_per_sec.into(),
+            undistributed_reward: self.ledger.undistributed_reward.into(),
+            locked_token_amount: self.ledger.locked_token_amount.into(),
+            cur_undistributed_reward: (self.ledger.undistributed_reward - to_be_distributed).into(),
+            cur_locked_token_amount: (self.ledger.locked_token_amount + to_be_distributed).into(),
+            total_staked: self.ledger.total_staked.into(),
+            prev_distribution_time_in_sec: self.ledger.prev_distribution_time_in_sec,
+            reward_genesis_time_in_sec: self.ledger.reward_genesis_time_in_sec,
+            reward_per_sec: self.ledger.reward_per_sec.into(),
+            performance_fee_bps: self.performance_fee_bps,
+            treasury_id: self.treasury_id.clone(),
+            accrued_performance_fee: self.accrued_performance_fee.into(),
+            exit_fee_bps: self.exit_fee_bps,
+            exit_fee_duration_in_sec: self.exit_fee_duration_in_sec,
+            exit_fee_to_treasury: self.exit_fee_to_treasury,
+            min_holding_period_in_sec: self.min_holding_period_in_sec,
+            referral_fee_bps: self.referral_fee_bps,
+            referral_pool: self.referral_pool.into(),
+            deferred_reward: self.get_deferred_reward(None),
             account_number: (self.shares.len() as u128).into(),
         }
     }
 
-    pub fn get_virtual_price(&self) -> WrappedBalance {
-        if self.total_staked == 0 {
-            100_000_000.into()
+    pub fn get_virtual_price(&self, pool_id: Option<PoolId>) -> WrappedBalance {
+        let cur_time = nano_to_sec(env::block_timestamp());
+        match pool_id {
+            Some(pool_id) => self.internal_get_pool(pool_id).ledger.virtual_price_at(cur_time).into(),
+            None => self.internal_virtual_price_at(cur_time).into(),
+        }
+    }
+
+    /// Projected virtual price at a future timestamp, at the current `reward_per_sec` and total staked
+    pub fn get_virtual_price_at(&self, timestamp_in_sec: u32, pool_id: Option<PoolId>) -> WrappedBalance {
+        assert!(timestamp_in_sec >= nano_to_sec(env::block_timestamp()), "ERR_TIMESTAMP_IN_PAST");
+        match pool_id {
+            Some(pool_id) => self.internal_get_pool(pool_id).ledger.virtual_price_at(timestamp_in_sec).into(),
+            None => self.internal_virtual_price_at(timestamp_in_sec).into(),
+        }
+    }
+
+    /// APR in basis points for the reward streamed at the current `reward_per_sec` after the performance fee,
+    /// 0 if nothing is streaming
+    pub fn get_apr_bps(&self, pool_id: Option<PoolId>) -> WrappedBalance {
+        let cur_time = nano_to_sec(env::block_timestamp());
+        if let Some(pool_id) = pool_id {
+            let pool = self.internal_get_pool(pool_id);
+            let to_be_distributed = pool.ledger.try_distribute_reward(cur_time);
+            return apr_bps(
+                pool.ledger.reward_per_sec,
+                pool.ledger.total_staked,
+                pool.ledger.locked_token_amount + to_be_distributed,
+                pool.ledger.undistributed_reward - to_be_distributed,
+                0,
+            )
+            .into();
+        }
+
+        let to_be_distributed = self.ledger.try_distribute_reward(cur_time);
+        let fee_bps = if self.treasury_id.is_some() {
+            self.performance_fee_bps as u128
         } else {
-            ((self.locked_token_amount + self.try_distribute_reward(nano_to_sec(env::block_timestamp()))) * 100_000_000 / self.total_staked)
-                .into()
+            0
+        };
+        apr_bps(
+            self.ledger.reward_per_sec,
+            self.ledger.total_staked,
+            self.ledger.locked_token_amount + to_be_distributed,
+            self.ledger.undistributed_reward - to_be_distributed,
+            fee_bps,
+        )
+        .into()
+    }
+
+    /// The second when undistributed reward runs out at the current `reward_per_sec`, counted from the reward genesis
+    /// if it's ahead. None if nothing is streaming, including while nobody is staked since the reward is paused then.
+    pub fn get_reward_end_time_in_sec(&self, pool_id: Option<PoolId>) -> Option<u32> {
+        let cur_time = nano_to_sec(env::block_timestamp());
+        match pool_id {
+            Some(pool_id) => ledger_reward_end_time_in_sec(&self.internal_get_pool(pool_id).ledger, cur_time),
+            None => ledger_reward_end_time_in_sec(&self.ledger, cur_time),
         }
     }
 
-    pub fn get_undistributed_reward(&self) -> WrappedBalance {
-        self.undistributed_reward.into()
+    pub fn get_undistributed_reward(&self, pool_id: Option<PoolId>) -> WrappedBalance {
+        match pool_id {
+            Some(pool_id) => self.internal_get_pool(pool_id).ledger.undistributed_reward.into(),
+            None => self.ledger.undistributed_reward.into(),
+        }
     }
 
-    pub fn get_shares(&self, account_id: AccountId) -> WrappedBalance {
-        self.shares.get(&account_id).unwrap_or_default().into()
+    pub fn get_deferred_reward(&self, pool_id: Option<PoolId>) -> WrappedBalance {
+        let cur_time = nano_to_sec(env::block_timestamp());
+        match pool_id {
+            Some(pool_id) => {
+                let pool = self.internal_get_pool(pool_id);
+                (pool.ledger.deferred_reward + pool.ledger.try_defer_reward(cur_time)).into()
+            }
+            None => (self.ledger.deferred_reward + self.ledger.try_defer_reward(cur_time)).into(),
+        }
     }
 
-    pub fn get_total_staked(&self) -> WrappedBalance {
-        self.total_staked.into()
+    pub fn get_shares(&self, account_id: AccountId, pool_id: Option<PoolId>) -> WrappedBalance {
+        match pool_id {
+            Some(pool_id) => self.internal_get_pool(pool_id).shares.get(&account_id).unwrap_or_default().into(),
+            None => self.shares.get(&account_id).unwrap_or_default().into(),
+        }
     }
+
+    /// The time when all shares of the account become unstakable, 0 if none were held.
+    /// Always 0 for added pools, they have no holding period. The unstake cooldown is shown by `get_unstake_cooldown`.
+    pub fn get_unstake_available_time(&self, account_id: AccountId, pool_id: Option<PoolId>) -> u32 {
+        match pool_id {
+            Some(pool_id) => {
+                assert!(self.pools.get(&pool_id).is_some(), "ERR_NO_POOL");
+                0
+            }
+            None => self.internal_unstake_available_time(&account_id),
+        }
+    }
+
+    pub fn get_total_staked(&self, pool_id: Option<PoolId>) -> WrappedBalance {
+        match pool_id {
+            Some(pool_id) => self.internal_get_pool(pool_id).ledger.total_staked.into(),
+            None => self.ledger.total_staked.into(),
+        }
+    }
+}
+
+/// APR in basis points of the reward streamed at `reward_per_sec`, 0 if nothing is streaming
+/// APR in basis points of `reward_per_sec` after `fee_bps`, 0 if nothing is streaming. The admin tool projects it too.
+pub fn apr_bps(
+    reward_per_sec: Balance,
+    total_staked: Balance,
+    cur_locked_token_amount: Balance,
+    cur_undistributed_reward: Balance,
+    fee_bps: u128,
+) -> Balance {
+    if total_staked == 0 || cur_locked_token_amount == 0 || cur_undistributed_reward == 0 {
+        return 0;
+    }
+    (U256::from(reward_per_sec) * U256::from(DURATION_1YEAR_IN_SEC) * U256::from(FULL_BASIS_POINTS - fee_bps)
+        / U256::from(cur_locked_token_amount))
+    .as_u128()
+}
+
+/// The second when `cur_undistributed_reward` runs out streaming from `start_time`, None if nothing is streaming,
+/// including while nobody is staked since the reward is paused then. The admin tool projects it too.
+pub fn reward_end_time_in_sec(
+    reward_per_sec: Balance,
+    total_staked: Balance,
+    cur_undistributed_reward: Balance,
+    start_time: u32,
+) -> Option<u32> {
+    if reward_per_sec == 0 || total_staked == 0 {
+        return None;
+    }
+    // rounded up, the last second may release less than reward_per_sec
+    let duration = ((U256::from(cur_undistributed_reward) + U256::from(reward_per_sec - 1)) / U256::from(reward_per_sec)).as_u128();
+    Some(start_time.saturating_add(min(duration, u32::MAX as u128) as u32))
+}
+
+/// The second when the undistributed reward of `ledger` runs out, streaming from `cur_time` or the reward genesis
+fn ledger_reward_end_time_in_sec(ledger: &Ledger, cur_time: u32) -> Option<u32> {
+    let start_time = max(
+        cur_time,
+        max(ledger.prev_distribution_time_in_sec, ledger.reward_genesis_time_in_sec),
+    );
+    reward_end_time_in_sec(
+        ledger.reward_per_sec,
+        ledger.total_staked,
+        ledger.undistributed_reward - ledger.try_distribute_reward(cur_time),
+        start_time,
+    )
 }
@@ -0,0 +1,243 @@
+//! Deterministic off-chain simulation of the main pool on top of `staking::accounting`.
+//! Random sequences of stake, add reward, time advance and unstake are replayed from a seed,
+//! every step checks the invariants of the share math.
+
+use near_sdk::Balance;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use staking::accounting::{Ledger, INITIAL_VIRTUAL_PRICE, MIN_TOTAL_STAKED};
+
+const ONE_TOKEN: Balance = 10u128.pow(24);
+const NUM_USERS: usize = 5;
+const NUM_SEEDS: u64 = 200;
+const NUM_STEPS: usize = 300;
+
+#[derive(Debug)]
+enum Action {
+    Stake { user: usize, amount: Balance },
+    AddReward { amount: Balance },
+    AdvanceTime { seconds: u32 },
+    Unstake { user: usize, shares: Option<Balance> },
+    RoundTrip { user: usize, amount: Balance },
+}
+
+/// Main pool of the contract: the same order of ledger calls as `internal_stake` and `unstake`.
+/// A failed action leaves the state untouched like a reverted transaction.
+#[derive(Clone, Debug, PartialEq)]
+struct Simulator {
+    ledger: Ledger,
+    shares: Vec<Balance>,
+    // staked token owned by the contract
+    balance: Balance,
+    cur_time_in_sec: u32,
+}
+
+impl Simulator {
+    fn new(reward_per_sec: Balance) -> Self {
+        Self {
+            ledger: Ledger::new(reward_per_sec, 0),
+            shares: vec![0; NUM_USERS],
+            balance: 0,
+            cur_time_in_sec: 0,
+        }
+    }
+
+    fn stake(&mut self, user: usize, amount: Balance) -> Result<Balance, &'static str> {
+        let mut next = self.clone();
+        next.ledger.distribute_reward(next.cur_time_in_sec);
+        let minted = next.ledger.stake(amount);
+        if minted == 0 {
+            return Err("ERR_STAKE_TOO_SMALL");
+        }
+        next.shares[user] += minted;
+        next.balance += amount;
+        *self = next;
+        Ok(minted)
+    }
+
+    fn add_reward(&mut self, amount: Balance) {
+        self.ledger.distribute_reward(self.cur_time_in_sec);
+        self.ledger.undistributed_reward += amount;
+        self.balance += amount;
+    }
+
+    /// burns `shares` or all shares of the user and returns the unlocked token amount
+    fn unstake(&mut self, user: usize, shares: Option<Balance>) -> Result<Balance, &'static str> {
+        let mut next = self.clone();
+        next.ledger.distribute_reward(next.cur_time_in_sec);
+        let shares = shares.unwrap_or(next.shares[user]);
+        if next.ledger.total_staked == 0 {
+            return Err("ERR_EMPTY_TOTAL_SUPPLY");
+        }
+        if shares > next.shares[user] {
+            return Err("The account doesn't have enough balance");
+        }
+        if next.ledger.total_staked - shares < MIN_TOTAL_STAKED {
+            return Err("ERR_KEEP_AT_LEAST_ONE_STAKED_TOKEN");
+        }
+        let (unlocked, _) = next.ledger.unstake(shares, 0);
+        next.shares[user] -= shares;
+        next.balance -= unlocked;
+        *self = next;
+        Ok(unlocked)
+    }
+
+    fn apply(&mut self, action: &Action) {
+        match *action {
+            Action::Stake { user, amount } => {
+                let _ = self.stake(user, amount);
+            }
+            Action::AddReward { amount } => self.add_reward(amount),
+            Action::AdvanceTime { seconds } => self.cur_time_in_sec += seconds,
+            Action::Unstake { user, shares } => {
+                let _ = self.unstake(user, shares);
+            }
+            Action::RoundTrip { user, amount } => {
+                if let Ok(minted) = self.stake(user, amount) {
+                    if let Ok(unlocked) = self.unstake(user, Some(minted)) {
+                        assert!(unlocked <= amount, "round trip of {} returned {}", amount, unlocked);
+                    }
+                }
+            }
+        }
+    }
+
+    fn virtual_price(&self) -> Balance {
+        self.ledger.virtual_price_at(self.cur_time_in_sec)
+    }
+
+    fn assert_invariants(&self) {
+        let ledger = &self.ledger;
+        assert_eq!(
+            self.shares.iter().sum::<Balance>(),
+            ledger.total_staked,
+            "shares don't add up to total_staked"
+        );
+        assert!(
+            self.balance >= ledger.locked_token_amount + ledger.undistributed_reward,
+            "insolvent: {} < {} + {}",
+            self.balance,
+            ledger.locked_token_amount,
+            ledger.undistributed_reward
+        );
+        if ledger.total_staked > 0 {
+            let claims: Balance = self.shares.iter().map(|shares| ledger.tokens_to_unlock(*shares)).sum();
+            assert!(claims <= ledger.locked_token_amount, "claims {} exceed locked token", claims);
+        }
+    }
+}
+
+/// dust and regular amounts, rounding errors show up on the dust ones
+fn random_amount(rng: &mut ChaCha8Rng) -> Balance {
+    match rng.gen_range(0..4) {
+        0 => rng.gen_range(1..1_000),
+        1 => rng.gen_range(1..ONE_TOKEN),
+        _ => rng.gen_range(ONE_TOKEN..1_000_000 * ONE_TOKEN),
+    }
+}
+
+fn random_action(rng: &mut ChaCha8Rng, sim: &Simulator) -> Action {
+    let user = rng.gen_range(0..NUM_USERS);
+    match rng.gen_range(0..10) {
+        0..=2 => Action::Stake {
+            user,
+            amount: random_amount(rng),
+        },
+        3 => Action::AddReward {
+            amount: random_amount(rng),
+        },
+        4..=5 => Action::AdvanceTime {
+            seconds: rng.gen_range(1..100_000),
+        },
+        6..=7 => Action::Unstake {
+            user,
+            shares: match sim.shares[user] {
+                0 => None,
+                shares if rng.gen_bool(0.5) => Some(rng.gen_range(1..=shares)),
+                _ => None,
+            },
+        },
+        _ => Action::RoundTrip {
+            user,
+            amount: random_amount(rng),
+        },
+    }
+}
+
+fn simulate(seed: u64) -> Simulator {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let mut sim = Simulator::new(random_amount(&mut rng));
+    let mut prev_price = sim.virtual_price();
+    for step in 0..NUM_STEPS {
+        let action = random_action(&mut rng, &sim);
+        sim.apply(&action);
+        sim.assert_invariants();
+
+        let price = sim.virtual_price();
+        assert!(
+            price >= prev_price,
+            "seed {} step {}: {:?} dropped virtual price {} -> {}",
+            seed,
+            step,
+            action,
+            prev_price,
+            price
+        );
+        prev_price = price;
+    }
+    sim
+}
+
+#[test]
+fn simulate_random_actions() {
+    for seed in 0..NUM_SEEDS {
+        simulate(seed);
+    }
+}
+
+#[test]
+fn simulation_is_deterministic() {
+    assert_eq!(simulate(7), simulate(7));
+}
+
+#[test]
+fn round_trip_never_profits() {
+    let mut rng = ChaCha8Rng::seed_from_u64(0);
+    for _ in 0..10_000 {
+        let mut sim = Simulator::new(0);
+        sim.stake(0, rng.gen_range(MIN_TOTAL_STAKED..1_000_000 * ONE_TOKEN)).unwrap();
+        // a share price far from 1 makes the rounding visible
+        sim.add_reward(random_amount(&mut rng));
+        sim.ledger.reward_per_sec = sim.ledger.undistributed_reward;
+        sim.cur_time_in_sec = 1;
+
+        let amount = random_amount(&mut rng);
+        let before = sim.clone();
+        if let Ok(minted) = sim.stake(1, amount) {
+            let unlocked = sim.unstake(1, Some(minted)).unwrap();
+            assert!(
+                unlocked <= amount,
+                "{:?}: round trip of {} returned {}",
+                before.ledger,
+                amount,
+                unlocked
+            );
+            assert!(sim.virtual_price() >= before.virtual_price());
+        }
+    }
+}
+
+#[test]
+fn first_stake_mints_at_initial_price() {
+    let mut sim = Simulator::new(ONE_TOKEN);
+    // reward released before the first stake is deferred, not given to the first staker
+    sim.add_reward(10 * ONE_TOKEN);
+    sim.cur_time_in_sec = 5;
+    assert_eq!(sim.stake(0, ONE_TOKEN), Ok(ONE_TOKEN));
+    assert_eq!(sim.ledger.deferred_reward, 5 * ONE_TOKEN);
+    assert_eq!(sim.virtual_price(), INITIAL_VIRTUAL_PRICE);
+
+    sim.cur_time_in_sec = 6;
+    assert_eq!(sim.virtual_price(), 2 * INITIAL_VIRTUAL_PRICE);
+    sim.assert_invariants();
+}
@@ -0,0 +1,188 @@
+use near_gas::NearGas;
+use near_sdk::json_types::U128;
+use near_sdk::serde_json;
+use near_workspaces::network::Sandbox;
+use near_workspaces::{types::NearToken, Account, Contract, Worker};
+
+const CONTRACT_WASM_FILEPATH: &str = "./out/release.wasm";
+const FACTORY_WASM_FILEPATH: &str = "./out/factory_release.wasm";
+const FT_WASM_FILEPATH: &str = "./out/fungible_token.wasm";
+const REWARD_PER_SEC: u128 = 100_000;
+
+async fn init(worker: &Worker<Sandbox>) -> anyhow::Result<(Contract, Contract, Account)> {
+    let owner = worker.dev_create_account().await?;
+
+    let ft_wasm = std::fs::read(FT_WASM_FILEPATH)?;
+    let ft_contract = worker.dev_deploy(&ft_wasm).await?;
+
+    let factory_wasm = std::fs::read(FACTORY_WASM_FILEPATH)?;
+    let factory = worker.dev_deploy(&factory_wasm).await?;
+
+    let _ = factory
+        .call("new")
+        .args_json(serde_json::json!({
+            "owner_id": owner.id().to_string(),
+        }))
+        .gas(NearGas::from_tgas(100))
+        .transact()
+        .await?;
+
+    let contract_wasm = std::fs::read(CONTRACT_WASM_FILEPATH)?;
+    let store_code = owner
+        .call(factory.id(), "store_code")
+        .args(contract_wasm)
+        .deposit(NearToken::from_near(10))
+        .max_gas()
+        .transact()
+        .await?;
+    assert!(store_code.is_success(), "ERR CODE WAS NOT STORED");
+
+    Ok((factory, ft_contract, owner))
+}
+
+/// The pool deposit and 1 NEAR for the registry entry
+async fn create_pool_deposit(factory: &Contract) -> anyhow::Result<NearToken> {
+    let min_pool_deposit: U128 = factory.view("get_min_pool_deposit").await?.json()?;
+    Ok(NearToken::from_yoctonear(
+        min_pool_deposit.0 + NearToken::from_near(1).as_yoctonear(),
+    ))
+}
+
+#[tokio::test]
+async fn verify_create_pool() -> anyhow::Result<()> {
+    let worker: Worker<Sandbox> = near_workspaces::sandbox().await?;
+    let (factory, ft_contract, owner) = init(&worker).await?;
+    let deposit = create_pool_deposit(&factory).await?;
+    let pool_owner = worker.dev_create_account().await?;
+
+    let create_pool_without_deposit = owner
+        .call(factory.id(), "create_pool")
+        .args_json(serde_json::json!({
+            "prefix": "token",
+            "token_id": ft_contract.id().to_string(),
+            "owner_id": pool_owner.id().to_string(),
+            "name": "Token staking",
+        }))
+        .max_gas()
+        .transact()
+        .await?;
+    assert!(!create_pool_without_deposit.is_success(), "ERR POOL CREATED WITHOUT DEPOSIT");
+
+    // the pool deposit is derived from the stored code
+    let min_pool_deposit: U128 = factory.view("get_min_pool_deposit").await?.json()?;
+    let contract_wasm = std::fs::read(CONTRACT_WASM_FILEPATH)?;
+    assert!(min_pool_deposit.0 > contract_wasm.len() as u128 * 10u128.pow(19));
+    let create_pool_with_small_deposit = owner
+        .call(factory.id(), "create_pool")
+        .args_json(serde_json::json!({
+            "prefix": "token",
+            "token_id": ft_contract.id().to_string(),
+            "owner_id": pool_owner.id().to_string(),
+            "name": "Token staking",
+        }))
+        .deposit(NearToken::from_yoctonear(min_pool_deposit.0))
+        .max_gas()
+        .transact()
+        .await?;
+    assert!(!create_pool_with_small_deposit.is_success(), "ERR REGISTRY ENTRY NOT PAID");
+
+    let create_pool = owner
+        .call(factory.id(), "create_pool")
+        .args_json(serde_json::json!({
+            "prefix": "token",
+            "token_id": ft_contract.id().to_string(),
+            "owner_id": pool_owner.id().to_string(),
+            "name": "Token staking",
+            "icon": "data:image/svg+xml,<svg></svg>",
+            "reward_per_sec": U128::from(REWARD_PER_SEC),
+        }))
+        .deposit(deposit)
+        .max_gas()
+        .transact()
+        .await?;
+    println!("create_pool: {:#?}", create_pool.logs());
+    assert!(create_pool.json::<bool>()?, "ERR POOL WAS NOT CREATED");
+
+    let pools: Vec<serde_json::Value> = factory.call("get_pools").args_json(serde_json::json!({})).view().await?.json()?;
+    assert_eq!(pools.len(), 1);
+    assert_eq!(pools[0]["token_id"], ft_contract.id().to_string());
+    assert_eq!(pools[0]["code_version"], 0);
+    let pool_id: near_workspaces::AccountId = pools[0]["pool_id"].as_str().unwrap().parse()?;
+
+    let pool_owner_id: serde_json::Value = worker.view(&pool_id, "get_owner").await?.json()?;
+    assert_eq!(pool_owner_id, pool_owner.id().to_string());
+
+    let reward_per_sec: U128 = worker
+        .view(&pool_id, "get_reward_per_sec")
+        .args_json(serde_json::json!({}))
+        .await?
+        .json()?;
+    assert_eq!(reward_per_sec.0, REWARD_PER_SEC);
+
+    // the same prefix can't be used twice
+    let create_pool_2 = owner
+        .call(factory.id(), "create_pool")
+        .args_json(serde_json::json!({
+            "prefix": "token",
+            "token_id": ft_contract.id().to_string(),
+            "owner_id": pool_owner.id().to_string(),
+            "name": "Token staking",
+        }))
+        .deposit(deposit)
+        .max_gas()
+        .transact()
+        .await?;
+    assert!(!create_pool_2.is_success(), "ERR POOL CREATED TWICE");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn verify_upgrade_pools() -> anyhow::Result<()> {
+    let worker: Worker<Sandbox> = near_workspaces::sandbox().await?;
+    let (factory, ft_contract, owner) = init(&worker).await?;
+    let deposit = create_pool_deposit(&factory).await?;
+
+    let _ = owner
+        .call(factory.id(), "create_pool")
+        .args_json(serde_json::json!({
+            "prefix": "token",
+            "token_id": ft_contract.id().to_string(),
+            "owner_id": owner.id().to_string(),
+            "name": "Token staking",
+        }))
+        .deposit(deposit)
+        .max_gas()
+        .transact()
+        .await?;
+
+    // store a new code version
+    let contract_wasm = std::fs::read(CONTRACT_WASM_FILEPATH)?;
+    let store_code = owner
+        .call(factory.id(), "store_code")
+        .args(contract_wasm)
+        .deposit(NearToken::from_near(10))
+        .max_gas()
+        .transact()
+        .await?;
+    assert_eq!(store_code.json::<u32>()?, 1);
+
+    let upgrade_pools = owner
+        .call(factory.id(), "upgrade_pools")
+        .args_json(serde_json::json!({}))
+        .max_gas()
+        .transact()
+        .await?;
+    println!("upgrade_pools: {:#?}", upgrade_pools.logs());
+    assert!(upgrade_pools.is_success());
+
+    let pools: Vec<serde_json::Value> = factory.call("get_pools").args_json(serde_json::json!({})).view().await?.json()?;
+    assert_eq!(pools[0]["code_version"], 1, "ERR POOL WAS NOT UPGRADED");
+
+    let pool_id: near_workspaces::AccountId = pools[0]["pool_id"].as_str().unwrap().parse()?;
+    let pool_token_id: serde_json::Value =
+        worker.view(&pool_id, "contract_metadata").await?.json::<serde_json::Value>()?["token_id"].clone();
+    assert_eq!(pool_token_id, ft_contract.id().to_string(), "ERR POOL STATE WAS NOT MIGRATED");
+
+    Ok(())
+}
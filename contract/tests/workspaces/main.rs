@@ -10,7 +10,11 @@ use near_sdk::serde_json;
 // https://github.com/near/near-sdk-rs/blob/master/examples/fungible-token/tests/workspaces.rs
 
 const CONTRACT_WASM_FILEPATH: &str = "./out/release.wasm";
+// first upgradable release, its state is converted by migrate
+const CONTRACT_V1_WASM_FILEPATH: &str = "./out/release_v1.wasm";
 const FT_WASM_FILEPATH: &str = "./out/fungible_token.wasm";
+const MOCK_DEX_WASM_FILEPATH: &str = "./out/mock_dex.wasm";
+const MOCK_LENDING_WASM_FILEPATH: &str = "./out/mock_lending.wasm";
 const REWARD_PER_SEC: Balance = 100_000;
 const REWARD_PER_SEC_2: Balance = 2 * REWARD_PER_SEC;
 
@@ -126,7 +130,12 @@ async fn verify_get_set_reward_per_sec() -> anyhow::Result<()> {
     let worker: Worker<Sandbox> = near_workspaces::sandbox().await?;
     let (contract, _, owner, anon) = init(&worker).await?;
 
-    let reward_per_nanosec: serde_json::Value = contract.call("get_reward_per_sec").view().await?.json()?;
+    let reward_per_nanosec: serde_json::Value = contract
+        .call("get_reward_per_sec")
+        .args_json(serde_json::json!({}))
+        .view()
+        .await?
+        .json()?;
     assert_ne!(reward_per_nanosec, REWARD_PER_SEC.to_string());
 
     let set_reward_per_sec_outcome_by_anon = anon
@@ -217,7 +226,12 @@ async fn verify_add_rewards() -> anyhow::Result<()> {
 
     assert!(ft_transfer_outcome_add_rewards.is_success());
 
-    let get_undistributed_reward_after_100_tokens: U128 = contract.call("get_undistributed_reward").view().await?.json()?;
+    let get_undistributed_reward_after_100_tokens: U128 = contract
+        .call("get_undistributed_reward")
+        .args_json(serde_json::json!({}))
+        .view()
+        .await?
+        .json()?;
 
     println!(
         "get_undistributed_reward_after_100_tokens: {}",
@@ -255,7 +269,12 @@ async fn verify_add_rewards() -> anyhow::Result<()> {
         .transact()
         .await?;
 
-    let get_undistributed_reward_after_300_tokens: U128 = contract.call("get_undistributed_reward").view().await?.json()?;
+    let get_undistributed_reward_after_300_tokens: U128 = contract
+        .call("get_undistributed_reward")
+        .args_json(serde_json::json!({}))
+        .view()
+        .await?
+        .json()?;
 
     println!(
         "get_undistributed_reward_after 300 + 100 tokens: {}",
@@ -415,7 +434,12 @@ async fn verify_add_deposits() -> anyhow::Result<()> {
         .await?;
     println!("alice_stake: {:#?}", alice_stake.logs());
 
-    let total_staked: U128 = contract.call("get_total_staked").view().await?.json()?;
+    let total_staked: U128 = contract
+        .call("get_total_staked")
+        .args_json(serde_json::json!({}))
+        .view()
+        .await?
+        .json()?;
 
     println!("total_staked: {:#?}", total_staked);
 
@@ -690,3 +714,3134 @@ async fn verify_add_deposits() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn verify_reward_deferred_without_stakers() -> anyhow::Result<()> {
+    let worker: Worker<Sandbox> = near_workspaces::sandbox().await?;
+    let (contract, ft_contract, owner, _anon) = init(&worker).await?;
+
+    let rewards_100_tokens = U128::from(NearToken::from_near(100).as_yoctonear());
+
+    // add 100 tokens rewards
+    let _ = owner
+        .call(ft_contract.id(), "ft_transfer_call")
+        .args_json(serde_json::json!({
+            "receiver_id": contract.id().to_string(),
+            "amount": rewards_100_tokens,
+            "msg": "\"AddRewards\""
+        }))
+        .max_gas()
+        .deposit(ONE_YOCTO)
+        .transact()
+        .await?;
+
+    let _ = owner
+        .call(contract.id(), "modify_reward_per_sec")
+        .args_json(serde_json::json!({
+            "reward_per_sec": U128::from(REWARD_PER_SEC),
+            "distribute_before_change": false,
+        }))
+        .transact()
+        .await?;
+
+    let (timestamp, _epoch_height): (u64, u64) = contract.call("current_env_data").view().await?.json()?;
+
+    let _ = owner
+        .call(contract.id(), "reset_reward_genesis_time_in_sec")
+        .args_json(serde_json::json!({
+            "reward_genesis_time_in_sec": timestamp / NANOSEC_IN_SEC + 1,
+        }))
+        .transact()
+        .await?;
+
+    worker.fast_forward(100).await?;
+
+    let deferred_reward: U128 = contract
+        .call("get_deferred_reward")
+        .args_json(serde_json::json!({}))
+        .view()
+        .await?
+        .json()?;
+    assert!(deferred_reward.0 > 0, "ERR REWARD WAS NOT DEFERRED");
+
+    // first staker gets shares at the initial price, nothing was distributed to an empty pool
+    let amount_10_tokens = U128::from(NearToken::from_near(10).as_yoctonear());
+    let _ = owner
+        .call(ft_contract.id(), "ft_transfer_call")
+        .args_json(serde_json::json!({
+            "receiver_id": contract.id().to_string(),
+            "amount": amount_10_tokens,
+            "msg": "\"Stake\""
+        }))
+        .max_gas()
+        .deposit(ONE_YOCTO)
+        .transact()
+        .await?;
+
+    let owner_get_shares: U128 = contract
+        .call("get_shares")
+        .args_json(serde_json::json!({
+            "account_id": owner.id().to_string(),
+        }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(owner_get_shares.0, amount_10_tokens.0, "ERR ILLEGAL STAKING BALANCE");
+
+    let undistributed_reward: U128 = contract
+        .call("get_undistributed_reward")
+        .args_json(serde_json::json!({}))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(
+        undistributed_reward.0, rewards_100_tokens.0,
+        "ERR REWARD DISTRIBUTED WHILE NOBODY WAS STAKED"
+    );
+
+    let deferred_reward_after_stake: U128 = contract
+        .call("get_deferred_reward")
+        .args_json(serde_json::json!({}))
+        .view()
+        .await?
+        .json()?;
+    assert!(deferred_reward_after_stake.0 >= deferred_reward.0, "ERR DEFERRED REWARD DECREASED");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn verify_recover_tokens() -> anyhow::Result<()> {
+    let worker: Worker<Sandbox> = near_workspaces::sandbox().await?;
+    let (contract, ft_contract, owner, anon) = init(&worker).await?;
+
+    // another token sent to the contract with a plain ft_transfer
+    let ft_wasm = std::fs::read(FT_WASM_FILEPATH)?;
+    let other_ft_contract = worker.dev_deploy(&ft_wasm).await?;
+    let _ = other_ft_contract
+        .call("new_default_meta")
+        .args_json(serde_json::json!({
+            "owner_id": owner.id().to_string(),
+            "total_supply": U128::from(TOKEN_TOTAL_SUPPLY),
+        }))
+        .gas(NearGas::from_tgas(100))
+        .transact()
+        .await?;
+
+    for account_id in [contract.id(), anon.id()] {
+        let _ = owner
+            .call(other_ft_contract.id(), "storage_deposit")
+            .args_json(serde_json::json!({
+                "account_id": account_id.to_string(),
+            }))
+            .gas(NearGas::from_tgas(100))
+            .deposit(NearToken::from_yoctonear(1250000000000000000000))
+            .transact()
+            .await?;
+    }
+
+    let amount_10_tokens = U128::from(NearToken::from_near(10).as_yoctonear());
+    let _ = owner
+        .call(other_ft_contract.id(), "ft_transfer")
+        .args_json(serde_json::json!({
+            "receiver_id": contract.id().to_string(),
+            "amount": amount_10_tokens,
+        }))
+        .max_gas()
+        .deposit(ONE_YOCTO)
+        .transact()
+        .await?;
+
+    // staked token can't be recovered this way
+    let recover_staked_token = owner
+        .call(contract.id(), "recover_tokens")
+        .args_json(serde_json::json!({
+            "token_id": ft_contract.id().to_string(),
+            "amount": amount_10_tokens,
+            "receiver_id": anon.id().to_string(),
+        }))
+        .deposit(ONE_YOCTO)
+        .max_gas()
+        .transact()
+        .await?;
+    assert!(!recover_staked_token.is_success(), "ERR STAKED TOKEN RECOVERED");
+
+    let recover_by_anon = anon
+        .call(contract.id(), "recover_tokens")
+        .args_json(serde_json::json!({
+            "token_id": other_ft_contract.id().to_string(),
+            "amount": amount_10_tokens,
+            "receiver_id": anon.id().to_string(),
+        }))
+        .deposit(ONE_YOCTO)
+        .max_gas()
+        .transact()
+        .await?;
+    assert!(!recover_by_anon.is_success(), "ANON DOESN'T TRIGGER A FAILURE");
+
+    let recover_by_owner = owner
+        .call(contract.id(), "recover_tokens")
+        .args_json(serde_json::json!({
+            "token_id": other_ft_contract.id().to_string(),
+            "amount": amount_10_tokens,
+            "receiver_id": anon.id().to_string(),
+        }))
+        .deposit(ONE_YOCTO)
+        .max_gas()
+        .transact()
+        .await?;
+    println!("recover_by_owner: {:#?}", recover_by_owner.logs());
+    assert!(recover_by_owner.is_success());
+
+    let anon_ft_balance: U128 = other_ft_contract
+        .call("ft_balance_of")
+        .args_json(serde_json::json!({
+            "account_id": anon.id().to_string(),
+        }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(anon_ft_balance.0, amount_10_tokens.0, "ERR TOKENS WERE NOT RECOVERED");
+
+    // staked token sent with a plain ft_transfer is a surplus
+    let _ = owner
+        .call(ft_contract.id(), "ft_transfer_call")
+        .args_json(serde_json::json!({
+            "receiver_id": contract.id().to_string(),
+            "amount": amount_10_tokens,
+            "msg": "\"Stake\""
+        }))
+        .max_gas()
+        .deposit(ONE_YOCTO)
+        .transact()
+        .await?;
+
+    let amount_3_tokens = U128::from(NearToken::from_near(3).as_yoctonear());
+    let _ = owner
+        .call(ft_contract.id(), "ft_transfer")
+        .args_json(serde_json::json!({
+            "receiver_id": contract.id().to_string(),
+            "amount": amount_3_tokens,
+        }))
+        .max_gas()
+        .deposit(ONE_YOCTO)
+        .transact()
+        .await?;
+
+    let report: serde_json::Value = owner
+        .call(contract.id(), "token_balance_report")
+        .max_gas()
+        .transact()
+        .await?
+        .json()?;
+    println!("report: {:#?}", report);
+    assert_eq!(report["surplus"], amount_3_tokens.0.to_string());
+    // a margin of 1% of the accounted balance is not swept
+    let accounted_balance: u128 = report["accounted_balance"].as_str().unwrap().parse()?;
+    let sweepable = amount_3_tokens.0 - accounted_balance / 100;
+    assert_eq!(report["sweepable"], sweepable.to_string());
+
+    let sweep_surplus = owner
+        .call(contract.id(), "sweep_surplus")
+        .args_json(serde_json::json!({
+            "receiver_id": owner.id().to_string(),
+        }))
+        .deposit(ONE_YOCTO)
+        .max_gas()
+        .transact()
+        .await?;
+    println!("sweep_surplus: {:#?}", sweep_surplus.logs());
+    assert!(sweep_surplus.is_success());
+
+    let owner_ft_balance: U128 = ft_contract
+        .call("ft_balance_of")
+        .args_json(serde_json::json!({
+            "account_id": owner.id().to_string(),
+        }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(
+        owner_ft_balance.0,
+        TOKEN_TOTAL_SUPPLY - amount_10_tokens.0 - amount_3_tokens.0 + sweepable,
+        "ERR SURPLUS WAS NOT SWEPT"
+    );
+
+    // only the margin is left
+    let sweep_surplus_2 = owner
+        .call(contract.id(), "sweep_surplus")
+        .args_json(serde_json::json!({
+            "receiver_id": owner.id().to_string(),
+        }))
+        .deposit(ONE_YOCTO)
+        .max_gas()
+        .transact()
+        .await?;
+    assert!(!sweep_surplus_2.is_success(), "ERR EMPTY SURPLUS SWEPT");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn verify_withdraw_reward() -> anyhow::Result<()> {
+    let worker: Worker<Sandbox> = near_workspaces::sandbox().await?;
+    let (contract, ft_contract, owner, anon) = init(&worker).await?;
+
+    let rewards_100_tokens = U128::from(NearToken::from_near(100).as_yoctonear());
+    let rewards_40_tokens = U128::from(NearToken::from_near(40).as_yoctonear());
+
+    let _ = owner
+        .call(ft_contract.id(), "ft_transfer_call")
+        .args_json(serde_json::json!({
+            "receiver_id": contract.id().to_string(),
+            "amount": rewards_100_tokens,
+            "msg": "\"AddRewards\""
+        }))
+        .max_gas()
+        .deposit(ONE_YOCTO)
+        .transact()
+        .await?;
+
+    let withdraw_by_anon = anon
+        .call(contract.id(), "withdraw_reward")
+        .args_json(serde_json::json!({
+            "amount": rewards_40_tokens,
+        }))
+        .deposit(ONE_YOCTO)
+        .max_gas()
+        .transact()
+        .await?;
+    assert!(!withdraw_by_anon.is_success(), "ANON DOESN'T TRIGGER A FAILURE");
+
+    // anon becomes a reward manager
+    let _ = owner
+        .call(contract.id(), "set_reward_manager")
+        .args_json(serde_json::json!({
+            "reward_manager_id": anon.id().to_string(),
+        }))
+        .transact()
+        .await?;
+
+    let withdraw_too_much = anon
+        .call(contract.id(), "withdraw_reward")
+        .args_json(serde_json::json!({
+            "amount": U128::from(rewards_100_tokens.0 + 1),
+            "receiver_id": owner.id().to_string(),
+        }))
+        .deposit(ONE_YOCTO)
+        .max_gas()
+        .transact()
+        .await?;
+    assert!(!withdraw_too_much.is_success(), "ERR WITHDRAWN MORE THAN UNDISTRIBUTED REWARD");
+
+    let withdraw_by_manager = anon
+        .call(contract.id(), "withdraw_reward")
+        .args_json(serde_json::json!({
+            "amount": rewards_40_tokens,
+            "receiver_id": owner.id().to_string(),
+        }))
+        .deposit(ONE_YOCTO)
+        .max_gas()
+        .transact()
+        .await?;
+    println!("withdraw_by_manager: {:#?}", withdraw_by_manager.logs());
+    assert!(withdraw_by_manager.is_success());
+
+    let undistributed_reward: U128 = contract
+        .call("get_undistributed_reward")
+        .args_json(serde_json::json!({}))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(undistributed_reward.0, rewards_100_tokens.0 - rewards_40_tokens.0);
+
+    let owner_ft_balance: U128 = ft_contract
+        .call("ft_balance_of")
+        .args_json(serde_json::json!({
+            "account_id": owner.id().to_string(),
+        }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(owner_ft_balance.0, TOKEN_TOTAL_SUPPLY - undistributed_reward.0);
+
+    // anon is not registered in the token, transfer fails and reward is refunded
+    let withdraw_to_unregistered = owner
+        .call(contract.id(), "withdraw_reward")
+        .args_json(serde_json::json!({
+            "amount": rewards_40_tokens,
+            "receiver_id": anon.id().to_string(),
+        }))
+        .deposit(ONE_YOCTO)
+        .max_gas()
+        .transact()
+        .await?;
+    println!("withdraw_to_unregistered: {:#?}", withdraw_to_unregistered.logs());
+
+    let undistributed_reward_2: U128 = contract
+        .call("get_undistributed_reward")
+        .args_json(serde_json::json!({}))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(
+        undistributed_reward_2.0, undistributed_reward.0,
+        "ERR FAILED WITHDRAW WAS NOT REFUNDED"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn verify_reward_runway_views() -> anyhow::Result<()> {
+    let worker: Worker<Sandbox> = near_workspaces::sandbox().await?;
+    let (contract, ft_contract, owner, _anon) = init(&worker).await?;
+
+    let reward_end_time: Option<u32> = contract
+        .call("get_reward_end_time_in_sec")
+        .args_json(serde_json::json!({}))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(reward_end_time, None, "ERR RUNWAY WITHOUT REWARD_PER_SEC");
+
+    let amount_100_tokens = U128::from(NearToken::from_near(100).as_yoctonear());
+    let transfer_call = |msg: &'static str| {
+        owner
+            .call(ft_contract.id(), "ft_transfer_call")
+            .args_json(serde_json::json!({
+                "receiver_id": contract.id().to_string(),
+                "amount": amount_100_tokens,
+                "msg": msg
+            }))
+            .max_gas()
+            .deposit(ONE_YOCTO)
+            .transact()
+    };
+    let _ = transfer_call("\"AddRewards\"").await?;
+
+    let _ = owner
+        .call(contract.id(), "modify_reward_per_sec")
+        .args_json(serde_json::json!({
+            "reward_per_sec": U128::from(REWARD_PER_SEC),
+            "distribute_before_change": false,
+        }))
+        .transact()
+        .await?;
+
+    // the reward is paused while nobody is staked
+    let reward_end_time: Option<u32> = contract
+        .call("get_reward_end_time_in_sec")
+        .args_json(serde_json::json!({}))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(reward_end_time, None, "ERR RUNWAY WITHOUT STAKERS");
+    let _ = transfer_call("\"Stake\"").await?;
+
+    let (timestamp, _epoch_height): (u64, u64) = contract.call("current_env_data").view().await?.json()?;
+    let reward_genesis_time_in_sec = (timestamp / NANOSEC_IN_SEC + 10) as u32;
+
+    let _ = owner
+        .call(contract.id(), "reset_reward_genesis_time_in_sec")
+        .args_json(serde_json::json!({
+            "reward_genesis_time_in_sec": reward_genesis_time_in_sec,
+        }))
+        .transact()
+        .await?;
+
+    let reward_end_time: Option<u32> = contract
+        .call("get_reward_end_time_in_sec")
+        .args_json(serde_json::json!({}))
+        .view()
+        .await?
+        .json()?;
+    let expected_duration = (amount_100_tokens.0 / REWARD_PER_SEC) as u32;
+    assert_eq!(reward_end_time, Some(reward_genesis_time_in_sec + expected_duration));
+
+    let apr_bps: U128 = contract.call("get_apr_bps").args_json(serde_json::json!({})).view().await?.json()?;
+    let expected_apr_bps = REWARD_PER_SEC * 60 * 60 * 24 * 365 * 10_000 / amount_100_tokens.0;
+    assert_eq!(apr_bps.0, expected_apr_bps);
+
+    // all reward is distributed to the only staker at the end of the runway
+    let virtual_price_at_end: U128 = contract
+        .call("get_virtual_price_at")
+        .args_json(serde_json::json!({
+            "timestamp_in_sec": reward_end_time.unwrap() + 1000,
+        }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(virtual_price_at_end.0, 2 * 100_000_000);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn verify_price_history() -> anyhow::Result<()> {
+    let worker: Worker<Sandbox> = near_workspaces::sandbox().await?;
+    let (contract, ft_contract, owner, _anon) = init(&worker).await?;
+
+    let _ = owner
+        .call(contract.id(), "set_price_snapshot_interval")
+        .args_json(serde_json::json!({
+            "price_snapshot_interval_in_sec": 1,
+        }))
+        .transact()
+        .await?;
+
+    let amount_100_tokens = U128::from(NearToken::from_near(100).as_yoctonear());
+    for msg in ["\"AddRewards\"", "\"Stake\""] {
+        let _ = owner
+            .call(ft_contract.id(), "ft_transfer_call")
+            .args_json(serde_json::json!({
+                "receiver_id": contract.id().to_string(),
+                "amount": amount_100_tokens,
+                "msg": msg
+            }))
+            .max_gas()
+            .deposit(ONE_YOCTO)
+            .transact()
+            .await?;
+    }
+
+    let _ = owner
+        .call(contract.id(), "modify_reward_per_sec")
+        .args_json(serde_json::json!({
+            "reward_per_sec": U128::from(REWARD_PER_SEC),
+            "distribute_before_change": false,
+        }))
+        .transact()
+        .await?;
+
+    let (timestamp, _epoch_height): (u64, u64) = contract.call("current_env_data").view().await?.json()?;
+    let _ = owner
+        .call(contract.id(), "reset_reward_genesis_time_in_sec")
+        .args_json(serde_json::json!({
+            "reward_genesis_time_in_sec": timestamp / NANOSEC_IN_SEC + 1,
+        }))
+        .transact()
+        .await?;
+
+    worker.fast_forward(100).await?;
+
+    // checkpoint with a stake to record one more snapshot
+    let _ = owner
+        .call(ft_contract.id(), "ft_transfer_call")
+        .args_json(serde_json::json!({
+            "receiver_id": contract.id().to_string(),
+            "amount": amount_100_tokens,
+            "msg": "\"Stake\""
+        }))
+        .max_gas()
+        .deposit(ONE_YOCTO)
+        .transact()
+        .await?;
+
+    let price_history: Vec<serde_json::Value> = contract
+        .call("get_price_history")
+        .args_json(serde_json::json!({}))
+        .view()
+        .await?
+        .json()?;
+    println!("price_history: {:#?}", price_history);
+    assert!(price_history.len() >= 2, "ERR ILLEGAL PRICE HISTORY LENGTH");
+
+    let first_price: u128 = price_history[0]["virtual_price"].as_str().unwrap().parse()?;
+    let last_price: u128 = price_history[price_history.len() - 1]["virtual_price"].as_str().unwrap().parse()?;
+    assert!(last_price > first_price, "ERR VIRTUAL PRICE DIDN'T GROW");
+
+    let limited_price_history: Vec<serde_json::Value> = contract
+        .call("get_price_history")
+        .args_json(serde_json::json!({
+            "from_index": 1,
+            "limit": 10,
+        }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(limited_price_history, price_history[1..].to_vec());
+
+    let twap: U128 = contract
+        .call("get_time_weighted_average_price")
+        .args_json(serde_json::json!({
+            "from_timestamp_in_sec": 0,
+        }))
+        .view()
+        .await?
+        .json()?;
+    let cur_price: U128 = contract
+        .call("get_virtual_price")
+        .args_json(serde_json::json!({}))
+        .view()
+        .await?
+        .json()?;
+    assert!(twap.0 >= first_price && twap.0 <= cur_price.0, "ERR ILLEGAL TWAP");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn verify_performance_fee() -> anyhow::Result<()> {
+    let worker: Worker<Sandbox> = near_workspaces::sandbox().await?;
+    let (contract, ft_contract, owner, anon) = init(&worker).await?;
+
+    let set_too_high_fee = owner
+        .call(contract.id(), "set_performance_fee")
+        .args_json(serde_json::json!({
+            "performance_fee_bps": 2001,
+        }))
+        .transact()
+        .await?;
+    assert!(!set_too_high_fee.is_success(), "ERR FEE ABOVE MAXIMUM WAS SET");
+
+    let set_fee_by_anon = anon
+        .call(contract.id(), "set_performance_fee")
+        .args_json(serde_json::json!({
+            "performance_fee_bps": 1000,
+        }))
+        .transact()
+        .await?;
+    assert!(!set_fee_by_anon.is_success(), "ANON DOESN'T TRIGGER A FAILURE");
+
+    let _ = owner
+        .call(contract.id(), "set_performance_fee")
+        .args_json(serde_json::json!({
+            "performance_fee_bps": 1000,
+        }))
+        .transact()
+        .await?;
+
+    let _ = owner
+        .call(contract.id(), "set_treasury")
+        .args_json(serde_json::json!({
+            "treasury_id": anon.id().to_string(),
+        }))
+        .transact()
+        .await?;
+
+    let amount_100_tokens = U128::from(NearToken::from_near(100).as_yoctonear());
+    for msg in ["\"AddRewards\"", "\"Stake\""] {
+        let _ = owner
+            .call(ft_contract.id(), "ft_transfer_call")
+            .args_json(serde_json::json!({
+                "receiver_id": contract.id().to_string(),
+                "amount": amount_100_tokens,
+                "msg": msg
+            }))
+            .max_gas()
+            .deposit(ONE_YOCTO)
+            .transact()
+            .await?;
+    }
+
+    let _ = owner
+        .call(contract.id(), "modify_reward_per_sec")
+        .args_json(serde_json::json!({
+            "reward_per_sec": U128::from(REWARD_PER_SEC),
+            "distribute_before_change": false,
+        }))
+        .transact()
+        .await?;
+
+    let (timestamp, _epoch_height): (u64, u64) = contract.call("current_env_data").view().await?.json()?;
+    let _ = owner
+        .call(contract.id(), "reset_reward_genesis_time_in_sec")
+        .args_json(serde_json::json!({
+            "reward_genesis_time_in_sec": timestamp / NANOSEC_IN_SEC + 1,
+        }))
+        .transact()
+        .await?;
+
+    worker.fast_forward(100).await?;
+
+    // checkpoint mints the fee shares
+    let _ = owner
+        .call(contract.id(), "set_performance_fee")
+        .args_json(serde_json::json!({
+            "performance_fee_bps": 1000,
+        }))
+        .transact()
+        .await?;
+
+    let accrued_fee: U128 = contract.call("get_accrued_performance_fee").view().await?.json()?;
+    let undistributed_reward: U128 = contract
+        .call("get_undistributed_reward")
+        .args_json(serde_json::json!({}))
+        .view()
+        .await?
+        .json()?;
+    let distributed_reward = amount_100_tokens.0 - undistributed_reward.0;
+    assert_eq!(accrued_fee.0, distributed_reward / 10, "ERR ILLEGAL PERFORMANCE FEE");
+
+    let treasury_shares: U128 = contract
+        .call("get_shares")
+        .args_json(serde_json::json!({
+            "account_id": anon.id().to_string(),
+        }))
+        .view()
+        .await?
+        .json()?;
+    let virtual_price: U128 = contract
+        .call("get_virtual_price")
+        .args_json(serde_json::json!({}))
+        .view()
+        .await?
+        .json()?;
+    let treasury_tokens = treasury_shares.0 * virtual_price.0 / 100_000_000;
+    assert!(
+        u128_diff(treasury_tokens, accrued_fee.0) <= 10 * REWARD_PER_SEC,
+        "ERR ILLEGAL TREASURY SHARES"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn verify_exit_fee() -> anyhow::Result<()> {
+    let worker: Worker<Sandbox> = near_workspaces::sandbox().await?;
+    let (contract, ft_contract, owner, anon) = init(&worker).await?;
+
+    let set_too_high_fee = owner
+        .call(contract.id(), "set_exit_fee")
+        .args_json(serde_json::json!({
+            "exit_fee_bps": 1001,
+            "exit_fee_duration_in_sec": 60 * 60 * 24,
+            "exit_fee_to_treasury": false,
+        }))
+        .transact()
+        .await?;
+    assert!(!set_too_high_fee.is_success(), "ERR FEE ABOVE MAXIMUM WAS SET");
+
+    // 10% exit fee decaying to 0 in a day
+    let _ = owner
+        .call(contract.id(), "set_exit_fee")
+        .args_json(serde_json::json!({
+            "exit_fee_bps": 1000,
+            "exit_fee_duration_in_sec": 60 * 60 * 24,
+            "exit_fee_to_treasury": false,
+        }))
+        .transact()
+        .await?;
+
+    // anon to register in the token and receive 100 tokens
+    let amount_100_tokens = U128::from(NearToken::from_near(100).as_yoctonear());
+    let _ = anon
+        .call(ft_contract.id(), "storage_deposit")
+        .args_json(serde_json::json!({
+            "account_id": anon.id().to_string(),
+        }))
+        .gas(NearGas::from_tgas(100))
+        .deposit(NearToken::from_yoctonear(1250000000000000000000))
+        .transact()
+        .await?;
+    let _ = owner
+        .call(ft_contract.id(), "ft_transfer")
+        .args_json(serde_json::json!({
+            "receiver_id": anon.id().to_string(),
+            "amount": amount_100_tokens,
+        }))
+        .max_gas()
+        .deposit(ONE_YOCTO)
+        .transact()
+        .await?;
+
+    for account in [&owner, &anon] {
+        let _ = account
+            .call(ft_contract.id(), "ft_transfer_call")
+            .args_json(serde_json::json!({
+                "receiver_id": contract.id().to_string(),
+                "amount": amount_100_tokens,
+                "msg": "\"Stake\""
+            }))
+            .max_gas()
+            .deposit(ONE_YOCTO)
+            .transact()
+            .await?;
+    }
+
+    let exit_fee: serde_json::Value = contract
+        .call("get_exit_fee")
+        .args_json(serde_json::json!({
+            "account_id": anon.id().to_string(),
+        }))
+        .view()
+        .await?
+        .json()?;
+    println!("exit_fee: {:#?}", exit_fee);
+    assert!(exit_fee["exit_fee_bps"].as_u64().unwrap() > 990, "ERR ILLEGAL EXIT FEE");
+
+    let anon_unstake = anon
+        .call(contract.id(), "unstake")
+        .args_json(serde_json::json!({}))
+        .deposit(ONE_YOCTO)
+        .gas(NearGas::from_tgas(100))
+        .transact()
+        .await?;
+    println!("anon_unstake: {:#?}", anon_unstake.logs());
+
+    let anon_ft_balance: U128 = ft_contract
+        .call("ft_balance_of")
+        .args_json(serde_json::json!({
+            "account_id": anon.id().to_string(),
+        }))
+        .view()
+        .await?
+        .json()?;
+    let anon_exit_fee = amount_100_tokens.0 - anon_ft_balance.0;
+    assert!(
+        anon_exit_fee > amount_100_tokens.0 * 99 / 1000 && anon_exit_fee <= amount_100_tokens.0 / 10,
+        "ERR ILLEGAL EXIT FEE CHARGED"
+    );
+
+    // exit fee stays with the remaining staker
+    let virtual_price: U128 = contract
+        .call("get_virtual_price")
+        .args_json(serde_json::json!({}))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(
+        virtual_price.0,
+        (amount_100_tokens.0 + anon_exit_fee) * 100_000_000 / amount_100_tokens.0,
+        "ERR EXIT FEE DIDN'T RAISE VIRTUAL PRICE"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn verify_exit_fee_on_failed_unstake() -> anyhow::Result<()> {
+    let worker: Worker<Sandbox> = near_workspaces::sandbox().await?;
+    let (contract, ft_contract, owner, anon) = init(&worker).await?;
+
+    // 10% exit fee minted to the owner as the treasury
+    let _ = owner
+        .call(contract.id(), "set_treasury")
+        .args_json(serde_json::json!({ "treasury_id": owner.id().to_string() }))
+        .transact()
+        .await?;
+    let _ = owner
+        .call(contract.id(), "set_exit_fee")
+        .args_json(serde_json::json!({
+            "exit_fee_bps": 1000,
+            "exit_fee_duration_in_sec": 60 * 60 * 24,
+            "exit_fee_to_treasury": true,
+        }))
+        .transact()
+        .await?;
+
+    let amount_100_tokens = U128::from(NearToken::from_near(100).as_yoctonear());
+    let _ = anon
+        .call(ft_contract.id(), "storage_deposit")
+        .args_json(serde_json::json!({ "account_id": anon.id().to_string() }))
+        .gas(NearGas::from_tgas(100))
+        .deposit(NearToken::from_yoctonear(1250000000000000000000))
+        .transact()
+        .await?;
+    let _ = owner
+        .call(ft_contract.id(), "ft_transfer")
+        .args_json(serde_json::json!({ "receiver_id": anon.id().to_string(), "amount": amount_100_tokens }))
+        .deposit(ONE_YOCTO)
+        .transact()
+        .await?;
+    for account in [&owner, &anon] {
+        let _ = account
+            .call(ft_contract.id(), "ft_transfer_call")
+            .args_json(serde_json::json!({
+                "receiver_id": contract.id().to_string(),
+                "amount": amount_100_tokens,
+                "msg": "\"Stake\""
+            }))
+            .max_gas()
+            .deposit(ONE_YOCTO)
+            .transact()
+            .await?;
+    }
+
+    // anon leaves the token, so the unstaked tokens can't be sent
+    let _ = anon
+        .call(ft_contract.id(), "storage_unregister")
+        .args_json(serde_json::json!({ "force": true }))
+        .deposit(ONE_YOCTO)
+        .transact()
+        .await?;
+    let anon_unstake = anon
+        .call(contract.id(), "unstake")
+        .args_json(serde_json::json!({}))
+        .deposit(ONE_YOCTO)
+        .gas(NearGas::from_tgas(100))
+        .transact()
+        .await?;
+    println!("anon_unstake: {:#?}", anon_unstake.logs());
+    assert!(anon_unstake.logs().iter().any(|log| log.contains("withdraw_failed")));
+
+    // the fee shares of the treasury stay backed, anon is staked back without the fee
+    let virtual_price: U128 = contract
+        .call("get_virtual_price")
+        .args_json(serde_json::json!({}))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(virtual_price.0, 100_000_000, "ERR FAILED UNSTAKE DILUTED STAKERS");
+    let anon_shares: U128 = contract
+        .call("get_shares")
+        .args_json(serde_json::json!({ "account_id": anon.id().to_string() }))
+        .view()
+        .await?
+        .json()?;
+    // the fee decays by a few basis points while the blocks pass
+    assert!(anon_shares.0 > amount_100_tokens.0 * 899 / 1000 && anon_shares.0 < amount_100_tokens.0 * 902 / 1000);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn verify_min_holding_period() -> anyhow::Result<()> {
+    let worker: Worker<Sandbox> = near_workspaces::sandbox().await?;
+    let (contract, ft_contract, owner, _anon) = init(&worker).await?;
+
+    let min_holding_period_in_sec: u32 = 60 * 60;
+    let _ = owner
+        .call(contract.id(), "set_min_holding_period")
+        .args_json(serde_json::json!({
+            "min_holding_period_in_sec": min_holding_period_in_sec,
+        }))
+        .transact()
+        .await?;
+
+    let amount_100_tokens = U128::from(NearToken::from_near(100).as_yoctonear());
+    let _ = owner
+        .call(ft_contract.id(), "ft_transfer_call")
+        .args_json(serde_json::json!({
+            "receiver_id": contract.id().to_string(),
+            "amount": amount_100_tokens,
+            "msg": "\"Stake\""
+        }))
+        .max_gas()
+        .deposit(ONE_YOCTO)
+        .transact()
+        .await?;
+
+    let (timestamp, _epoch_height): (u64, u64) = contract.call("current_env_data").view().await?.json()?;
+
+    let unstake_available_time: u32 = contract
+        .call("get_unstake_available_time")
+        .args_json(serde_json::json!({
+            "account_id": owner.id().to_string(),
+        }))
+        .view()
+        .await?
+        .json()?;
+    assert!(
+        unstake_available_time > (timestamp / NANOSEC_IN_SEC) as u32,
+        "ERR SHARES ARE UNSTAKABLE"
+    );
+    assert!(unstake_available_time <= (timestamp / NANOSEC_IN_SEC) as u32 + min_holding_period_in_sec);
+
+    let owner_unstake = owner
+        .call(contract.id(), "unstake")
+        .args_json(serde_json::json!({
+            "amount": U128::from(NearToken::from_near(1).as_yoctonear()),
+        }))
+        .deposit(ONE_YOCTO)
+        .gas(NearGas::from_tgas(100))
+        .transact()
+        .await?;
+    assert!(!owner_unstake.is_success(), "ERR UNSTAKED DURING HOLDING PERIOD");
+    assert!(format!("{:?}", owner_unstake.into_result()).contains("ERR_HOLDING_PERIOD_NOT_PASSED"));
+
+    // lift the holding period
+    let _ = owner
+        .call(contract.id(), "set_min_holding_period")
+        .args_json(serde_json::json!({
+            "min_holding_period_in_sec": 0,
+        }))
+        .transact()
+        .await?;
+
+    let owner_unstake_2 = owner
+        .call(contract.id(), "unstake")
+        .args_json(serde_json::json!({
+            "amount": U128::from(NearToken::from_near(1).as_yoctonear()),
+        }))
+        .deposit(ONE_YOCTO)
+        .gas(NearGas::from_tgas(100))
+        .transact()
+        .await?;
+    assert!(owner_unstake_2.is_success(), "ERR UNSTAKE FAILED WITHOUT HOLDING PERIOD");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn verify_holding_period_of_top_up() -> anyhow::Result<()> {
+    let worker: Worker<Sandbox> = near_workspaces::sandbox().await?;
+    let (contract, ft_contract, owner, _anon) = init(&worker).await?;
+
+    let _ = owner
+        .call(contract.id(), "set_min_holding_period")
+        .args_json(serde_json::json!({
+            "min_holding_period_in_sec": 60,
+        }))
+        .transact()
+        .await?;
+
+    let amount_100_tokens = U128::from(NearToken::from_near(100).as_yoctonear());
+    let stake = || {
+        owner
+            .call(ft_contract.id(), "ft_transfer_call")
+            .args_json(serde_json::json!({
+                "receiver_id": contract.id().to_string(),
+                "amount": amount_100_tokens,
+                "msg": "\"Stake\""
+            }))
+            .max_gas()
+            .deposit(ONE_YOCTO)
+            .transact()
+    };
+    let _ = stake().await?;
+
+    // the first stake leaves its holding period
+    worker.fast_forward(1000).await?;
+    let (timestamp, _epoch_height): (u64, u64) = contract.call("current_env_data").view().await?.json()?;
+    let unstake_available_time: u32 = contract
+        .call("get_unstake_available_time")
+        .args_json(serde_json::json!({
+            "account_id": owner.id().to_string(),
+        }))
+        .view()
+        .await?
+        .json()?;
+    assert!(unstake_available_time <= (timestamp / NANOSEC_IN_SEC) as u32, "ERR SHARES ARE HELD");
+
+    let _ = stake().await?;
+
+    // the top-up is held
+    let owner_unstake = owner
+        .call(contract.id(), "unstake")
+        .args_json(serde_json::json!({
+            "amount": U128::from(NearToken::from_near(150).as_yoctonear()),
+        }))
+        .deposit(ONE_YOCTO)
+        .gas(NearGas::from_tgas(100))
+        .transact()
+        .await?;
+    assert!(format!("{:?}", owner_unstake.into_result()).contains("ERR_HOLDING_PERIOD_NOT_PASSED"));
+
+    // the older shares are not
+    let owner_unstake_2 = owner
+        .call(contract.id(), "unstake")
+        .args_json(serde_json::json!({
+            "amount": U128::from(NearToken::from_near(90).as_yoctonear()),
+        }))
+        .deposit(ONE_YOCTO)
+        .gas(NearGas::from_tgas(100))
+        .transact()
+        .await?;
+    assert!(owner_unstake_2.is_success(), "ERR OLDER SHARES ARE HELD");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn verify_shares_checkpoints() -> anyhow::Result<()> {
+    let worker: Worker<Sandbox> = near_workspaces::sandbox().await?;
+    let (contract, ft_contract, owner, _anon) = init(&worker).await?;
+
+    let (timestamp_before_stake, _epoch_height): (u64, u64) = contract.call("current_env_data").view().await?.json()?;
+
+    let amount_100_tokens = U128::from(NearToken::from_near(100).as_yoctonear());
+    let amount_40_tokens = U128::from(NearToken::from_near(40).as_yoctonear());
+    let _ = owner
+        .call(ft_contract.id(), "ft_transfer_call")
+        .args_json(serde_json::json!({
+            "receiver_id": contract.id().to_string(),
+            "amount": amount_100_tokens,
+            "msg": "\"Stake\""
+        }))
+        .max_gas()
+        .deposit(ONE_YOCTO)
+        .transact()
+        .await?;
+
+    let (timestamp_after_stake, _epoch_height): (u64, u64) = contract.call("current_env_data").view().await?.json()?;
+    worker.fast_forward(10).await?;
+
+    let _ = owner
+        .call(contract.id(), "unstake")
+        .args_json(serde_json::json!({
+            "amount": amount_40_tokens,
+        }))
+        .deposit(ONE_YOCTO)
+        .gas(NearGas::from_tgas(100))
+        .transact()
+        .await?;
+
+    let (timestamp_after_unstake, _epoch_height): (u64, u64) = contract.call("current_env_data").view().await?.json()?;
+    worker.fast_forward(10).await?;
+
+    for (timestamp, expected_shares) in [
+        (timestamp_before_stake - NANOSEC_IN_SEC, 0),
+        (timestamp_after_stake, amount_100_tokens.0),
+        (timestamp_after_unstake, amount_100_tokens.0 - amount_40_tokens.0),
+    ] {
+        let timestamp_in_sec = timestamp / NANOSEC_IN_SEC;
+        let shares_at: U128 = contract
+            .call("get_shares_at")
+            .args_json(serde_json::json!({
+                "account_id": owner.id().to_string(),
+                "timestamp_in_sec": timestamp_in_sec,
+            }))
+            .view()
+            .await?
+            .json()?;
+        assert_eq!(shares_at.0, expected_shares, "ERR ILLEGAL SHARES AT {}", timestamp_in_sec);
+
+        let total_staked_at: U128 = contract
+            .call("get_total_staked_at")
+            .args_json(serde_json::json!({
+                "timestamp_in_sec": timestamp_in_sec,
+            }))
+            .view()
+            .await?
+            .json()?;
+        assert_eq!(
+            total_staked_at.0, expected_shares,
+            "ERR ILLEGAL TOTAL STAKED AT {}",
+            timestamp_in_sec
+        );
+    }
+
+    // a stake below the minimum is refunded, it adds no checkpoint
+    let min_stake_amount: U128 = contract.call("get_min_stake_amount").view().await?.json()?;
+    assert_eq!(min_stake_amount.0, NearToken::from_near(1).as_yoctonear());
+    let dust_stake = owner
+        .call(ft_contract.id(), "ft_transfer_call")
+        .args_json(serde_json::json!({
+            "receiver_id": contract.id().to_string(),
+            "amount": U128::from(min_stake_amount.0 - 1),
+            "msg": "\"Stake\""
+        }))
+        .max_gas()
+        .deposit(ONE_YOCTO)
+        .transact()
+        .await?;
+    println!("dust_stake: {:#?}", dust_stake.logs());
+    let shares: U128 = contract
+        .call("get_shares")
+        .args_json(serde_json::json!({ "account_id": owner.id().to_string() }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(shares.0, amount_100_tokens.0 - amount_40_tokens.0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn verify_delegation() -> anyhow::Result<()> {
+    let worker: Worker<Sandbox> = near_workspaces::sandbox().await?;
+    let (contract, ft_contract, owner, anon) = init(&worker).await?;
+
+    let amount_100_tokens = U128::from(NearToken::from_near(100).as_yoctonear());
+    let amount_50_tokens = U128::from(NearToken::from_near(50).as_yoctonear());
+    let _ = owner
+        .call(ft_contract.id(), "ft_transfer_call")
+        .args_json(serde_json::json!({
+            "receiver_id": contract.id().to_string(),
+            "amount": amount_100_tokens,
+            "msg": "\"Stake\""
+        }))
+        .max_gas()
+        .deposit(ONE_YOCTO)
+        .transact()
+        .await?;
+
+    let self_delegate = owner
+        .call(contract.id(), "delegate")
+        .args_json(serde_json::json!({
+            "delegatee_id": owner.id().to_string(),
+        }))
+        .transact()
+        .await?;
+    assert!(!self_delegate.is_success(), "ERR SELF DELEGATION");
+
+    let delegate = owner
+        .call(contract.id(), "delegate")
+        .args_json(serde_json::json!({
+            "delegatee_id": anon.id().to_string(),
+        }))
+        .transact()
+        .await?;
+    println!("delegate: {:#?}", delegate.logs());
+    assert!(delegate.is_success());
+
+    // next stake follows the delegation
+    let _ = owner
+        .call(ft_contract.id(), "ft_transfer_call")
+        .args_json(serde_json::json!({
+            "receiver_id": contract.id().to_string(),
+            "amount": amount_50_tokens,
+            "msg": "\"Stake\""
+        }))
+        .max_gas()
+        .deposit(ONE_YOCTO)
+        .transact()
+        .await?;
+
+    let anon_voting_power: serde_json::Value = contract
+        .call("get_voting_power")
+        .args_json(serde_json::json!({
+            "account_id": anon.id().to_string(),
+        }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(
+        anon_voting_power["delegated_power"],
+        (amount_100_tokens.0 + amount_50_tokens.0).to_string()
+    );
+    assert_eq!(anon_voting_power["own_power"], "0");
+
+    let owner_voting_power: serde_json::Value = contract
+        .call("get_voting_power")
+        .args_json(serde_json::json!({
+            "account_id": owner.id().to_string(),
+        }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(owner_voting_power["total_power"], "0");
+    assert_eq!(owner_voting_power["delegatee_id"], anon.id().to_string());
+
+    let _ = owner
+        .call(contract.id(), "undelegate")
+        .args_json(serde_json::json!({}))
+        .transact()
+        .await?;
+
+    let anon_voting_power_2: serde_json::Value = contract
+        .call("get_voting_power")
+        .args_json(serde_json::json!({
+            "account_id": anon.id().to_string(),
+        }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(anon_voting_power_2["total_power"], "0");
+
+    let owner_delegatee: Option<String> = contract
+        .call("get_delegatee")
+        .args_json(serde_json::json!({
+            "account_id": owner.id().to_string(),
+        }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(owner_delegatee, None);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn verify_polls() -> anyhow::Result<()> {
+    let worker: Worker<Sandbox> = near_workspaces::sandbox().await?;
+    let (contract, ft_contract, owner, anon) = init(&worker).await?;
+
+    let amount_100_tokens = U128::from(NearToken::from_near(100).as_yoctonear());
+    let _ = owner
+        .call(ft_contract.id(), "ft_transfer_call")
+        .args_json(serde_json::json!({
+            "receiver_id": contract.id().to_string(),
+            "amount": amount_100_tokens,
+            "msg": "\"Stake\""
+        }))
+        .max_gas()
+        .deposit(ONE_YOCTO)
+        .transact()
+        .await?;
+
+    worker.fast_forward(10).await?;
+    let (timestamp, _epoch_height): (u64, u64) = contract.call("current_env_data").view().await?.json()?;
+    let deadline_in_sec = timestamp / NANOSEC_IN_SEC + 60 * 60;
+
+    let create_poll_by_anon = anon
+        .call(contract.id(), "create_poll")
+        .args_json(serde_json::json!({
+            "title": "Poll",
+            "options": ["Yes", "No"],
+            "deadline_in_sec": deadline_in_sec,
+        }))
+        .transact()
+        .await?;
+    assert!(!create_poll_by_anon.is_success(), "ANON DOESN'T TRIGGER A FAILURE");
+
+    let _ = owner
+        .call(contract.id(), "add_proposer")
+        .args_json(serde_json::json!({
+            "account_id": anon.id().to_string(),
+        }))
+        .transact()
+        .await?;
+
+    let create_poll = anon
+        .call(contract.id(), "create_poll")
+        .args_json(serde_json::json!({
+            "title": "Poll",
+            "options": ["Yes", "No"],
+            "deadline_in_sec": deadline_in_sec,
+        }))
+        .transact()
+        .await?;
+    println!("create_poll: {:#?}", create_poll.logs());
+    let poll_id: u64 = create_poll.json()?;
+
+    // anon has no shares at the poll snapshot
+    let anon_vote = anon
+        .call(contract.id(), "vote")
+        .args_json(serde_json::json!({
+            "poll_id": poll_id,
+            "option": 0,
+        }))
+        .transact()
+        .await?;
+    assert!(!anon_vote.is_success(), "ERR VOTED WITHOUT SHARES");
+
+    let owner_vote = owner
+        .call(contract.id(), "vote")
+        .args_json(serde_json::json!({
+            "poll_id": poll_id,
+            "option": 1,
+        }))
+        .transact()
+        .await?;
+    println!("owner_vote: {:#?}", owner_vote.logs());
+    assert!(owner_vote.is_success());
+
+    let owner_vote_2 = owner
+        .call(contract.id(), "vote")
+        .args_json(serde_json::json!({
+            "poll_id": poll_id,
+            "option": 0,
+        }))
+        .transact()
+        .await?;
+    assert!(!owner_vote_2.is_success(), "ERR VOTED TWICE");
+
+    let poll: serde_json::Value = contract
+        .call("get_poll")
+        .args_json(serde_json::json!({
+            "poll_id": poll_id,
+        }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(poll["votes"], serde_json::json!(["0", amount_100_tokens.0.to_string()]));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn verify_referral() -> anyhow::Result<()> {
+    let worker: Worker<Sandbox> = near_workspaces::sandbox().await?;
+    let (contract, ft_contract, owner, anon) = init(&worker).await?;
+
+    // anon to register in the token
+    let _ = anon
+        .call(ft_contract.id(), "storage_deposit")
+        .args_json(serde_json::json!({
+            "account_id": anon.id().to_string(),
+        }))
+        .gas(NearGas::from_tgas(100))
+        .deposit(NearToken::from_yoctonear(1250000000000000000000))
+        .transact()
+        .await?;
+
+    // 10% of referee reward goes to the referrer
+    let _ = owner
+        .call(contract.id(), "set_referral_fee")
+        .args_json(serde_json::json!({
+            "referral_fee_bps": 1000,
+        }))
+        .transact()
+        .await?;
+
+    let amount_100_tokens = U128::from(NearToken::from_near(100).as_yoctonear());
+    for msg in ["\"AddRewards\"", "\"AddReferralPool\""] {
+        let _ = owner
+            .call(ft_contract.id(), "ft_transfer_call")
+            .args_json(serde_json::json!({
+                "receiver_id": contract.id().to_string(),
+                "amount": amount_100_tokens,
+                "msg": msg
+            }))
+            .max_gas()
+            .deposit(ONE_YOCTO)
+            .transact()
+            .await?;
+    }
+
+    let owner_stake = owner
+        .call(ft_contract.id(), "ft_transfer_call")
+        .args_json(serde_json::json!({
+            "receiver_id": contract.id().to_string(),
+            "amount": amount_100_tokens,
+            "msg": serde_json::json!({ "Stake": { "referrer_id": anon.id().to_string() } }).to_string()
+        }))
+        .max_gas()
+        .deposit(ONE_YOCTO)
+        .transact()
+        .await?;
+    println!("owner_stake: {:#?}", owner_stake.logs());
+
+    let referrer: Option<String> = contract
+        .call("get_referrer")
+        .args_json(serde_json::json!({
+            "account_id": owner.id().to_string(),
+        }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(referrer, Some(anon.id().to_string()));
+
+    let referees: Vec<String> = contract
+        .call("get_referees")
+        .args_json(serde_json::json!({
+            "referrer_id": anon.id().to_string(),
+        }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(referees, vec![owner.id().to_string()]);
+
+    let _ = owner
+        .call(contract.id(), "modify_reward_per_sec")
+        .args_json(serde_json::json!({
+            "reward_per_sec": U128::from(REWARD_PER_SEC),
+            "distribute_before_change": false,
+        }))
+        .transact()
+        .await?;
+
+    let (timestamp, _epoch_height): (u64, u64) = contract.call("current_env_data").view().await?.json()?;
+    let _ = owner
+        .call(contract.id(), "reset_reward_genesis_time_in_sec")
+        .args_json(serde_json::json!({
+            "reward_genesis_time_in_sec": timestamp / NANOSEC_IN_SEC + 1,
+        }))
+        .transact()
+        .await?;
+
+    worker.fast_forward(100).await?;
+
+    let _ = anon
+        .call(contract.id(), "sync_referral")
+        .args_json(serde_json::json!({
+            "account_id": owner.id().to_string(),
+        }))
+        .transact()
+        .await?;
+
+    let referral_earnings: U128 = contract
+        .call("get_referral_earnings")
+        .args_json(serde_json::json!({
+            "account_id": anon.id().to_string(),
+        }))
+        .view()
+        .await?
+        .json()?;
+    let undistributed_reward: U128 = contract
+        .call("get_undistributed_reward")
+        .args_json(serde_json::json!({}))
+        .view()
+        .await?
+        .json()?;
+    let distributed_reward = amount_100_tokens.0 - undistributed_reward.0;
+    assert!(
+        u128_diff(referral_earnings.0, distributed_reward / 10) <= 1,
+        "ERR ILLEGAL REFERRAL EARNINGS"
+    );
+
+    let claim = anon
+        .call(contract.id(), "claim_referral_earnings")
+        .args_json(serde_json::json!({}))
+        .deposit(ONE_YOCTO)
+        .max_gas()
+        .transact()
+        .await?;
+    println!("claim: {:#?}", claim.logs());
+
+    let anon_ft_balance: U128 = ft_contract
+        .call("ft_balance_of")
+        .args_json(serde_json::json!({
+            "account_id": anon.id().to_string(),
+        }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(anon_ft_balance.0, referral_earnings.0, "ERR REFERRAL EARNINGS WERE NOT CLAIMED");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn verify_multiple_pools() -> anyhow::Result<()> {
+    let worker: Worker<Sandbox> = near_workspaces::sandbox().await?;
+    let (contract, ft_contract, owner, anon) = init(&worker).await?;
+
+    // a pool for another token
+    let ft_wasm = std::fs::read(FT_WASM_FILEPATH)?;
+    let other_ft_contract = worker.dev_deploy(&ft_wasm).await?;
+    let _ = other_ft_contract
+        .call("new_default_meta")
+        .args_json(serde_json::json!({
+            "owner_id": owner.id().to_string(),
+            "total_supply": U128::from(TOKEN_TOTAL_SUPPLY),
+        }))
+        .gas(NearGas::from_tgas(100))
+        .transact()
+        .await?;
+    let _ = owner
+        .call(other_ft_contract.id(), "storage_deposit")
+        .args_json(serde_json::json!({
+            "account_id": contract.id().to_string(),
+        }))
+        .gas(NearGas::from_tgas(100))
+        .deposit(NearToken::from_yoctonear(1250000000000000000000))
+        .transact()
+        .await?;
+
+    let add_pool_by_anon = anon
+        .call(contract.id(), "add_pool")
+        .args_json(serde_json::json!({
+            "token_id": other_ft_contract.id().to_string(),
+        }))
+        .transact()
+        .await?;
+    assert!(!add_pool_by_anon.is_success(), "ANON DOESN'T TRIGGER A FAILURE");
+
+    let (timestamp, _epoch_height): (u64, u64) = contract.call("current_env_data").view().await?.json()?;
+    let pool_id: u64 = owner
+        .call(contract.id(), "add_pool")
+        .args_json(serde_json::json!({
+            "token_id": other_ft_contract.id().to_string(),
+            "reward_per_sec": U128::from(REWARD_PER_SEC),
+            "reward_genesis_time_in_sec": timestamp / NANOSEC_IN_SEC + 1,
+        }))
+        .transact()
+        .await?
+        .json()?;
+    assert_eq!(pool_id, 1);
+
+    // the main token can't be staked into the pool
+    let amount_100_tokens = U128::from(NearToken::from_near(100).as_yoctonear());
+    let stake_illegal_token = owner
+        .call(ft_contract.id(), "ft_transfer_call")
+        .args_json(serde_json::json!({
+            "receiver_id": contract.id().to_string(),
+            "amount": amount_100_tokens,
+            "msg": serde_json::json!({ "Stake": { "pool_id": pool_id } }).to_string()
+        }))
+        .max_gas()
+        .deposit(ONE_YOCTO)
+        .transact()
+        .await?;
+    println!("stake_illegal_token: {:#?}", stake_illegal_token.logs());
+    let owner_ft_balance: U128 = ft_contract
+        .call("ft_balance_of")
+        .args_json(serde_json::json!({
+            "account_id": owner.id().to_string(),
+        }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(owner_ft_balance.0, TOKEN_TOTAL_SUPPLY, "ERR ILLEGAL TOKEN STAKED");
+
+    for (msg, event) in [
+        (serde_json::json!({ "AddRewards": { "pool_id": pool_id } }), "pool_add_rewards"),
+        (serde_json::json!({ "Stake": { "pool_id": pool_id } }), "pool_add_stake"),
+    ] {
+        let transfer = owner
+            .call(other_ft_contract.id(), "ft_transfer_call")
+            .args_json(serde_json::json!({
+                "receiver_id": contract.id().to_string(),
+                "amount": amount_100_tokens,
+                "msg": msg.to_string()
+            }))
+            .max_gas()
+            .deposit(ONE_YOCTO)
+            .transact()
+            .await?;
+        assert!(
+            transfer.logs().iter().any(|log| log.contains(&format!("\"event\":\"{}\"", event))),
+            "ERR NO POOL EVENT"
+        );
+    }
+
+    let pool: serde_json::Value = contract
+        .call("get_pool")
+        .args_json(serde_json::json!({
+            "pool_id": pool_id,
+        }))
+        .view()
+        .await?
+        .json()?;
+    println!("pool: {:#?}", pool);
+    assert_eq!(pool["token_id"], other_ft_contract.id().to_string());
+    assert_eq!(pool["total_staked"], amount_100_tokens.0.to_string());
+    assert_eq!(pool["account_number"], 1);
+
+    let pool_shares: U128 = contract
+        .call("get_shares")
+        .args_json(serde_json::json!({
+            "account_id": owner.id().to_string(),
+            "pool_id": pool_id,
+        }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(pool_shares, amount_100_tokens);
+
+    // the main pool is not affected
+    let main_total_staked: U128 = contract
+        .call("get_total_staked")
+        .args_json(serde_json::json!({}))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(main_total_staked.0, 0);
+
+    worker.fast_forward(100).await?;
+
+    let pool_price: U128 = contract
+        .call("get_virtual_price")
+        .args_json(serde_json::json!({
+            "pool_id": pool_id,
+        }))
+        .view()
+        .await?
+        .json()?;
+    assert!(pool_price.0 > 100_000_000, "ERR POOL REWARD WAS NOT DISTRIBUTED");
+
+    // keep one token staked
+    let amount_50_tokens = U128::from(NearToken::from_near(50).as_yoctonear());
+    let unstake = owner
+        .call(contract.id(), "unstake")
+        .args_json(serde_json::json!({
+            "amount": amount_50_tokens,
+            "pool_id": pool_id,
+        }))
+        .deposit(ONE_YOCTO)
+        .max_gas()
+        .transact()
+        .await?;
+    println!("unstake: {:#?}", unstake.logs());
+    assert!(unstake.is_success());
+
+    let owner_other_ft_balance: U128 = other_ft_contract
+        .call("ft_balance_of")
+        .args_json(serde_json::json!({
+            "account_id": owner.id().to_string(),
+        }))
+        .view()
+        .await?
+        .json()?;
+    assert!(
+        owner_other_ft_balance.0 > TOKEN_TOTAL_SUPPLY - 2 * amount_100_tokens.0 + amount_50_tokens.0,
+        "ERR UNSTAKE WITHOUT REWARD"
+    );
+
+    // the pool token can't be recovered
+    let recover_pool_token = owner
+        .call(contract.id(), "recover_tokens")
+        .args_json(serde_json::json!({
+            "token_id": other_ft_contract.id().to_string(),
+            "amount": amount_50_tokens,
+            "receiver_id": owner.id().to_string(),
+        }))
+        .deposit(ONE_YOCTO)
+        .max_gas()
+        .transact()
+        .await?;
+    assert!(!recover_pool_token.is_success(), "ERR POOL TOKEN RECOVERED");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn verify_points_seasons() -> anyhow::Result<()> {
+    let worker: Worker<Sandbox> = near_workspaces::sandbox().await?;
+    let (contract, ft_contract, owner, anon) = init(&worker).await?;
+
+    // anon to register in the token and get tokens to stake
+    let _ = anon
+        .call(ft_contract.id(), "storage_deposit")
+        .args_json(serde_json::json!({
+            "account_id": anon.id().to_string(),
+        }))
+        .gas(NearGas::from_tgas(100))
+        .deposit(NearToken::from_yoctonear(1250000000000000000000))
+        .transact()
+        .await?;
+    let amount_100_tokens = U128::from(NearToken::from_near(100).as_yoctonear());
+    let _ = owner
+        .call(ft_contract.id(), "ft_transfer")
+        .args_json(serde_json::json!({
+            "receiver_id": anon.id().to_string(),
+            "amount": amount_100_tokens,
+        }))
+        .deposit(ONE_YOCTO)
+        .transact()
+        .await?;
+
+    // owner stakes twice as much as anon and earlier
+    for (account, amount) in [(&owner, 2 * amount_100_tokens.0), (&anon, amount_100_tokens.0)] {
+        let _ = account
+            .call(ft_contract.id(), "ft_transfer_call")
+            .args_json(serde_json::json!({
+                "receiver_id": contract.id().to_string(),
+                "amount": U128::from(amount),
+                "msg": "\"Stake\""
+            }))
+            .max_gas()
+            .deposit(ONE_YOCTO)
+            .transact()
+            .await?;
+    }
+    worker.fast_forward(10).await?;
+
+    let ranking: Vec<serde_json::Value> = contract
+        .call("get_points_ranking")
+        .args_json(serde_json::json!({}))
+        .view()
+        .await?
+        .json()?;
+    let ranked_ids: Vec<&str> = ranking.iter().map(|points| points["account_id"].as_str().unwrap()).collect();
+    assert_eq!(ranked_ids, vec![owner.id().as_str(), anon.id().as_str()]);
+
+    // only the owner finishes a season
+    let anon_start_season = anon.call(contract.id(), "start_season").transact().await?;
+    assert!(anon_start_season.is_failure());
+    let season_id: u64 = owner.call(contract.id(), "start_season").transact().await?.json()?;
+    assert_eq!(season_id, 1);
+
+    // a finished season keeps its points, they add up to its total
+    let season: serde_json::Value = contract
+        .call("get_season")
+        .args_json(serde_json::json!({ "season_id": 0 }))
+        .view()
+        .await?
+        .json()?;
+    let total_points: u128 = season["total_points"].as_str().unwrap().parse()?;
+    let mut season_points = vec![];
+    for account in [&owner, &anon] {
+        let points: String = contract
+            .call("get_season_points")
+            .args_json(serde_json::json!({
+                "account_id": account.id().to_string(),
+                "season_id": 0,
+            }))
+            .view()
+            .await?
+            .json()?;
+        season_points.push(points.parse::<u128>()?);
+    }
+    assert!(season_points[0] > season_points[1], "ERR OWNER HAS LESS POINTS");
+    assert_eq!(season_points[0] + season_points[1], total_points);
+
+    // points are recorded when shares change in a later season
+    let _ = anon
+        .call(contract.id(), "unstake")
+        .args_json(serde_json::json!({}))
+        .deposit(ONE_YOCTO)
+        .gas(NearGas::from_tgas(100))
+        .transact()
+        .await?;
+    let anon_season_points: String = contract
+        .call("get_season_points")
+        .args_json(serde_json::json!({
+            "account_id": anon.id().to_string(),
+            "season_id": 0,
+        }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(anon_season_points.parse::<u128>()?, season_points[1]);
+
+    // a reset season discards its points
+    worker.fast_forward(5).await?;
+    let season_id: u64 = owner.call(contract.id(), "reset_season").transact().await?.json()?;
+    assert_eq!(season_id, 2);
+    let owner_reset_points: String = contract
+        .call("get_season_points")
+        .args_json(serde_json::json!({
+            "account_id": owner.id().to_string(),
+            "season_id": 1,
+        }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(owner_reset_points, "0");
+
+    // anon has no shares left, so it earns no points in the current season
+    let anon_points: String = contract
+        .call("get_points")
+        .args_json(serde_json::json!({
+            "account_id": anon.id().to_string(),
+        }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(anon_points, "0");
+
+    // points of a season in which shares didn't change are read from the checkpoints after a later stake
+    worker.fast_forward(5).await?;
+    let season_id: u64 = owner.call(contract.id(), "start_season").transact().await?.json()?;
+    assert_eq!(season_id, 3);
+    let _ = owner
+        .call(ft_contract.id(), "ft_transfer_call")
+        .args_json(serde_json::json!({
+            "receiver_id": contract.id().to_string(),
+            "amount": amount_100_tokens,
+            "msg": "\"Stake\""
+        }))
+        .max_gas()
+        .deposit(ONE_YOCTO)
+        .transact()
+        .await?;
+    let season: serde_json::Value = contract
+        .call("get_season")
+        .args_json(serde_json::json!({ "season_id": 2 }))
+        .view()
+        .await?
+        .json()?;
+    assert_ne!(season["total_points"], "0");
+    let owner_idle_points: String = contract
+        .call("get_season_points")
+        .args_json(serde_json::json!({
+            "account_id": owner.id().to_string(),
+            "season_id": 2,
+        }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(owner_idle_points, season["total_points"]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn verify_reward_vesting() -> anyhow::Result<()> {
+    let worker: Worker<Sandbox> = near_workspaces::sandbox().await?;
+    let (contract, ft_contract, owner, anon) = init(&worker).await?;
+
+    // anon to register in the token and get tokens to stake
+    let _ = anon
+        .call(ft_contract.id(), "storage_deposit")
+        .args_json(serde_json::json!({
+            "account_id": anon.id().to_string(),
+        }))
+        .gas(NearGas::from_tgas(100))
+        .deposit(NearToken::from_yoctonear(1250000000000000000000))
+        .transact()
+        .await?;
+    let amount_100_tokens = U128::from(NearToken::from_near(100).as_yoctonear());
+    let _ = owner
+        .call(ft_contract.id(), "ft_transfer")
+        .args_json(serde_json::json!({
+            "receiver_id": anon.id().to_string(),
+            "amount": amount_100_tokens,
+        }))
+        .deposit(ONE_YOCTO)
+        .transact()
+        .await?;
+
+    // only the owner turns vesting on
+    let anon_set_duration = anon
+        .call(contract.id(), "set_reward_vesting_duration")
+        .args_json(serde_json::json!({ "reward_vesting_duration_in_sec": 100 }))
+        .transact()
+        .await?;
+    assert!(anon_set_duration.is_failure());
+    let _ = owner
+        .call(contract.id(), "set_reward_vesting_duration")
+        .args_json(serde_json::json!({ "reward_vesting_duration_in_sec": 100 }))
+        .transact()
+        .await?;
+
+    let _ = owner
+        .call(ft_contract.id(), "ft_transfer_call")
+        .args_json(serde_json::json!({
+            "receiver_id": contract.id().to_string(),
+            "amount": amount_100_tokens,
+            "msg": "\"AddRewards\""
+        }))
+        .max_gas()
+        .deposit(ONE_YOCTO)
+        .transact()
+        .await?;
+    for account in [&owner, &anon] {
+        let _ = account
+            .call(ft_contract.id(), "ft_transfer_call")
+            .args_json(serde_json::json!({
+                "receiver_id": contract.id().to_string(),
+                "amount": amount_100_tokens,
+                "msg": "\"Stake\""
+            }))
+            .max_gas()
+            .deposit(ONE_YOCTO)
+            .transact()
+            .await?;
+    }
+    let cost_basis: U128 = contract
+        .call("get_cost_basis")
+        .args_json(serde_json::json!({ "account_id": anon.id().to_string() }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(cost_basis, amount_100_tokens);
+
+    let _ = owner
+        .call(contract.id(), "modify_reward_per_sec")
+        .args_json(serde_json::json!({
+            "reward_per_sec": U128::from(REWARD_PER_SEC),
+            "distribute_before_change": false,
+        }))
+        .transact()
+        .await?;
+    let (timestamp, _epoch_height): (u64, u64) = contract.call("current_env_data").view().await?.json()?;
+    let _ = owner
+        .call(contract.id(), "reset_reward_genesis_time_in_sec")
+        .args_json(serde_json::json!({
+            "reward_genesis_time_in_sec": timestamp / NANOSEC_IN_SEC + 1,
+        }))
+        .transact()
+        .await?;
+    worker.fast_forward(50).await?;
+
+    // the principal comes back right away, the reward starts vesting
+    let unstake = anon
+        .call(contract.id(), "unstake")
+        .args_json(serde_json::json!({}))
+        .deposit(ONE_YOCTO)
+        .gas(NearGas::from_tgas(100))
+        .transact()
+        .await?;
+    println!("unstake: {:#?}", unstake.logs());
+    assert!(unstake.is_success());
+
+    let anon_ft_balance: U128 = ft_contract
+        .call("ft_balance_of")
+        .args_json(serde_json::json!({ "account_id": anon.id().to_string() }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(anon_ft_balance, amount_100_tokens, "ERR PRINCIPAL WAS NOT RETURNED");
+
+    let vesting: serde_json::Value = contract
+        .call("get_vesting")
+        .args_json(serde_json::json!({ "account_id": anon.id().to_string() }))
+        .view()
+        .await?
+        .json()?;
+    let locked: u128 = vesting["locked"].as_str().unwrap().parse()?;
+    let claimable: u128 = vesting["claimable"].as_str().unwrap().parse()?;
+    assert!(locked > 0, "ERR REWARD IS NOT VESTING");
+    let total_vesting: U128 = contract.call("get_total_vesting").view().await?.json()?;
+    assert_eq!(total_vesting.0, locked + claimable);
+
+    // everything vested after the vesting duration
+    worker.fast_forward(150).await?;
+    let claim = anon
+        .call(contract.id(), "claim_vested")
+        .args_json(serde_json::json!({}))
+        .deposit(ONE_YOCTO)
+        .max_gas()
+        .transact()
+        .await?;
+    println!("claim: {:#?}", claim.logs());
+    assert!(claim.is_success());
+
+    let anon_ft_balance: U128 = ft_contract
+        .call("ft_balance_of")
+        .args_json(serde_json::json!({ "account_id": anon.id().to_string() }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(
+        anon_ft_balance.0,
+        amount_100_tokens.0 + total_vesting.0,
+        "ERR VESTED REWARD WAS NOT CLAIMED"
+    );
+    let total_vesting: U128 = contract.call("get_total_vesting").view().await?.json()?;
+    assert_eq!(total_vesting.0, 0);
+
+    let claim_again = anon
+        .call(contract.id(), "claim_vested")
+        .args_json(serde_json::json!({}))
+        .deposit(ONE_YOCTO)
+        .max_gas()
+        .transact()
+        .await?;
+    assert!(claim_again.is_failure());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn verify_stake_grants() -> anyhow::Result<()> {
+    let worker: Worker<Sandbox> = near_workspaces::sandbox().await?;
+    let (contract, ft_contract, owner, anon) = init(&worker).await?;
+    let treasury = worker.dev_create_account().await?;
+
+    // anon to register in the token to get unstaked tokens
+    let _ = anon
+        .call(ft_contract.id(), "storage_deposit")
+        .args_json(serde_json::json!({
+            "account_id": anon.id().to_string(),
+        }))
+        .gas(NearGas::from_tgas(100))
+        .deposit(NearToken::from_yoctonear(1250000000000000000000))
+        .transact()
+        .await?;
+    let _ = owner
+        .call(contract.id(), "set_treasury")
+        .args_json(serde_json::json!({ "treasury_id": treasury.id().to_string() }))
+        .transact()
+        .await?;
+
+    let amount_100_tokens = U128::from(NearToken::from_near(100).as_yoctonear());
+    let _ = owner
+        .call(ft_contract.id(), "ft_transfer_call")
+        .args_json(serde_json::json!({
+            "receiver_id": contract.id().to_string(),
+            "amount": amount_100_tokens,
+            "msg": "\"Stake\""
+        }))
+        .max_gas()
+        .deposit(ONE_YOCTO)
+        .transact()
+        .await?;
+
+    // owner grants anon 100 tokens unlocking in 100 seconds after a 20 seconds cliff
+    let grant_stake = owner
+        .call(ft_contract.id(), "ft_transfer_call")
+        .args_json(serde_json::json!({
+            "receiver_id": contract.id().to_string(),
+            "amount": amount_100_tokens,
+            "msg": serde_json::json!({
+                "GrantStake": {
+                    "beneficiary_id": anon.id().to_string(),
+                    "cliff_duration_in_sec": 20,
+                    "duration_in_sec": 100,
+                    "revocable": true,
+                }
+            })
+            .to_string()
+        }))
+        .max_gas()
+        .deposit(ONE_YOCTO)
+        .transact()
+        .await?;
+    println!("grant_stake: {:#?}", grant_stake.logs());
+
+    let anon_shares: U128 = contract
+        .call("get_shares")
+        .args_json(serde_json::json!({ "account_id": anon.id().to_string() }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(anon_shares, amount_100_tokens);
+
+    // nothing is vested before the cliff
+    let unstake = anon
+        .call(contract.id(), "unstake")
+        .args_json(serde_json::json!({}))
+        .deposit(ONE_YOCTO)
+        .gas(NearGas::from_tgas(100))
+        .transact()
+        .await?;
+    assert!(unstake.is_failure());
+
+    worker.fast_forward(50).await?;
+    let grant: serde_json::Value = contract
+        .call("get_grant")
+        .args_json(serde_json::json!({ "account_id": anon.id().to_string() }))
+        .view()
+        .await?
+        .json()?;
+    let vested_shares: u128 = grant["vested_shares"].as_str().unwrap().parse()?;
+    assert!(vested_shares > 0, "ERR GRANT DIDN'T VEST");
+    assert!(vested_shares < amount_100_tokens.0, "ERR GRANT VESTED TOO EARLY");
+
+    let unstake = anon
+        .call(contract.id(), "unstake")
+        .args_json(serde_json::json!({ "amount": U128::from(vested_shares) }))
+        .deposit(ONE_YOCTO)
+        .gas(NearGas::from_tgas(100))
+        .transact()
+        .await?;
+    assert!(unstake.is_success());
+
+    // only the owner revokes, unvested shares go to the treasury
+    let anon_revoke = anon
+        .call(contract.id(), "revoke_grant")
+        .args_json(serde_json::json!({ "beneficiary_id": anon.id().to_string() }))
+        .transact()
+        .await?;
+    assert!(anon_revoke.is_failure());
+    let revoked_shares: U128 = owner
+        .call(contract.id(), "revoke_grant")
+        .args_json(serde_json::json!({ "beneficiary_id": anon.id().to_string() }))
+        .transact()
+        .await?
+        .json()?;
+    assert!(revoked_shares.0 > 0);
+
+    let treasury_shares: U128 = contract
+        .call("get_shares")
+        .args_json(serde_json::json!({ "account_id": treasury.id().to_string() }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(treasury_shares, revoked_shares);
+    let anon_shares: U128 = contract
+        .call("get_shares")
+        .args_json(serde_json::json!({ "account_id": anon.id().to_string() }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(anon_shares.0 + vested_shares + revoked_shares.0, amount_100_tokens.0);
+
+    let grant: Option<serde_json::Value> = contract
+        .call("get_grant")
+        .args_json(serde_json::json!({ "account_id": anon.id().to_string() }))
+        .view()
+        .await?
+        .json()?;
+    assert!(grant.is_none());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn verify_locked_positions() -> anyhow::Result<()> {
+    let worker: Worker<Sandbox> = near_workspaces::sandbox().await?;
+    let (contract, ft_contract, owner, anon) = init(&worker).await?;
+
+    // anon to register in the token and get tokens to lock
+    let _ = anon
+        .call(ft_contract.id(), "storage_deposit")
+        .args_json(serde_json::json!({
+            "account_id": anon.id().to_string(),
+        }))
+        .gas(NearGas::from_tgas(100))
+        .deposit(NearToken::from_yoctonear(1250000000000000000000))
+        .transact()
+        .await?;
+    let amount_100_tokens = U128::from(NearToken::from_near(100).as_yoctonear());
+    let _ = owner
+        .call(ft_contract.id(), "ft_transfer")
+        .args_json(serde_json::json!({
+            "receiver_id": anon.id().to_string(),
+            "amount": amount_100_tokens,
+        }))
+        .deposit(ONE_YOCTO)
+        .transact()
+        .await?;
+    let _ = owner
+        .call(ft_contract.id(), "ft_transfer_call")
+        .args_json(serde_json::json!({
+            "receiver_id": contract.id().to_string(),
+            "amount": amount_100_tokens,
+            "msg": "\"Stake\""
+        }))
+        .max_gas()
+        .deposit(ONE_YOCTO)
+        .transact()
+        .await?;
+    let _ = owner
+        .call(contract.id(), "set_max_lock_boost")
+        .args_json(serde_json::json!({ "max_lock_boost_bps": 10_000 }))
+        .transact()
+        .await?;
+
+    // anon locks 60 tokens for 30 seconds and 40 tokens for 10 seconds
+    let amount_60_tokens = U128::from(NearToken::from_near(60).as_yoctonear());
+    let amount_40_tokens = U128::from(NearToken::from_near(40).as_yoctonear());
+    for (amount, lock_duration_in_sec) in [(amount_60_tokens, 30), (amount_40_tokens, 10)] {
+        let lock_stake = anon
+            .call(ft_contract.id(), "ft_transfer_call")
+            .args_json(serde_json::json!({
+                "receiver_id": contract.id().to_string(),
+                "amount": amount,
+                "msg": serde_json::json!({ "LockStake": { "lock_duration_in_sec": lock_duration_in_sec } }).to_string()
+            }))
+            .max_gas()
+            .deposit(ONE_YOCTO)
+            .transact()
+            .await?;
+        println!("lock_stake: {:#?}", lock_stake.logs());
+    }
+
+    let token: serde_json::Value = contract
+        .call("nft_token")
+        .args_json(serde_json::json!({ "token_id": "0" }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(token["owner_id"], anon.id().to_string());
+    assert!(token["metadata"]["description"].as_str().unwrap().contains("locked until"));
+    let position: serde_json::Value = contract
+        .call("get_position")
+        .args_json(serde_json::json!({ "token_id": "0" }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(position["shares"], serde_json::json!(amount_60_tokens));
+    assert!(position["boost_bps"].as_u64().unwrap() > 10_000);
+
+    // a lock below the minimum stake is refunded, the contract pays the storage of positions
+    let dust_lock = owner
+        .call(ft_contract.id(), "ft_transfer_call")
+        .args_json(serde_json::json!({
+            "receiver_id": contract.id().to_string(),
+            "amount": U128::from(1),
+            "msg": serde_json::json!({ "LockStake": { "lock_duration_in_sec": 10 } }).to_string()
+        }))
+        .max_gas()
+        .deposit(ONE_YOCTO)
+        .transact()
+        .await?;
+    println!("dust_lock: {:#?}", dust_lock.logs());
+    let token: Option<serde_json::Value> = contract
+        .call("nft_token")
+        .args_json(serde_json::json!({ "token_id": "2" }))
+        .view()
+        .await?
+        .json()?;
+    assert!(token.is_none(), "ERR DUST POSITION MINTED");
+
+    // shares of the positions vote with anon, not with the contract which holds them
+    let (timestamp_after_lock, _epoch_height): (u64, u64) = contract.call("current_env_data").view().await?.json()?;
+    worker.fast_forward(2).await?;
+    for (account_id, expected_shares) in [(anon.id(), amount_100_tokens.0), (contract.id(), 0)] {
+        let shares_at: U128 = contract
+            .call("get_shares_at")
+            .args_json(serde_json::json!({
+                "account_id": account_id.to_string(),
+                "timestamp_in_sec": timestamp_after_lock / NANOSEC_IN_SEC,
+            }))
+            .view()
+            .await?
+            .json()?;
+        assert_eq!(shares_at.0, expected_shares);
+    }
+
+    // a locked position can't be unstaked
+    let unstake = anon
+        .call(contract.id(), "unstake_position")
+        .args_json(serde_json::json!({ "token_id": "0" }))
+        .deposit(ONE_YOCTO)
+        .gas(NearGas::from_tgas(100))
+        .transact()
+        .await?;
+    assert!(unstake.is_failure());
+
+    // split 20 tokens off to a new position and transfer it to the owner
+    let amount_20_tokens = U128::from(NearToken::from_near(20).as_yoctonear());
+    let split_token_id: String = anon
+        .call(contract.id(), "split_position")
+        .args_json(serde_json::json!({ "token_id": "0", "shares": amount_20_tokens }))
+        .deposit(ONE_YOCTO)
+        .max_gas()
+        .transact()
+        .await?
+        .json()?;
+    assert_eq!(split_token_id, "2");
+    let _ = anon
+        .call(contract.id(), "nft_transfer")
+        .args_json(serde_json::json!({ "receiver_id": owner.id().to_string(), "token_id": split_token_id }))
+        .deposit(ONE_YOCTO)
+        .max_gas()
+        .transact()
+        .await?;
+    let owner_supply: U128 = contract
+        .call("nft_supply_for_owner")
+        .args_json(serde_json::json!({ "account_id": owner.id().to_string() }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(owner_supply.0, 1);
+
+    // merge the 10 seconds lock into the 30 seconds one
+    let merge = anon
+        .call(contract.id(), "merge_positions")
+        .args_json(serde_json::json!({ "token_id": "0", "other_token_id": "1" }))
+        .deposit(ONE_YOCTO)
+        .max_gas()
+        .transact()
+        .await?;
+    assert!(merge.is_success());
+    let position: serde_json::Value = contract
+        .call("get_position")
+        .args_json(serde_json::json!({ "token_id": "0" }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(
+        position["amount"],
+        serde_json::json!(U128::from(amount_60_tokens.0 - amount_20_tokens.0 + amount_40_tokens.0))
+    );
+
+    // points of the positions go to their owners, not to the contract holding their shares
+    let contract_points: String = contract
+        .call("get_points")
+        .args_json(serde_json::json!({ "account_id": contract.id().to_string() }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(contract_points, "0");
+    let ranking: Vec<serde_json::Value> = contract
+        .call("get_points_ranking")
+        .args_json(serde_json::json!({}))
+        .view()
+        .await?
+        .json()?;
+    let ranked_ids: Vec<&str> = ranking.iter().map(|points| points["account_id"].as_str().unwrap()).collect();
+    assert!(ranked_ids.contains(&anon.id().as_str()), "ERR NO POSITION POINTS");
+    assert!(!ranked_ids.contains(&contract.id().as_str()), "ERR CONTRACT RANKED");
+
+    worker.fast_forward(40).await?;
+    let unstake = anon
+        .call(contract.id(), "unstake_position")
+        .args_json(serde_json::json!({ "token_id": "0" }))
+        .deposit(ONE_YOCTO)
+        .gas(NearGas::from_tgas(100))
+        .transact()
+        .await?;
+    println!("unstake_position: {:#?}", unstake.logs());
+    assert!(unstake.is_success());
+
+    let anon_ft_balance: U128 = ft_contract
+        .call("ft_balance_of")
+        .args_json(serde_json::json!({ "account_id": anon.id().to_string() }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(anon_ft_balance.0, amount_100_tokens.0 - amount_20_tokens.0);
+    let anon_supply: U128 = contract
+        .call("nft_supply_for_owner")
+        .args_json(serde_json::json!({ "account_id": anon.id().to_string() }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(anon_supply.0, 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn verify_position_holding_period_and_exit_fee() -> anyhow::Result<()> {
+    let worker: Worker<Sandbox> = near_workspaces::sandbox().await?;
+    let (contract, ft_contract, owner, _anon) = init(&worker).await?;
+
+    let amount_100_tokens = U128::from(NearToken::from_near(100).as_yoctonear());
+    let lock_stake = |amount: U128, lock_duration_in_sec: u32| {
+        owner
+            .call(ft_contract.id(), "ft_transfer_call")
+            .args_json(serde_json::json!({
+                "receiver_id": contract.id().to_string(),
+                "amount": amount,
+                "msg": serde_json::json!({ "LockStake": { "lock_duration_in_sec": lock_duration_in_sec } }).to_string()
+            }))
+            .max_gas()
+            .deposit(ONE_YOCTO)
+            .transact()
+    };
+    let _ = owner
+        .call(ft_contract.id(), "ft_transfer_call")
+        .args_json(serde_json::json!({
+            "receiver_id": contract.id().to_string(),
+            "amount": amount_100_tokens,
+            "msg": "\"Stake\""
+        }))
+        .max_gas()
+        .deposit(ONE_YOCTO)
+        .transact()
+        .await?;
+    for (method, args) in [
+        ("set_min_holding_period", serde_json::json!({ "min_holding_period_in_sec": 20 })),
+        (
+            "set_exit_fee",
+            serde_json::json!({
+                "exit_fee_bps": 1000,
+                "exit_fee_duration_in_sec": 60 * 60 * 24,
+                "exit_fee_to_treasury": false,
+            }),
+        ),
+    ] {
+        let _ = owner.call(contract.id(), method).args_json(args).transact().await?;
+    }
+
+    // a lock shorter than the holding period is refunded
+    let _ = lock_stake(amount_100_tokens, 1).await?;
+    let supply: U128 = contract.call("nft_total_supply").view().await?.json()?;
+    assert_eq!(supply.0, 0, "ERR LOCKED FOR LESS THAN HOLDING PERIOD");
+
+    let _ = lock_stake(amount_100_tokens, 20).await?;
+    let supply: U128 = contract.call("nft_total_supply").view().await?.json()?;
+    assert_eq!(supply.0, 1);
+
+    worker.fast_forward(100).await?;
+    let owner_balance_before: U128 = ft_contract
+        .call("ft_balance_of")
+        .args_json(serde_json::json!({ "account_id": owner.id().to_string() }))
+        .view()
+        .await?
+        .json()?;
+    let unstake = owner
+        .call(contract.id(), "unstake_position")
+        .args_json(serde_json::json!({ "token_id": "0" }))
+        .deposit(ONE_YOCTO)
+        .gas(NearGas::from_tgas(100))
+        .transact()
+        .await?;
+    assert!(unstake.is_success(), "ERR POSITION NOT UNSTAKED");
+    let owner_balance_after: U128 = ft_contract
+        .call("ft_balance_of")
+        .args_json(serde_json::json!({ "account_id": owner.id().to_string() }))
+        .view()
+        .await?
+        .json()?;
+    // the fee of a fresh position is close to 10%
+    let received = owner_balance_after.0 - owner_balance_before.0;
+    assert!(received < NearToken::from_near(91).as_yoctonear(), "ERR NO EXIT FEE");
+    assert!(received > NearToken::from_near(89).as_yoctonear());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn verify_nft_boosts() -> anyhow::Result<()> {
+    let worker: Worker<Sandbox> = near_workspaces::sandbox().await?;
+    let (contract, ft_contract, owner, anon) = init(&worker).await?;
+
+    // a second staking contract is the NFT collection, its locked positions are the NFTs
+    let collection = worker.dev_deploy(&std::fs::read(CONTRACT_WASM_FILEPATH)?).await?;
+    let _ = collection
+        .call("new")
+        .args_json(serde_json::json!({
+            "owner_id": owner.id().to_string(),
+            "token_id": ft_contract.id().to_string()
+        }))
+        .gas(NearGas::from_tgas(100))
+        .transact()
+        .await?;
+    for account_id in [collection.id(), anon.id()] {
+        let _ = owner
+            .call(ft_contract.id(), "storage_deposit")
+            .args_json(serde_json::json!({ "account_id": account_id.to_string() }))
+            .gas(NearGas::from_tgas(100))
+            .deposit(NearToken::from_yoctonear(1250000000000000000000))
+            .transact()
+            .await?;
+    }
+    let _ = owner
+        .call(ft_contract.id(), "ft_transfer")
+        .args_json(serde_json::json!({
+            "receiver_id": anon.id().to_string(),
+            "amount": U128::from(NearToken::from_near(200).as_yoctonear()),
+        }))
+        .deposit(ONE_YOCTO)
+        .transact()
+        .await?;
+
+    // anon locks 10 tokens in the collection to get the NFT "0", owner and anon stake 100 tokens each
+    let amount_10_tokens = U128::from(NearToken::from_near(10).as_yoctonear());
+    let amount_100_tokens = U128::from(NearToken::from_near(100).as_yoctonear());
+    let transfers = [
+        (
+            &anon,
+            collection.id(),
+            amount_10_tokens,
+            r#"{"LockStake":{"lock_duration_in_sec":10}}"#,
+        ),
+        (&owner, contract.id(), amount_100_tokens, "\"Stake\""),
+        (&anon, contract.id(), amount_100_tokens, "\"Stake\""),
+    ];
+    for (account, receiver_id, amount, msg) in transfers {
+        let _ = account
+            .call(ft_contract.id(), "ft_transfer_call")
+            .args_json(serde_json::json!({
+                "receiver_id": receiver_id.to_string(),
+                "amount": amount,
+                "msg": msg
+            }))
+            .max_gas()
+            .deposit(ONE_YOCTO)
+            .transact()
+            .await?;
+    }
+
+    // NFTs of a collection which is not approved are sent back
+    let deposit_nft = anon
+        .call(collection.id(), "nft_transfer_call")
+        .args_json(serde_json::json!({ "receiver_id": contract.id().to_string(), "token_id": "0", "msg": "" }))
+        .deposit(ONE_YOCTO)
+        .max_gas()
+        .transact()
+        .await?;
+    println!("deposit_nft: {:#?}", deposit_nft.logs());
+    let token: serde_json::Value = collection
+        .call("nft_token")
+        .args_json(serde_json::json!({ "token_id": "0" }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(token["owner_id"], anon.id().to_string());
+
+    // the collection boosts 2x once approved
+    let _ = owner
+        .call(contract.id(), "set_boost_collection")
+        .args_json(serde_json::json!({ "collection_id": collection.id().to_string(), "boost_bps": 10_000 }))
+        .transact()
+        .await?;
+    let deposit_nft = anon
+        .call(collection.id(), "nft_transfer_call")
+        .args_json(serde_json::json!({ "receiver_id": contract.id().to_string(), "token_id": "0", "msg": "" }))
+        .deposit(ONE_YOCTO)
+        .max_gas()
+        .transact()
+        .await?;
+    println!("deposit_nft: {:#?}", deposit_nft.logs());
+    let boost: serde_json::Value = contract
+        .call("get_boost")
+        .args_json(serde_json::json!({ "account_id": anon.id().to_string() }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(boost["nft_boost_bps"], 10_000);
+    assert_eq!(boost["weight"], serde_json::json!(U128::from(2 * amount_100_tokens.0)));
+
+    // 1 token per second of boost reward
+    let _ = owner
+        .call(ft_contract.id(), "ft_transfer_call")
+        .args_json(serde_json::json!({
+            "receiver_id": contract.id().to_string(),
+            "amount": amount_100_tokens,
+            "msg": "\"AddBoostRewards\""
+        }))
+        .max_gas()
+        .deposit(ONE_YOCTO)
+        .transact()
+        .await?;
+    let _ = owner
+        .call(contract.id(), "modify_boost_reward_per_sec")
+        .args_json(serde_json::json!({ "reward_per_sec": U128::from(NearToken::from_near(1).as_yoctonear()) }))
+        .transact()
+        .await?;
+    worker.fast_forward(10).await?;
+
+    let mut unclaimed = vec![];
+    for account in [&owner, &anon] {
+        let boost: serde_json::Value = contract
+            .call("get_boost")
+            .args_json(serde_json::json!({ "account_id": account.id().to_string() }))
+            .view()
+            .await?
+            .json()?;
+        unclaimed.push(boost["unclaimed"].as_str().unwrap().parse::<u128>()?);
+    }
+    println!("unclaimed: {:?}", unclaimed);
+    assert!(unclaimed[0] > 0);
+    // anon weighs twice as much as the owner, up to rounding
+    assert!(unclaimed[1] + 10 >= 2 * unclaimed[0] && unclaimed[1] <= 2 * unclaimed[0] + 10);
+
+    let claim = anon
+        .call(contract.id(), "claim_boost_reward")
+        .args_json(serde_json::json!({}))
+        .deposit(ONE_YOCTO)
+        .max_gas()
+        .transact()
+        .await?;
+    println!("claim_boost_reward: {:#?}", claim.logs());
+    assert!(claim.is_success());
+    let anon_ft_balance: U128 = ft_contract
+        .call("ft_balance_of")
+        .args_json(serde_json::json!({ "account_id": anon.id().to_string() }))
+        .view()
+        .await?
+        .json()?;
+    assert!(anon_ft_balance.0 >= NearToken::from_near(90).as_yoctonear() + unclaimed[1]);
+
+    // withdrawing the NFT sends it back and removes the boost
+    let withdraw = anon
+        .call(contract.id(), "nft_withdraw")
+        .args_json(serde_json::json!({ "collection_id": collection.id().to_string() }))
+        .deposit(ONE_YOCTO)
+        .max_gas()
+        .transact()
+        .await?;
+    println!("nft_withdraw: {:#?}", withdraw.logs());
+    assert!(withdraw.is_success());
+    let token: serde_json::Value = collection
+        .call("nft_token")
+        .args_json(serde_json::json!({ "token_id": "0" }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(token["owner_id"], anon.id().to_string());
+    let boost: serde_json::Value = contract
+        .call("get_boost")
+        .args_json(serde_json::json!({ "account_id": anon.id().to_string() }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(boost["nft_boost_bps"], 0);
+    assert_eq!(boost["weight"], serde_json::json!(amount_100_tokens));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn verify_swap_rewards() -> anyhow::Result<()> {
+    let worker: Worker<Sandbox> = near_workspaces::sandbox().await?;
+    let (contract, ft_contract, owner, _) = init(&worker).await?;
+
+    // a partner token and an exchange which buys it for half of the staked token
+    let reward_ft_contract = worker.dev_deploy(&std::fs::read(FT_WASM_FILEPATH)?).await?;
+    let _ = reward_ft_contract
+        .call("new_default_meta")
+        .args_json(serde_json::json!({
+            "owner_id": owner.id().to_string(),
+            "total_supply": U128::from(TOKEN_TOTAL_SUPPLY),
+        }))
+        .gas(NearGas::from_tgas(100))
+        .transact()
+        .await?;
+    let dex = worker.dev_deploy(&std::fs::read(MOCK_DEX_WASM_FILEPATH)?).await?;
+    let _ = dex.call("new").args_json(serde_json::json!({})).transact().await?;
+    let dex_pool_id: u64 = dex
+        .call("add_pool")
+        .args_json(serde_json::json!({
+            "token_in": reward_ft_contract.id().to_string(),
+            "token_out": ft_contract.id().to_string(),
+            "rate_bps": 5_000,
+        }))
+        .transact()
+        .await?
+        .json()?;
+    for (token_id, account_id) in [
+        (ft_contract.id(), dex.id()),
+        (reward_ft_contract.id(), dex.id()),
+        (reward_ft_contract.id(), contract.id()),
+    ] {
+        let _ = owner
+            .call(token_id, "storage_deposit")
+            .args_json(serde_json::json!({ "account_id": account_id.to_string() }))
+            .gas(NearGas::from_tgas(100))
+            .deposit(NearToken::from_yoctonear(1250000000000000000000))
+            .transact()
+            .await?;
+    }
+    let amount_100_tokens = U128::from(NearToken::from_near(100).as_yoctonear());
+    let _ = owner
+        .call(ft_contract.id(), "ft_transfer_call")
+        .args_json(serde_json::json!({
+            "receiver_id": dex.id().to_string(),
+            "amount": amount_100_tokens,
+            "msg": "AddLiquidity"
+        }))
+        .max_gas()
+        .deposit(ONE_YOCTO)
+        .transact()
+        .await?;
+
+    let amount_10_tokens = U128::from(NearToken::from_near(10).as_yoctonear());
+    let amount_5_tokens = U128::from(NearToken::from_near(5).as_yoctonear());
+    let swap_rewards = |min_amount_out: U128| {
+        owner
+            .call(reward_ft_contract.id(), "ft_transfer_call")
+            .args_json(serde_json::json!({
+                "receiver_id": contract.id().to_string(),
+                "amount": amount_10_tokens,
+                "msg": serde_json::json!({ "SwapRewards": { "min_amount_out": min_amount_out } }).to_string()
+            }))
+            .max_gas()
+            .deposit(ONE_YOCTO)
+            .transact()
+    };
+
+    // reward tokens which are not approved are refunded
+    let swap = swap_rewards(amount_5_tokens).await?;
+    println!("swap_rewards: {:#?}", swap.logs());
+    let contract_reward_ft_balance: U128 = reward_ft_contract
+        .call("ft_balance_of")
+        .args_json(serde_json::json!({ "account_id": contract.id().to_string() }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(contract_reward_ft_balance.0, 0);
+
+    let _ = owner
+        .call(contract.id(), "set_swap_dex")
+        .args_json(serde_json::json!({ "dex_id": dex.id().to_string() }))
+        .transact()
+        .await?;
+    let _ = owner
+        .call(contract.id(), "set_reward_token")
+        .args_json(serde_json::json!({ "token_id": reward_ft_contract.id().to_string(), "dex_pool_id": dex_pool_id }))
+        .transact()
+        .await?;
+
+    // 10 partner tokens are swapped to 5 staked tokens of reward
+    let swap = swap_rewards(amount_5_tokens).await?;
+    println!("swap_rewards: {:#?}", swap.logs());
+    assert!(swap.logs().iter().any(|log| log.contains("swap_rewards_succeeded")));
+    let undistributed_reward: U128 = contract
+        .call("get_undistributed_reward")
+        .args_json(serde_json::json!({}))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(undistributed_reward, amount_5_tokens);
+
+    // the swap fails on slippage and the partner tokens come back to the contract
+    let amount_6_tokens = U128::from(NearToken::from_near(6).as_yoctonear());
+    let swap = swap_rewards(amount_6_tokens).await?;
+    println!("swap_rewards: {:#?}", swap.logs());
+    assert!(swap.logs().iter().any(|log| log.contains("swap_rewards_failed")));
+    let undistributed_reward: U128 = contract
+        .call("get_undistributed_reward")
+        .args_json(serde_json::json!({}))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(undistributed_reward, amount_5_tokens);
+    let contract_reward_ft_balance: U128 = reward_ft_contract
+        .call("ft_balance_of")
+        .args_json(serde_json::json!({ "account_id": contract.id().to_string() }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(contract_reward_ft_balance, amount_10_tokens);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn verify_yield_strategy() -> anyhow::Result<()> {
+    let worker: Worker<Sandbox> = near_workspaces::sandbox().await?;
+    let (contract, ft_contract, owner, anon) = init(&worker).await?;
+
+    let lending = worker.dev_deploy(&std::fs::read(MOCK_LENDING_WASM_FILEPATH)?).await?;
+    let _ = lending
+        .call("new")
+        .args_json(serde_json::json!({ "token_id": ft_contract.id().to_string() }))
+        .transact()
+        .await?;
+    for account_id in [lending.id(), anon.id()] {
+        let _ = owner
+            .call(ft_contract.id(), "storage_deposit")
+            .args_json(serde_json::json!({ "account_id": account_id.to_string() }))
+            .gas(NearGas::from_tgas(100))
+            .deposit(NearToken::from_yoctonear(1250000000000000000000))
+            .transact()
+            .await?;
+    }
+    let amount_100_tokens = U128::from(NearToken::from_near(100).as_yoctonear());
+    let _ = owner
+        .call(ft_contract.id(), "ft_transfer")
+        .args_json(serde_json::json!({ "receiver_id": anon.id().to_string(), "amount": amount_100_tokens }))
+        .deposit(ONE_YOCTO)
+        .transact()
+        .await?;
+
+    // owner and anon stake 100 tokens each, 20% of them are kept in the contract
+    for account in [&owner, &anon] {
+        let _ = account
+            .call(ft_contract.id(), "ft_transfer_call")
+            .args_json(serde_json::json!({
+                "receiver_id": contract.id().to_string(),
+                "amount": amount_100_tokens,
+                "msg": "\"Stake\""
+            }))
+            .max_gas()
+            .deposit(ONE_YOCTO)
+            .transact()
+            .await?;
+    }
+    let _ = owner
+        .call(contract.id(), "set_strategy")
+        .args_json(serde_json::json!({ "strategy_id": lending.id().to_string() }))
+        .transact()
+        .await?;
+    let _ = owner
+        .call(contract.id(), "set_liquidity_buffer")
+        .args_json(serde_json::json!({ "liquidity_buffer_bps": 2_000 }))
+        .transact()
+        .await?;
+
+    let deploy = |amount: u128| {
+        owner
+            .call(contract.id(), "deploy_to_strategy")
+            .args_json(serde_json::json!({ "amount": U128::from(NearToken::from_near(amount).as_yoctonear()) }))
+            .deposit(ONE_YOCTO)
+            .max_gas()
+            .transact()
+    };
+    let deploy_170 = deploy(170).await?;
+    assert!(format!("{:?}", deploy_170.into_result()).contains("ERR_BUFFER_TOO_LOW"));
+    let deploy_150 = deploy(150).await?;
+    println!("deploy_to_strategy: {:#?}", deploy_150.logs());
+    assert!(deploy_150.is_success());
+    let lending_balance: U128 = lending
+        .call("get_balance")
+        .args_json(serde_json::json!({ "account_id": contract.id().to_string() }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(lending_balance.0, NearToken::from_near(150).as_yoctonear());
+
+    // the strategy earns 10 tokens, harvest adds them to undistributed reward
+    let amount_10_tokens = U128::from(NearToken::from_near(10).as_yoctonear());
+    let _ = owner
+        .call(ft_contract.id(), "ft_transfer_call")
+        .args_json(serde_json::json!({
+            "receiver_id": lending.id().to_string(),
+            "amount": amount_10_tokens,
+            "msg": "AddYield"
+        }))
+        .max_gas()
+        .deposit(ONE_YOCTO)
+        .transact()
+        .await?;
+    let _ = owner
+        .call(lending.id(), "accrue")
+        .args_json(serde_json::json!({ "account_id": contract.id().to_string(), "amount": amount_10_tokens }))
+        .transact()
+        .await?;
+    let undistributed_reward_before: U128 = contract.call("get_undistributed_reward").view().await?.json()?;
+    let harvest = owner.call(contract.id(), "harvest_strategy").max_gas().transact().await?;
+    println!("harvest_strategy: {:#?}", harvest.logs());
+    let undistributed_reward: U128 = contract.call("get_undistributed_reward").view().await?.json()?;
+    assert_eq!(undistributed_reward.0 - undistributed_reward_before.0, amount_10_tokens.0);
+
+    // 50 tokens left in the contract can't pay the unstake of anon, it waits in the queue
+    let unstake = anon
+        .call(contract.id(), "unstake")
+        .args_json(serde_json::json!({ "amount": amount_100_tokens }))
+        .deposit(ONE_YOCTO)
+        .max_gas()
+        .transact()
+        .await?;
+    println!("unstake: {:#?}", unstake.logs());
+    assert!(unstake.logs().iter().any(|log| log.contains("queue_withdraw")));
+    let anon_balance: U128 = ft_contract
+        .call("ft_balance_of")
+        .args_json(serde_json::json!({ "account_id": anon.id().to_string() }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(anon_balance.0, 0);
+    let queue: serde_json::Value = contract
+        .call("get_withdraw_queue")
+        .args_json(serde_json::json!({}))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(queue.as_array().unwrap().len(), 1);
+
+    // tokens for the queue and the buffer come back from the strategy and anon is paid
+    let process = anon.call(contract.id(), "process_withdraw_queue").max_gas().transact().await?;
+    println!("process_withdraw_queue: {:#?}", process.logs());
+    assert!(process.is_success());
+    let anon_balance: U128 = ft_contract
+        .call("ft_balance_of")
+        .args_json(serde_json::json!({ "account_id": anon.id().to_string() }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(anon_balance, amount_100_tokens);
+    let strategy: serde_json::Value = contract.call("get_strategy").view().await?.json()?;
+    assert_eq!(strategy["queue_length"], 0);
+    assert_eq!(strategy["in_progress"], false);
+    assert_eq!(
+        strategy["deployed_amount"],
+        U128::from(NearToken::from_near(80).as_yoctonear()).0.to_string()
+    );
+
+    // the strategy loses 30 tokens, the write-down takes them from locked tokens
+    let price_before: U128 = contract
+        .call("get_virtual_price")
+        .args_json(serde_json::json!({}))
+        .view()
+        .await?
+        .json()?;
+    let write_down = owner
+        .call(contract.id(), "write_down_strategy")
+        .args_json(serde_json::json!({ "amount": U128::from(NearToken::from_near(30).as_yoctonear()) }))
+        .deposit(ONE_YOCTO)
+        .transact()
+        .await?;
+    println!("write_down_strategy: {:#?}", write_down.logs());
+    assert!(write_down.is_success());
+    assert!(write_down
+        .logs()
+        .iter()
+        .any(|log| log.contains("\"event\":\"strategy_write_down\"")));
+    let strategy: serde_json::Value = contract.call("get_strategy").view().await?.json()?;
+    assert_eq!(
+        strategy["deployed_amount"],
+        U128::from(NearToken::from_near(50).as_yoctonear()).0.to_string()
+    );
+    let price: U128 = contract
+        .call("get_virtual_price")
+        .args_json(serde_json::json!({}))
+        .view()
+        .await?
+        .json()?;
+    assert!(price.0 < price_before.0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn verify_safety_module() -> anyhow::Result<()> {
+    let worker: Worker<Sandbox> = near_workspaces::sandbox().await?;
+    let (contract, ft_contract, owner, anon) = init(&worker).await?;
+    let slasher = worker.dev_create_account().await?;
+
+    for account_id in [anon.id(), slasher.id()] {
+        let _ = owner
+            .call(ft_contract.id(), "storage_deposit")
+            .args_json(serde_json::json!({ "account_id": account_id.to_string() }))
+            .gas(NearGas::from_tgas(100))
+            .deposit(NearToken::from_yoctonear(1250000000000000000000))
+            .transact()
+            .await?;
+    }
+    let amount_100_tokens = U128::from(NearToken::from_near(100).as_yoctonear());
+    let _ = owner
+        .call(ft_contract.id(), "ft_transfer")
+        .args_json(serde_json::json!({ "receiver_id": anon.id().to_string(), "amount": amount_100_tokens }))
+        .deposit(ONE_YOCTO)
+        .transact()
+        .await?;
+    for account in [&owner, &anon] {
+        let _ = account
+            .call(ft_contract.id(), "ft_transfer_call")
+            .args_json(serde_json::json!({
+                "receiver_id": contract.id().to_string(),
+                "amount": amount_100_tokens,
+                "msg": "\"Stake\""
+            }))
+            .max_gas()
+            .deposit(ONE_YOCTO)
+            .transact()
+            .await?;
+    }
+
+    // slashes of a period take at most 10% of 200 locked tokens
+    let _ = owner
+        .call(contract.id(), "set_slasher")
+        .args_json(serde_json::json!({ "slasher_id": slasher.id().to_string() }))
+        .transact()
+        .await?;
+    let _ = owner
+        .call(contract.id(), "set_max_slash")
+        .args_json(serde_json::json!({ "max_slash_bps": 1_000 }))
+        .transact()
+        .await?;
+    let slash = |account: &Account, amount: u128| {
+        account
+            .call(contract.id(), "slash")
+            .args_json(serde_json::json!({
+                "amount": U128::from(NearToken::from_near(amount).as_yoctonear()),
+                "receiver_id": slasher.id().to_string(),
+                "reason": "shortfall",
+            }))
+            .deposit(ONE_YOCTO)
+            .max_gas()
+            .transact()
+    };
+    let anon_slash = slash(&anon, 20).await?;
+    assert!(format!("{:?}", anon_slash.into_result()).contains("ERR_NOT_A_SLASHER"));
+    let slash_30 = slash(&slasher, 30).await?;
+    assert!(format!("{:?}", slash_30.into_result()).contains("ERR_SLASH_TOO_HIGH"));
+    let slash_20 = slash(&slasher, 20).await?;
+    println!("slash: {:#?}", slash_20.logs());
+    assert!(slash_20.logs().iter().any(|log| log.contains("slash_succeeded")));
+    let slash_1 = slash(&slasher, 1).await?;
+    assert!(format!("{:?}", slash_1.into_result()).contains("ERR_SLASH_TOO_HIGH"));
+
+    let slasher_balance: U128 = ft_contract
+        .call("ft_balance_of")
+        .args_json(serde_json::json!({ "account_id": slasher.id().to_string() }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(slasher_balance.0, NearToken::from_near(20).as_yoctonear());
+    let slashes: serde_json::Value = contract.call("get_slashes").args_json(serde_json::json!({})).view().await?.json()?;
+    assert_eq!(slashes.as_array().unwrap().len(), 1);
+    assert_eq!(slashes[0]["reason"], "shortfall");
+    assert_eq!(slashes[0]["reverted"], false);
+    let total_slashed: U128 = contract.call("get_total_slashed").view().await?.json()?;
+    assert_eq!(total_slashed.0, NearToken::from_near(20).as_yoctonear());
+    let virtual_price: U128 = contract
+        .call("get_virtual_price")
+        .args_json(serde_json::json!({}))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(virtual_price.0, 90_000_000);
+
+    // unstakes wait for the cooldown
+    let _ = owner
+        .call(contract.id(), "set_unstake_cooldown")
+        .args_json(serde_json::json!({ "cooldown_in_sec": 50, "unstake_window_in_sec": 1_000_000 }))
+        .transact()
+        .await?;
+    let unstake = || {
+        anon.call(contract.id(), "unstake")
+            .args_json(serde_json::json!({ "amount": amount_100_tokens }))
+            .deposit(ONE_YOCTO)
+            .max_gas()
+            .transact()
+    };
+    let unstake_1 = unstake().await?;
+    assert!(format!("{:?}", unstake_1.into_result()).contains("ERR_UNSTAKE_COOLDOWN_NOT_STARTED"));
+    let _ = anon.call(contract.id(), "start_unstake_cooldown").transact().await?;
+    let unstake_2 = unstake().await?;
+    assert!(format!("{:?}", unstake_2.into_result()).contains("ERR_UNSTAKE_COOLDOWN_NOT_PASSED"));
+
+    // after the cooldown anon gets 90 tokens for 100 shares
+    worker.fast_forward(100).await?;
+    let unstake_3 = unstake().await?;
+    println!("unstake: {:#?}", unstake_3.logs());
+    assert!(unstake_3.is_success());
+    let anon_balance: U128 = ft_contract
+        .call("ft_balance_of")
+        .args_json(serde_json::json!({ "account_id": anon.id().to_string() }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(anon_balance.0, NearToken::from_near(90).as_yoctonear());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn verify_migrate_from_v1() -> anyhow::Result<()> {
+    let worker: Worker<Sandbox> = near_workspaces::sandbox().await?;
+    let (_, ft_contract, owner, _) = init(&worker).await?;
+
+    let contract = worker.dev_deploy(&std::fs::read(CONTRACT_V1_WASM_FILEPATH)?).await?;
+    let _ = contract
+        .call("new")
+        .args_json(serde_json::json!({
+            "owner_id": owner.id().to_string(),
+            "token_id": ft_contract.id().to_string()
+        }))
+        .gas(NearGas::from_tgas(100))
+        .transact()
+        .await?;
+    let _ = owner
+        .call(ft_contract.id(), "storage_deposit")
+        .args_json(serde_json::json!({ "account_id": contract.id().to_string() }))
+        .gas(NearGas::from_tgas(100))
+        .deposit(NearToken::from_yoctonear(1250000000000000000000))
+        .transact()
+        .await?;
+
+    // owner stakes 100 tokens with the old code
+    let amount_100_tokens = U128::from(NearToken::from_near(100).as_yoctonear());
+    let _ = owner
+        .call(ft_contract.id(), "ft_transfer_call")
+        .args_json(serde_json::json!({
+            "receiver_id": contract.id().to_string(),
+            "amount": amount_100_tokens,
+            "msg": "\"Stake\""
+        }))
+        .max_gas()
+        .deposit(ONE_YOCTO)
+        .transact()
+        .await?;
+    let shares_before: U128 = contract
+        .call("get_shares")
+        .args_json(serde_json::json!({ "account_id": owner.id().to_string() }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(shares_before, amount_100_tokens);
+
+    let upgrade = owner
+        .call(contract.id(), "upgrade")
+        .args(std::fs::read(CONTRACT_WASM_FILEPATH)?)
+        .max_gas()
+        .transact()
+        .await?;
+    println!("upgrade: {:#?}", upgrade.logs());
+    assert!(upgrade.is_success());
+
+    // shares and the total are kept, the staked value becomes the cost basis
+    let shares: U128 = contract
+        .call("get_shares")
+        .args_json(serde_json::json!({ "account_id": owner.id().to_string() }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(shares, shares_before);
+    let total_staked: U128 = contract
+        .call("get_total_staked")
+        .args_json(serde_json::json!({}))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(total_staked, amount_100_tokens);
+    let cost_basis: U128 = contract
+        .call("get_cost_basis")
+        .args_json(serde_json::json!({ "account_id": owner.id().to_string() }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(cost_basis, amount_100_tokens);
+
+    // the shares count as boost weight right after the upgrade
+    let boost: serde_json::Value = contract
+        .call("get_boost")
+        .args_json(serde_json::json!({ "account_id": owner.id().to_string() }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(boost["weight"], serde_json::json!(amount_100_tokens));
+
+    // the converted account unstakes with the new code
+    let owner_balance_before: U128 = ft_contract
+        .call("ft_balance_of")
+        .args_json(serde_json::json!({ "account_id": owner.id().to_string() }))
+        .view()
+        .await?
+        .json()?;
+    let amount_50_tokens = U128::from(NearToken::from_near(50).as_yoctonear());
+    let unstake = owner
+        .call(contract.id(), "unstake")
+        .args_json(serde_json::json!({ "amount": amount_50_tokens }))
+        .deposit(ONE_YOCTO)
+        .max_gas()
+        .transact()
+        .await?;
+    println!("unstake: {:#?}", unstake.logs());
+    assert!(unstake.is_success());
+    let owner_balance: U128 = ft_contract
+        .call("ft_balance_of")
+        .args_json(serde_json::json!({ "account_id": owner.id().to_string() }))
+        .view()
+        .await?
+        .json()?;
+    assert_eq!(owner_balance.0 - owner_balance_before.0, amount_50_tokens.0);
+
+    Ok(())
+}
@@ -0,0 +1,55 @@
+use crate::*;
+
+impl Contract {
+    fn code_key(code_version: CodeVersion) -> Vec<u8> {
+        [b"code".as_ref(), &code_version.to_le_bytes()].concat()
+    }
+
+    pub(crate) fn internal_get_code(&self, code_version: CodeVersion) -> Vec<u8> {
+        env::storage_read(&Self::code_key(code_version)).expect("ERR_NO_CODE")
+    }
+
+    pub(crate) fn internal_latest_code_version(&self) -> CodeVersion {
+        assert!(!self.code_hashes.is_empty(), "ERR_NO_CODE");
+        (self.code_hashes.len() - 1) as CodeVersion
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Store the staking contract code passed as raw input, it becomes the latest code version.
+    /// Requirements:
+    /// * Can only be called by the owner.
+    /// * Attached deposit should cover the storage of the code.
+    #[payable]
+    pub fn store_code(&mut self) -> CodeVersion {
+        self.assert_owner();
+        let initial_storage_usage = env::storage_usage();
+
+        let code = env::input().expect("ERR_NO_CODE");
+        let code_version = self.code_hashes.len() as CodeVersion;
+        env::storage_write(&Self::code_key(code_version), &code);
+        self.code_hashes.push(&env::sha256_array(&code));
+
+        let storage_cost = (env::storage_usage() - initial_storage_usage) as Balance * env::storage_byte_cost();
+        assert!(env::attached_deposit() >= storage_cost, "ERR_NOT_ENOUGH_DEPOSIT_FOR_STORAGE");
+
+        log!("Stored code version {}", code_version);
+        code_version
+    }
+
+    pub fn get_latest_code_version(&self) -> Option<CodeVersion> {
+        if self.code_hashes.is_empty() {
+            None
+        } else {
+            Some(self.internal_latest_code_version())
+        }
+    }
+
+    /// sha256 of the stored code, in hex
+    pub fn get_code_hash(&self, code_version: CodeVersion) -> Option<String> {
+        self.code_hashes
+            .get(code_version as u64)
+            .map(|hash| hash.iter().map(|byte| format!("{:02x}", byte)).collect())
+    }
+}
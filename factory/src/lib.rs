@@ -0,0 +1,78 @@
+// near_bindgen generates an extra function with the contract as an argument
+#![allow(clippy::too_many_arguments)]
+
+use near_sdk::json_types::U128;
+use near_sdk::{
+    borsh::{self, BorshDeserialize, BorshSerialize},
+    collections::{UnorderedMap, Vector},
+    env, ext_contract, log, near_bindgen,
+    serde::{Deserialize, Serialize},
+    AccountId, Balance, BorshStorageKey, CryptoHash, Gas, PanicOnDefault, Promise,
+};
+
+pub type WrappedBalance = U128;
+pub type CodeVersion = u32;
+
+mod code;
+mod pools;
+
+#[derive(BorshSerialize, BorshStorageKey)]
+enum StorageKey {
+    CodeHashes,
+    Pools,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct PoolInfo {
+    pub name: String,
+    pub icon: Option<String>,
+    pub token_id: AccountId,
+    pub owner_id: AccountId,
+    /// version of the staking code deployed to the pool
+    pub code_version: CodeVersion,
+}
+
+#[near_bindgen]
+#[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
+pub struct Contract {
+    // admin account to configure this contract
+    owner_id: AccountId,
+    // sha256 of every stored staking code, the index is a code version
+    pub code_hashes: Vector<CryptoHash>,
+    // every pool deployed by the factory
+    pub pools: UnorderedMap<AccountId, PoolInfo>,
+}
+
+#[near_bindgen]
+impl Contract {
+    #[init]
+    pub fn new(owner_id: AccountId) -> Self {
+        Self {
+            owner_id,
+            code_hashes: Vector::new(StorageKey::CodeHashes),
+            pools: UnorderedMap::new(StorageKey::Pools),
+        }
+    }
+
+    pub fn set_owner(&mut self, owner_id: AccountId) {
+        self.assert_owner();
+        self.owner_id = owner_id;
+    }
+
+    pub fn get_owner(&self) -> AccountId {
+        self.owner_id.clone()
+    }
+
+    pub(crate) fn assert_owner(&self) {
+        assert_eq!(env::predecessor_account_id(), self.owner_id, "ERR_NOT_AN_OWNER");
+    }
+}
+
+#[ext_contract(ext_self)]
+pub trait ExtSelf {
+    fn callback_post_create_pool(&mut self, pool_id: AccountId, pool: PoolInfo, predecessor_id: AccountId, deposit: WrappedBalance)
+        -> bool;
+    fn callback_post_upgrade_pool(&mut self, pool_id: AccountId, code_version: CodeVersion);
+}
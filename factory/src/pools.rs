@@ -0,0 +1,211 @@
+use crate::*;
+use near_sdk::serde_json::json;
+use near_sdk::{is_promise_success, StorageUsage};
+use std::convert::TryFrom;
+
+/// Storage on top of the staking code for the pool account and its initial state
+pub const POOL_STATE_STORAGE_BYTES: StorageUsage = 50_000;
+
+const GAS_FOR_POOL_INIT: Gas = Gas(Gas::ONE_TERA.0 * 10);
+const GAS_FOR_POOL_SETUP: Gas = Gas(Gas::ONE_TERA.0 * 5);
+const GAS_FOR_AFTER_CREATE_POOL: Gas = Gas(Gas::ONE_TERA.0 * 10);
+const GAS_FOR_UPGRADE_POOL: Gas = Gas(Gas::ONE_TERA.0 * 60);
+const GAS_FOR_AFTER_UPGRADE_POOL: Gas = Gas(Gas::ONE_TERA.0 * 5);
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct PoolView {
+    pub pool_id: AccountId,
+    #[serde(flatten)]
+    pub info: PoolInfo,
+}
+
+impl Contract {
+    pub(crate) fn internal_min_pool_deposit(code_len: usize) -> Balance {
+        (code_len as Balance + POOL_STATE_STORAGE_BYTES as Balance) * env::storage_byte_cost()
+    }
+
+    /// Storage cost of the registry entry of `pool`, measured by writing it and removing it again
+    fn internal_registry_storage_cost(&mut self, pool_id: &AccountId, pool: &PoolInfo) -> Balance {
+        let initial_storage_usage = env::storage_usage();
+        self.pools.insert(pool_id, pool);
+        let storage_usage = env::storage_usage() - initial_storage_usage;
+        self.pools.remove(pool_id);
+        storage_usage as Balance * env::storage_byte_cost()
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Deploy a staking pool with the latest code to `<prefix>.<factory_account_id>`.
+    /// The factory sets the reward parameters first and then hands the pool to `owner_id`.
+    /// Requirements:
+    /// * Attached deposit should cover the registry entry of the pool in the factory,
+    ///   the rest goes to the pool account and should be at least `get_min_pool_deposit`.
+    #[payable]
+    pub fn create_pool(
+        &mut self,
+        prefix: String,
+        token_id: AccountId,
+        owner_id: AccountId,
+        name: String,
+        icon: Option<String>,
+        reward_per_sec: Option<WrappedBalance>,
+        reward_genesis_time_in_sec: Option<u32>,
+    ) -> Promise {
+        let deposit = env::attached_deposit();
+        let pool_id = AccountId::try_from(format!("{}.{}", prefix, env::current_account_id())).expect("ERR_ILLEGAL_PREFIX");
+        assert!(self.pools.get(&pool_id).is_none(), "ERR_POOL_EXISTS");
+
+        let code_version = self.internal_latest_code_version();
+        let code = self.internal_get_code(code_version);
+        let pool = PoolInfo {
+            name,
+            icon,
+            token_id: token_id.clone(),
+            owner_id: owner_id.clone(),
+            code_version,
+        };
+        // the caller pays for the registry entry, the factory keeps that part of the deposit
+        let pool_deposit = deposit
+            .checked_sub(self.internal_registry_storage_cost(&pool_id, &pool))
+            .expect("ERR_NOT_ENOUGH_DEPOSIT");
+        assert!(
+            pool_deposit >= Self::internal_min_pool_deposit(code.len()),
+            "ERR_NOT_ENOUGH_DEPOSIT"
+        );
+
+        let factory_id = env::current_account_id();
+        let mut promise = Promise::new(pool_id.clone())
+            .create_account()
+            .transfer(pool_deposit)
+            .deploy_contract(code)
+            .function_call(
+                "new".to_string(),
+                json!({ "owner_id": factory_id, "token_id": token_id, "factory_id": factory_id })
+                    .to_string()
+                    .into_bytes(),
+                0,
+                GAS_FOR_POOL_INIT,
+            );
+        if let Some(reward_per_sec) = reward_per_sec {
+            promise = promise.function_call(
+                "modify_reward_per_sec".to_string(),
+                json!({ "reward_per_sec": reward_per_sec, "distribute_before_change": false })
+                    .to_string()
+                    .into_bytes(),
+                0,
+                GAS_FOR_POOL_SETUP,
+            );
+        }
+        if let Some(reward_genesis_time_in_sec) = reward_genesis_time_in_sec {
+            promise = promise.function_call(
+                "reset_reward_genesis_time_in_sec".to_string(),
+                json!({ "reward_genesis_time_in_sec": reward_genesis_time_in_sec })
+                    .to_string()
+                    .into_bytes(),
+                0,
+                GAS_FOR_POOL_SETUP,
+            );
+        }
+        promise = promise.function_call(
+            "set_owner".to_string(),
+            json!({ "owner_id": owner_id }).to_string().into_bytes(),
+            0,
+            GAS_FOR_POOL_SETUP,
+        );
+
+        promise.then(
+            ext_self::ext(env::current_account_id())
+                .with_static_gas(GAS_FOR_AFTER_CREATE_POOL)
+                .callback_post_create_pool(pool_id, pool, env::predecessor_account_id(), deposit.into()),
+        )
+    }
+
+    #[private]
+    pub fn callback_post_create_pool(
+        &mut self,
+        pool_id: AccountId,
+        pool: PoolInfo,
+        predecessor_id: AccountId,
+        deposit: WrappedBalance,
+    ) -> bool {
+        if is_promise_success() {
+            self.pools.insert(&pool_id, &pool);
+            log!("Pool {} created", pool_id);
+            true
+        } else {
+            // The pool account is not created, the pool deposit came back to the factory
+            // and the registry entry is not stored, the whole deposit is refunded.
+            Promise::new(predecessor_id).transfer(deposit.0);
+            log!("Pool {} creation failed, deposit refunded", pool_id);
+            false
+        }
+    }
+
+    /// Upgrade pools from the registry to the latest stored code, in batches to fit into gas.
+    /// Requirements:
+    /// * Can only be called by the owner.
+    pub fn upgrade_pools(&mut self, from_index: Option<u64>, limit: Option<u64>) {
+        self.assert_owner();
+        let code_version = self.internal_latest_code_version();
+        let code = self.internal_get_code(code_version);
+
+        let keys = self.pools.keys_as_vector();
+        let from_index = from_index.unwrap_or(0);
+        let to_index = std::cmp::min(keys.len(), from_index.saturating_add(limit.unwrap_or(keys.len())));
+        for index in from_index..to_index {
+            let pool_id = keys.get(index).unwrap();
+            if self.pools.get(&pool_id).unwrap().code_version == code_version {
+                continue;
+            }
+            Promise::new(pool_id.clone())
+                .function_call("upgrade".to_string(), code.clone(), 0, GAS_FOR_UPGRADE_POOL)
+                .then(
+                    ext_self::ext(env::current_account_id())
+                        .with_static_gas(GAS_FOR_AFTER_UPGRADE_POOL)
+                        .callback_post_upgrade_pool(pool_id, code_version),
+                );
+        }
+    }
+
+    #[private]
+    pub fn callback_post_upgrade_pool(&mut self, pool_id: AccountId, code_version: CodeVersion) {
+        if is_promise_success() {
+            let mut pool = self.pools.get(&pool_id).expect("ERR_NO_POOL");
+            pool.code_version = code_version;
+            self.pools.insert(&pool_id, &pool);
+            log!("Pool {} upgraded to code version {}", pool_id, code_version);
+        } else {
+            log!("Pool {} upgrade to code version {} failed", pool_id, code_version);
+        }
+    }
+
+    /// Deposit that goes to a new pool account: the latest staking code and POOL_STATE_STORAGE_BYTES of state
+    pub fn get_min_pool_deposit(&self) -> WrappedBalance {
+        let code = self.internal_get_code(self.internal_latest_code_version());
+        Self::internal_min_pool_deposit(code.len()).into()
+    }
+
+    pub fn get_pool(&self, pool_id: AccountId) -> Option<PoolInfo> {
+        self.pools.get(&pool_id)
+    }
+
+    pub fn get_pools(&self, from_index: Option<u64>, limit: Option<u64>) -> Vec<PoolView> {
+        let keys = self.pools.keys_as_vector();
+        let values = self.pools.values_as_vector();
+        let from_index = from_index.unwrap_or(0);
+        let to_index = std::cmp::min(keys.len(), from_index.saturating_add(limit.unwrap_or(keys.len())));
+        (from_index..to_index)
+            .map(|index| PoolView {
+                pool_id: keys.get(index).unwrap(),
+                info: values.get(index).unwrap(),
+            })
+            .collect()
+    }
+
+    pub fn get_number_of_pools(&self) -> u64 {
+        self.pools.len()
+    }
+}
@@ -0,0 +1,173 @@
+//! Exchange with the Ref Finance interface used by the staking contract, for sandbox tests.
+//! Tokens are deposited by `ft_transfer_call`, `swap` trades deposits at a fixed rate of the pool
+//! against the liquidity of the token out, and `withdraw` sends deposits back, restoring them if the transfer fails.
+
+use near_contract_standards::fungible_token::core::ext_ft_core;
+use near_contract_standards::fungible_token::receiver::FungibleTokenReceiver;
+use near_sdk::json_types::U128;
+use near_sdk::{
+    assert_one_yocto,
+    borsh::{self, BorshDeserialize, BorshSerialize},
+    collections::{LookupMap, Vector},
+    env, near_bindgen,
+    serde::{Deserialize, Serialize},
+    AccountId, Balance, BorshStorageKey, Gas, PanicOnDefault, Promise, PromiseOrValue, PromiseResult, ONE_YOCTO,
+};
+
+const GAS_FOR_FT_TRANSFER: Gas = Gas(Gas::ONE_TERA.0 * 20);
+const GAS_FOR_AFTER_FT_TRANSFER: Gas = Gas(Gas::ONE_TERA.0 * 10);
+const FULL_BASIS_POINTS: u128 = 10_000;
+/// `ft_transfer_call` msg which adds liquidity instead of a deposit
+const ADD_LIQUIDITY_MSG: &str = "AddLiquidity";
+
+#[derive(BorshSerialize, BorshStorageKey)]
+enum StorageKey {
+    Pools,
+    Deposits,
+    Liquidity,
+}
+
+/// Trades `token_in` to `token_out` at `rate_bps` of the amount in
+#[derive(BorshSerialize, BorshDeserialize, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Pool {
+    pub token_in: AccountId,
+    pub token_out: AccountId,
+    pub rate_bps: u32,
+}
+
+/// The same as in Ref Finance
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SwapAction {
+    pub pool_id: u64,
+    pub token_in: AccountId,
+    /// the output of the previous action if not set
+    pub amount_in: Option<U128>,
+    pub token_out: AccountId,
+    pub min_amount_out: U128,
+}
+
+#[near_bindgen]
+#[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
+pub struct Contract {
+    pools: Vector<Pool>,
+    // deposit of every account in every token
+    deposits: LookupMap<(AccountId, AccountId), Balance>,
+    // tokens to pay swaps with
+    liquidity: LookupMap<AccountId, Balance>,
+}
+
+#[near_bindgen]
+impl FungibleTokenReceiver for Contract {
+    fn ft_on_transfer(&mut self, sender_id: AccountId, amount: U128, msg: String) -> PromiseOrValue<U128> {
+        let token_id = env::predecessor_account_id();
+        if msg == ADD_LIQUIDITY_MSG {
+            let liquidity = self.liquidity.get(&token_id).unwrap_or_default();
+            self.liquidity.insert(&token_id, &(liquidity + amount.0));
+        } else {
+            self.internal_deposit(&sender_id, &token_id, amount.0);
+        }
+        PromiseOrValue::Value(U128(0))
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    #[init]
+    pub fn new() -> Self {
+        Self {
+            pools: Vector::new(StorageKey::Pools),
+            deposits: LookupMap::new(StorageKey::Deposits),
+            liquidity: LookupMap::new(StorageKey::Liquidity),
+        }
+    }
+
+    /// Add a pool, returns its id
+    pub fn add_pool(&mut self, token_in: AccountId, token_out: AccountId, rate_bps: u32) -> u64 {
+        self.pools.push(&Pool {
+            token_in,
+            token_out,
+            rate_bps,
+        });
+        self.pools.len() - 1
+    }
+
+    /// Execute the actions on the predecessor deposits, returns the amount out of the last one
+    #[payable]
+    #[allow(unused_variables)]
+    pub fn swap(&mut self, actions: Vec<SwapAction>, referral_id: Option<AccountId>) -> U128 {
+        assert!(!actions.is_empty(), "E72: at least one swap");
+        let account_id = env::predecessor_account_id();
+        let mut amount_out = 0;
+        for action in actions {
+            let pool = self.pools.get(action.pool_id).expect("E85: invalid pool");
+            assert!(
+                pool.token_in == action.token_in && pool.token_out == action.token_out,
+                "E10: illegal token"
+            );
+            let amount_in = action.amount_in.map(|amount| amount.0).unwrap_or(amount_out);
+            amount_out = amount_in * pool.rate_bps as u128 / FULL_BASIS_POINTS;
+            assert!(amount_out >= action.min_amount_out.0, "E68: slippage error");
+
+            let liquidity = self.liquidity.get(&pool.token_out).unwrap_or_default();
+            assert!(liquidity >= amount_out, "E69: not enough liquidity");
+            self.liquidity.insert(&pool.token_out, &(liquidity - amount_out));
+            let liquidity = self.liquidity.get(&pool.token_in).unwrap_or_default();
+            self.liquidity.insert(&pool.token_in, &(liquidity + amount_in));
+
+            self.internal_withdraw(&account_id, &pool.token_in, amount_in);
+            self.internal_deposit(&account_id, &pool.token_out, amount_out);
+        }
+        amount_out.into()
+    }
+
+    /// Send `amount` of the predecessor deposit, like Ref Finance a failed transfer goes back to the deposit
+    /// and the withdraw still succeeds
+    #[payable]
+    #[allow(unused_variables)]
+    pub fn withdraw(&mut self, token_id: AccountId, amount: U128, unregister: Option<bool>) -> Promise {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        self.internal_withdraw(&account_id, &token_id, amount.0);
+        ext_ft_core::ext(token_id.clone())
+            .with_attached_deposit(ONE_YOCTO)
+            .with_static_gas(GAS_FOR_FT_TRANSFER)
+            .ft_transfer(account_id.clone(), amount, None)
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_AFTER_FT_TRANSFER)
+                    .callback_post_withdraw(account_id, token_id, amount),
+            )
+    }
+
+    #[private]
+    pub fn callback_post_withdraw(&mut self, account_id: AccountId, token_id: AccountId, amount: U128) {
+        if let PromiseResult::Failed = env::promise_result(0) {
+            self.internal_deposit(&account_id, &token_id, amount.0);
+        }
+    }
+
+    pub fn get_deposit(&self, account_id: AccountId, token_id: AccountId) -> U128 {
+        self.deposits.get(&(account_id, token_id)).unwrap_or_default().into()
+    }
+
+    pub fn get_pool(&self, pool_id: u64) -> Option<Pool> {
+        self.pools.get(pool_id)
+    }
+}
+
+impl Contract {
+    fn internal_deposit(&mut self, account_id: &AccountId, token_id: &AccountId, amount: Balance) {
+        let key = (account_id.clone(), token_id.clone());
+        let deposit = self.deposits.get(&key).unwrap_or_default();
+        self.deposits.insert(&key, &(deposit + amount));
+    }
+
+    fn internal_withdraw(&mut self, account_id: &AccountId, token_id: &AccountId, amount: Balance) {
+        let key = (account_id.clone(), token_id.clone());
+        let deposit = self.deposits.get(&key).unwrap_or_default();
+        assert!(deposit >= amount, "E22: not enough tokens in deposit");
+        self.deposits.insert(&key, &(deposit - amount));
+    }
+}
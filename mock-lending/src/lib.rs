@@ -0,0 +1,98 @@
+//! Lending market with the interface used by the staking contract strategy, for sandbox tests.
+//! Tokens are supplied by `ft_transfer_call`, `accrue` pays interest from the reserve,
+//! and `withdraw` sends supplied tokens back, restoring them if the transfer fails.
+
+use near_contract_standards::fungible_token::core::ext_ft_core;
+use near_contract_standards::fungible_token::receiver::FungibleTokenReceiver;
+use near_sdk::json_types::U128;
+use near_sdk::{
+    assert_one_yocto,
+    borsh::{self, BorshDeserialize, BorshSerialize},
+    collections::LookupMap,
+    env, near_bindgen, AccountId, Balance, BorshStorageKey, Gas, PanicOnDefault, Promise, PromiseOrValue, PromiseResult, ONE_YOCTO,
+};
+
+const GAS_FOR_FT_TRANSFER: Gas = Gas(Gas::ONE_TERA.0 * 20);
+const GAS_FOR_AFTER_FT_TRANSFER: Gas = Gas(Gas::ONE_TERA.0 * 10);
+/// `ft_transfer_call` msg which funds the reserve instead of a supply
+const ADD_YIELD_MSG: &str = "AddYield";
+
+#[derive(BorshSerialize, BorshStorageKey)]
+enum StorageKey {
+    Balances,
+}
+
+#[near_bindgen]
+#[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
+pub struct Contract {
+    token_id: AccountId,
+    // supplied tokens with interest of every account
+    balances: LookupMap<AccountId, Balance>,
+    // tokens to pay interest with
+    reserve: Balance,
+}
+
+#[near_bindgen]
+impl FungibleTokenReceiver for Contract {
+    fn ft_on_transfer(&mut self, sender_id: AccountId, amount: U128, msg: String) -> PromiseOrValue<U128> {
+        assert_eq!(env::predecessor_account_id(), self.token_id, "ERR_ILLEGAL_TOKEN");
+        if msg == ADD_YIELD_MSG {
+            self.reserve += amount.0;
+        } else {
+            let balance = self.balances.get(&sender_id).unwrap_or_default();
+            self.balances.insert(&sender_id, &(balance + amount.0));
+        }
+        PromiseOrValue::Value(U128(0))
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    #[init]
+    pub fn new(token_id: AccountId) -> Self {
+        Self {
+            token_id,
+            balances: LookupMap::new(StorageKey::Balances),
+            reserve: 0,
+        }
+    }
+
+    /// Move `amount` of the reserve to the balance of `account_id` as interest
+    pub fn accrue(&mut self, account_id: AccountId, amount: U128) {
+        assert!(self.reserve >= amount.0, "ERR_NOT_ENOUGH_RESERVE");
+        self.reserve -= amount.0;
+        let balance = self.balances.get(&account_id).unwrap_or_default();
+        self.balances.insert(&account_id, &(balance + amount.0));
+    }
+
+    /// Send `amount` of the predecessor balance, a failed transfer goes back to the balance
+    #[payable]
+    pub fn withdraw(&mut self, amount: U128) -> Promise {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let balance = self.balances.get(&account_id).unwrap_or_default();
+        assert!(balance >= amount.0, "ERR_NOT_ENOUGH_BALANCE");
+        self.balances.insert(&account_id, &(balance - amount.0));
+        ext_ft_core::ext(self.token_id.clone())
+            .with_attached_deposit(ONE_YOCTO)
+            .with_static_gas(GAS_FOR_FT_TRANSFER)
+            .ft_transfer(account_id.clone(), amount, None)
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_AFTER_FT_TRANSFER)
+                    .callback_post_withdraw(account_id, amount),
+            )
+    }
+
+    #[private]
+    pub fn callback_post_withdraw(&mut self, account_id: AccountId, amount: U128) {
+        if let PromiseResult::Failed = env::promise_result(0) {
+            let balance = self.balances.get(&account_id).unwrap_or_default();
+            self.balances.insert(&account_id, &(balance + amount.0));
+        }
+    }
+
+    pub fn get_balance(&self, account_id: AccountId) -> U128 {
+        self.balances.get(&account_id).unwrap_or_default().into()
+    }
+}